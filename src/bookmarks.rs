@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::command_definitions::CommandExecutionTemplate;
+use crate::error::{Error, Result};
+use crate::file_handling;
+
+/// Runs saved with `rc bookmark add`, by name. Each is the whole execution, with its parameter
+/// values and environment, so it's run exactly as it was even if the command definition changes.
+pub type Bookmarks = BTreeMap<String, CommandExecutionTemplate>;
+
+/// Read the bookmarks file, there are none if it does not exist.
+pub fn read_bookmarks(bookmarks_path: &str) -> Result<Bookmarks> {
+    if !Path::new(bookmarks_path).exists() {
+        return Ok(Bookmarks::new());
+    }
+
+    let bookmarks = fs::read_to_string(bookmarks_path)
+        .map_err(|e| Error::io_error("bookmarks".to_string(), bookmarks_path.to_string(), e))?;
+
+    serde_yaml::from_str(&bookmarks).map_err(|e| {
+        Error::yaml_error(
+            "reading".to_string(),
+            "bookmarks".to_string(),
+            bookmarks_path.to_string(),
+            e,
+        )
+    })
+}
+
+fn write_bookmarks(bookmarks_path: &str, bookmarks: &Bookmarks) -> Result<()> {
+    let bookmarks = serde_yaml::to_string(bookmarks).map_err(|e| {
+        Error::yaml_error(
+            "writing".to_string(),
+            "bookmarks".to_string(),
+            bookmarks_path.to_string(),
+            e,
+        )
+    })?;
+
+    file_handling::write_atomically("bookmarks", bookmarks_path, &bookmarks)
+}
+
+/// Save the run as a bookmark. An existing bookmark with the same name is only replaced if
+/// `force` is given.
+pub fn add_bookmark(
+    bookmarks_path: &str,
+    name: &str,
+    execution_context: &CommandExecutionTemplate,
+    force: bool,
+) -> Result<()> {
+    let _lock = file_handling::lock_file("bookmarks", bookmarks_path)?;
+    let mut bookmarks = read_bookmarks(bookmarks_path)?;
+
+    if !force && bookmarks.contains_key(name) {
+        return Err(Error::BookmarkExists(name.to_string()));
+    }
+
+    bookmarks.insert(name.to_string(), execution_context.clone());
+    write_bookmarks(bookmarks_path, &bookmarks)
+}
+
+pub fn get_bookmark(bookmarks_path: &str, name: &str) -> Result<CommandExecutionTemplate> {
+    read_bookmarks(bookmarks_path)?
+        .remove(name)
+        .ok_or_else(|| Error::UnknownBookmark(name.to_string()))
+}
+
+pub fn remove_bookmark(bookmarks_path: &str, name: &str) -> Result<()> {
+    let _lock = file_handling::lock_file("bookmarks", bookmarks_path)?;
+    let mut bookmarks = read_bookmarks(bookmarks_path)?;
+
+    if bookmarks.remove(name).is_none() {
+        return Err(Error::UnknownBookmark(name.to_string()));
+    }
+
+    write_bookmarks(bookmarks_path, &bookmarks)
+}
+
+/// Print each bookmark's name and command, with the parameter values it was saved with.
+pub fn print_bookmarks(bookmarks: &Bookmarks) {
+    if bookmarks.is_empty() {
+        println!("There are no bookmarks, add the last command run with `rc bookmark add <name>`.");
+        return;
+    }
+
+    for (name, execution_context) in bookmarks {
+        println!("{name}: {execution_context}");
+
+        if let Some(template_context) = &execution_context.template_context {
+            let values = template_context
+                .iter()
+                .sorted()
+                .map(|(key, value)| format!("{key}={value}"))
+                .join(" ");
+            println!("    {values}");
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::command_definitions::{
+    get_command_index, CommandDefinition, CommandExecutionTemplate, ParameterDefinition,
+};
+use crate::error::{Error, Result};
+use crate::execution::{self, PreparedCommand};
+use crate::interpolation::{build_default_lookup, PreparedTemplate, ResolvedParameters};
+use crate::secrets::ConfigValue;
+use crate::settings::ProfileDefinition;
+
+/// A command with the commands to run after it: `and_then` if it succeeds, or `or_else` if it
+/// fails. These can have commands chained after them too.
+#[derive(Debug, Clone)]
+pub struct Chain {
+    pub execution_context: CommandExecutionTemplate,
+    /// Defaults of the command's parameters, which the commands before it may not have.
+    defaults: Option<HashMap<String, String>>,
+    pub and_then: Option<Box<Chain>>,
+    pub or_else: Option<Box<Chain>>,
+}
+
+/// The parameters used by the command, its working directory and pod.
+pub fn get_command_tokens(execution_context: &CommandExecutionTemplate) -> Result<HashSet<String>> {
+    let template = PreparedTemplate::parse(
+        &execution_context.command,
+        execution_context.working_directory.as_ref(),
+    )?;
+    let mut tokens = template.tokens().clone();
+
+    if let Some(kubectl) = &execution_context.kubectl {
+        tokens.extend(kubectl.tokens()?);
+    }
+
+    Ok(tokens)
+}
+
+/// Build the chain for the command with the ID, run after the command `from`. `chain_ids` are
+/// the commands before it in the chain, so a command that leads back to itself can be found.
+fn build_next(
+    id: Option<&String>,
+    from: &CommandExecutionTemplate,
+    command_definitions: &[CommandDefinition],
+    profile: Option<&ProfileDefinition>,
+    chain_ids: &mut Vec<String>,
+) -> Result<Option<Box<Chain>>> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+
+    let command_definition = get_command_index(command_definitions, id)
+        .map(|index| &command_definitions[index])
+        .map_err(|_| Error::UnknownChainedCommand {
+            command: from.history_key(),
+            chained: id.clone(),
+        })?;
+
+    let history_key = command_definition.history_key();
+    if chain_ids.contains(&history_key) {
+        return Err(Error::CommandChainCycle(format!(
+            "{}` -> `{history_key}",
+            chain_ids.join("` -> `")
+        )));
+    }
+
+    let mut execution_context =
+        CommandExecutionTemplate::from_command_definition(command_definition);
+    if let Some(profile) = profile {
+        profile.apply_environment(&mut execution_context.environment);
+    }
+
+    chain_ids.push(history_key);
+    let chain = build_chain(
+        execution_context,
+        build_default_lookup(&command_definition.parameters),
+        command_definitions,
+        profile,
+        chain_ids,
+    )?;
+    chain_ids.pop();
+
+    Ok(Some(Box::new(chain)))
+}
+
+fn build_chain(
+    execution_context: CommandExecutionTemplate,
+    defaults: Option<HashMap<String, String>>,
+    command_definitions: &[CommandDefinition],
+    profile: Option<&ProfileDefinition>,
+    chain_ids: &mut Vec<String>,
+) -> Result<Chain> {
+    let and_then = build_next(
+        execution_context.and_then.as_ref(),
+        &execution_context,
+        command_definitions,
+        profile,
+        chain_ids,
+    )?;
+    let or_else = build_next(
+        execution_context.or_else.as_ref(),
+        &execution_context,
+        command_definitions,
+        profile,
+        chain_ids,
+    )?;
+
+    Ok(Chain {
+        execution_context,
+        defaults,
+        and_then,
+        or_else,
+    })
+}
+
+impl Chain {
+    /// Follow the command's `and_then` and `or_else` to the commands with those IDs, and theirs.
+    /// The profile's environment is applied to each of them, as it is to the command.
+    pub fn build(
+        execution_context: &CommandExecutionTemplate,
+        command_definitions: &[CommandDefinition],
+        profile: Option<&ProfileDefinition>,
+    ) -> Result<Chain> {
+        build_chain(
+            execution_context.clone(),
+            None,
+            command_definitions,
+            profile,
+            &mut vec![execution_context.history_key()],
+        )
+    }
+
+    fn next_commands(&self) -> impl Iterator<Item = &Chain> {
+        self.and_then.iter().chain(&self.or_else).map(Box::as_ref)
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.next_commands().next().is_some()
+    }
+
+    /// The chained commands after this one, and theirs.
+    pub fn chained_commands(&self) -> Vec<&Chain> {
+        self.next_commands()
+            .flat_map(|next| std::iter::once(next).chain(next.chained_commands()))
+            .collect()
+    }
+
+    /// The parameters used by the chained commands, which are filled in along with the command's.
+    pub fn chained_tokens(&self) -> Result<HashSet<String>> {
+        let mut tokens = HashSet::new();
+        for chain in self.chained_commands() {
+            tokens.extend(get_command_tokens(&chain.execution_context)?);
+        }
+        Ok(tokens)
+    }
+
+    /// Add the chained commands' parameters with their defaults, for the parameters the command
+    /// doesn't have a default for.
+    pub fn add_chained_parameters(&self, parameters: &mut ResolvedParameters) {
+        for chain in self.chained_commands() {
+            for (name, default) in chain.defaults.iter().flatten() {
+                let parameter = parameters.entry(name.clone()).or_default();
+                if parameter.default().is_none() {
+                    parameter.definition = Some(ParameterDefinition::new(
+                        name.clone(),
+                        Some(ConfigValue::Plain(default.clone())),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Fill in the parameters of the command with the values shared by the chain.
+    pub fn prepare(
+        &self,
+        template_context: &Option<HashMap<String, String>>,
+    ) -> Result<PreparedCommand> {
+        let template = PreparedTemplate::parse(
+            &self.execution_context.command,
+            self.execution_context.working_directory.as_ref(),
+        )?;
+
+        execution::prepare_execution(&self.execution_context, &template, template_context)
+    }
+
+    /// Describe the chained commands, indented under the command they run after.
+    fn describe_next(
+        &self,
+        template_context: &Option<HashMap<String, String>>,
+        depth: usize,
+        lines: &mut Vec<String>,
+    ) -> Result<()> {
+        let indent = "\t".repeat(depth);
+
+        for (next, when) in [(&self.and_then, "succeeds"), (&self.or_else, "fails")] {
+            if let Some(next) = next {
+                lines.push(format!("{indent}If it {when}, then:"));
+                lines.push(format!(
+                    "{indent}\t{}",
+                    next.prepare(template_context)?.command
+                ));
+                next.describe_next(template_context, depth + 1, lines)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Print the chained commands with their parameters filled in, e.g. for a dry run.
+    pub fn print_chained_commands(
+        &self,
+        template_context: &Option<HashMap<String, String>>,
+    ) -> Result<()> {
+        let mut lines = Vec::new();
+        self.describe_next(template_context, 0, &mut lines)?;
+
+        for line in lines {
+            println!("{line}");
+        }
+
+        Ok(())
+    }
+}
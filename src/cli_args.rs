@@ -1,18 +1,40 @@
-use clap::Parser;
+use clap::builder::BoolishValueParser;
+use clap::{Arg, ArgAction, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+use crate::export::ExportFormat;
+use crate::import::ImportSource;
+use crate::init::InitTemplate;
+use crate::schedule::ScheduleBackend;
+use crate::shell_integration::Shell;
+use crate::tmux::TmuxTarget;
 
 #[derive(Parser, Debug)] // requires `derive` feature
 #[command(term_width = 0)] // Just to make testing across clap features easier
 #[allow(clippy::struct_excessive_bools)] // silence clippy's warning on this struct
 pub(crate) struct Args {
     /// Path to the commands definition config file YAML.
-    #[arg(long, short = 'c')]
+    #[arg(long, short = 'c', global = true)]
     pub config_path: Option<String>,
 
     /// Path to the file that stores the interpolated last command.
-    #[arg(long, short = 'l')]
+    #[arg(long, short = 'l', global = true)]
     pub last_command_path: Option<String>,
 
-    /// Perform a dry run, which just prints out the command but does not execute it.
+    /// Profile from the settings to use, which can have its own commands, history and
+    /// environment variables.
+    #[arg(long, short = 'p', global = true, env = "RC_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Path to the settings file YAML.
+    #[arg(long, global = true)]
+    pub settings_path: Option<String>,
+
+    /// Path to the file that stores the history of executed commands.
+    #[arg(long, global = true)]
+    pub history_path: Option<String>,
+
+    /// Perform a dry run, which just prints out the command but does not execute it. With
+    /// `--output json`, what would be run is printed as JSON.
     #[arg(long, short = 'd', action)]
     pub dry_run: bool,
 
@@ -20,14 +42,414 @@ pub(crate) struct Args {
     #[arg(long, short = 'f', action)]
     pub force: bool,
 
+    /// Never prompt: a command must be given, parameters use their defaults and confirmation is
+    /// skipped. This is the default when output is not to a terminal, e.g. in CI or cron.
+    #[arg(long, action)]
+    pub non_interactive: bool,
+
+    /// Run the command in a new terminal window, instead of this one.
+    #[arg(long, short = 't', action)]
+    pub new_terminal: bool,
+
+    /// Run the command in a new tmux window, pane or session, named after the command's ID.
+    #[arg(long, value_enum)]
+    pub tmux: Option<TmuxTarget>,
+
+    /// Give a parameter's value, e.g. `env=prod`, so it isn't prompted for. Can be given more
+    /// than once.
+    #[arg(long, value_name = "NAME=VALUE")]
+    pub param: Vec<String>,
+
+    /// Run the command in the background, with its output written to a log, and return straight
+    /// away. See `rc jobs`.
+    #[arg(long, action, conflicts_with_all = ["new_terminal", "tmux", "watch", "each", "print_only", "stdin_from"])]
+    pub background: bool,
+
+    /// Run the command once for each value of a parameter, e.g. `host=web-01,web-02`. If given
+    /// more than once, the command is run for every combination of the values.
+    #[arg(long, value_name = "NAME=VALUES")]
+    pub each: Vec<String>,
+
+    /// How many runs of `--each` to execute at once.
+    #[arg(long, default_value_t = 1, requires = "each")]
+    pub parallel: usize,
+
+    /// Rerun the command whenever a file matching the glob (e.g. `src/**/*.rs`) changes. Can be
+    /// given more than once.
+    #[arg(long, value_name = "GLOB", conflicts_with_all = ["each", "new_terminal", "tmux"])]
+    pub watch: Vec<String>,
+
+    /// Read the command's input from this file, instead of what is piped to `rc`.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["each", "new_terminal", "tmux"])]
+    pub stdin_from: Option<String>,
+
+    /// Open the command in the editor once its parameters are filled in, to change it for this
+    /// run only. The changed command is what's recorded in the history.
+    #[arg(long, action, conflicts_with_all = ["each", "non_interactive"])]
+    pub edit_command: bool,
+
+    /// Print the command once its parameters are filled in, instead of running it, with its
+    /// working directory and environment so it can be run with `eval`. If stdout is captured
+    /// (e.g. `$(rc --print-only)`), the command list and prompts are shown on the terminal, so
+    /// only the command is captured.
+    #[arg(long, action, conflicts_with_all = ["new_terminal", "tmux", "watch"])]
+    pub print_only: bool,
+
+    /// Show the commands as a numbered list and read the number of the one to run, and prompt for
+    /// parameters one line at a time, instead of using the full screen command list and form. For
+    /// screen readers and terminals that can't show them.
+    #[arg(long, action)]
+    pub plain_select: bool,
+
+    /// Warn about problems in the command definitions when they're loaded, e.g. parameters that
+    /// aren't used or template tokens without a parameter definition.
+    #[arg(long, short = 'v', action)]
+    pub verbose: bool,
+
+    /// Clear the screen before each rerun with `--watch`.
+    #[arg(long, action, requires = "watch")]
+    pub clear: bool,
+
     /// Rerun the last command (do not show allow to select another).
     #[arg(long, short = 'r', action)]
     pub rerun_last_command: bool,
 
+    /// Rerun the given command with the parameters from its most recent run (in the history),
+    /// instead of the last command run.
+    #[arg(
+        long,
+        action,
+        requires = "command",
+        conflicts_with = "rerun_last_command"
+    )]
+    pub last: bool,
+
     /// Skip saving of this command as the last command to replay. Retains existing last command.
     #[arg(long, short = 's', action)]
     pub skip_command_save: bool,
 
+    /// Output format. `json` prints a machine-readable result, including the command's captured
+    /// output, instead of human-readable text.
+    #[arg(long, short = 'o', value_enum, default_value_t = OutputFormat::Human)]
+    pub output: OutputFormat,
+
+    /// When to use colors, in the command list, the parameter form and other output. `auto`
+    /// uses them if stdout is a terminal and the `NO_COLOR` environment variable isn't set.
+    #[arg(long, value_enum, default_value_t = ColorPolicy::Auto)]
+    pub color: ColorPolicy,
+
+    /// Index or ID of the command to run.
     #[arg(num_args(1))]
-    pub command_index: Option<usize>,
+    pub command: Option<String>,
+
+    /// Arguments to pass on to the command, given after `--`, e.g. `rc cargo-run -- --release`.
+    /// They're put where the command has `{args...}`, quoted as given.
+    #[arg(last = true, value_name = "ARGUMENTS")]
+    pub arguments: Vec<String>,
+
+    #[command(subcommand)]
+    pub subcommand: Option<SubCommand>,
+}
+
+/// Commands other than running a command. Without one, a command is selected and run.
+#[derive(Subcommand, Debug)]
+pub(crate) enum SubCommand {
+    /// Check the command definitions for errors and likely mistakes, without running anything.
+    Validate,
+
+    /// Create the command definitions, optionally from a set of example commands and importing
+    /// shell aliases and functions, or starting with a single example command.
+    Init {
+        /// Start with a built-in set of example commands, choosing which to keep.
+        #[arg(long, value_enum)]
+        template: Option<InitTemplate>,
+    },
+
+    /// Import commands from shell aliases and functions, or the shell history, choosing which
+    /// to add to the command definitions.
+    Import {
+        /// Where to import commands from.
+        #[arg(long, value_enum, default_value_t = ImportSource::Shell)]
+        from: ImportSource,
+
+        /// Files to read, instead of the usual shell config or history files.
+        #[arg(long)]
+        path: Vec<String>,
+    },
+
+    /// Download or update the shared command sources defined in the settings.
+    Sync,
+
+    /// Add a new command to the command definitions, typed in or starting from the clipboard or
+    /// the shell history. Placeholders like `{name}` or `{{ name }}` become parameters.
+    New {
+        /// The command to start from, instead of typing it.
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            conflicts_with_all = ["from_clipboard", "from_last_shell"]
+        )]
+        command: Vec<String>,
+
+        /// Start from the command on the clipboard.
+        #[arg(long, conflicts_with = "from_last_shell")]
+        from_clipboard: bool,
+
+        /// Start from the last command run in the shell (bash, zsh or fish), from its history
+        /// file.
+        #[arg(long)]
+        from_last_shell: bool,
+    },
+
+    /// Run a command that isn't in the command definitions, e.g. `rc run -- 'kubectl get pods -n
+    /// {ns}'`, prompting for its parameters as usual. Afterwards, it can be saved with an ID.
+    Run {
+        /// The command to run, which can use template tokens.
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// Print a script for the shell's config which binds a key to choose a command and insert it
+    /// at the cursor, e.g. `eval "$(rc shell-init zsh)"` in `~/.zshrc`.
+    ShellInit {
+        shell: Shell,
+
+        /// The key to bind, `ctrl-` or `alt-` and a letter.
+        #[arg(long, default_value = "ctrl-g")]
+        key: String,
+    },
+
+    /// Update the command definitions to the latest layout, keeping comments and a copy of the
+    /// original.
+    Migrate,
+
+    /// Put back the command definitions from before `rc` last changed them, e.g. with `new`,
+    /// `edit`, `remove` or `migrate`. Restoring again undoes it.
+    RestoreConfig,
+
+    /// Print the commands as shell aliases and functions, a justfile or a Makefile, so they can
+    /// be used without rust-cuts.
+    Export {
+        /// Format to export the commands in.
+        #[arg(long, value_enum, default_value_t = ExportFormat::Aliases)]
+        format: ExportFormat,
+    },
+
+    /// Show how many times each command has been run, how often it failed, when it was last run
+    /// and how long it usually takes.
+    Stats {
+        /// `json` prints the stats for other tools, such as dashboards.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+
+    /// Save runs of commands, with their parameter values and environment, to run again exactly
+    /// as they were.
+    Bookmark {
+        #[command(subcommand)]
+        command: BookmarkCommand,
+    },
+
+    /// Run a command on a schedule, with a crontab entry, launchd agent or systemd timer.
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
+    },
+
+    /// List the commands running in the background, started with `--background`.
+    Jobs,
+
+    /// Show a background job's output, following it until the job finishes. ctrl-c stops
+    /// following it, leaving the job running.
+    Attach {
+        /// Number of the job, or the ID of its command.
+        job: String,
+    },
+
+    /// Stop a background job.
+    Kill {
+        /// Number of the job, or the ID of its command.
+        job: String,
+    },
+
+    /// Manage the history of commands that have been run.
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+
+    /// Open the latest saved output of a command with `log_output`, in `$PAGER`.
+    Logs {
+        /// Index or ID of the command.
+        command: String,
+    },
+
+    /// Edit a command's definition in `$VISUAL` or `$EDITOR`, leaving the rest of the command
+    /// definitions (including comments) as they are.
+    Edit {
+        /// Index or ID of the command.
+        command: String,
+    },
+
+    /// Remove a command from the command definitions, leaving the rest (including comments) as
+    /// they are.
+    Remove {
+        /// Index or ID of the command.
+        command: String,
+
+        /// Remove the command without confirming.
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Show where a command is defined, its parameters, and what it would run with their
+    /// defaults.
+    Which {
+        /// Index or ID of the command.
+        command: String,
+    },
+
+    /// Search the commands' IDs, names, groups, tags and commands, printing the matches with the
+    /// best first. Exits with 1 if nothing matches, like `grep`.
+    Search {
+        /// Text to fuzzy match, as when filtering the command list.
+        query: String,
+
+        /// `json` prints the matches for scripts.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+
+    /// Write Markdown documentation of the commands, grouped by their tags, e.g. to commit to a
+    /// team wiki.
+    Docs {
+        /// File to write the documentation to, instead of printing it.
+        #[arg(long, value_name = "PATH")]
+        output: Option<String>,
+    },
+
+    /// Explain a command: each parameter with where it's used and its default, its environment,
+    /// how it's confirmed, what runs after it, and what it would run with the defaults.
+    Explain {
+        /// Index or ID of the command.
+        command: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum BookmarkCommand {
+    /// Save the last command that was run as a bookmark.
+    Add {
+        name: String,
+
+        /// Replace the bookmark if it already exists.
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Run a bookmark, with the parameter values and environment it was saved with.
+    Run { name: String },
+
+    /// List the bookmarks.
+    List,
+
+    /// Remove a bookmark.
+    Remove { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum ScheduleCommand {
+    /// Run a command on a schedule, e.g. `rc schedule add deploy-report --cron "0 9 * * MON"
+    /// --param env=prod`. It's run with `--non-interactive`, so its parameters must have defaults
+    /// or be given with `--param`.
+    Add {
+        /// ID of the command.
+        command: String,
+
+        /// When to run the command, as a cron expression: minute, hour, day of the month, month
+        /// and day of the week.
+        #[arg(long, value_name = "EXPRESSION")]
+        cron: String,
+
+        /// A parameter's value, e.g. `env=prod`. Can be given more than once.
+        #[arg(long, value_name = "NAME=VALUE")]
+        param: Vec<String>,
+
+        /// How to install the schedule, launchd on macOS and cron elsewhere if not given.
+        #[arg(long, value_enum)]
+        backend: Option<ScheduleBackend>,
+
+        /// Name of the schedule, the command's ID if not given.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Replace the schedule if it already exists.
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// List the schedules.
+    List,
+
+    /// Remove a schedule, uninstalling it.
+    Remove { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum HistoryCommand {
+    /// Remove old entries from the history, e.g. `rc history prune --older-than 90d --keep 500`.
+    /// Without either option, the limits in the `history` settings are used.
+    Prune {
+        /// Remove entries older than this, e.g. `90d`.
+        #[arg(long, value_name = "DURATION")]
+        older_than: Option<String>,
+
+        /// Keep at most this many of the most recent entries.
+        #[arg(long, value_name = "COUNT")]
+        keep: Option<usize>,
+    },
+}
+
+/// Prefix of the environment variables that give the flags, e.g. `RC_CONFIG_PATH` for
+/// `--config-path`.
+const ENV_PREFIX: &str = "RC_";
+
+/// Give the flag an environment variable named after it, unless it already has one. Switches
+/// accept `1`, `yes` or `on` as well as `true` from the environment.
+fn add_env(arg: Arg) -> Arg {
+    let id = arg.get_id().as_str();
+    if arg.is_positional() || arg.get_env().is_some() || id == "help" || id == "version" {
+        return arg;
+    }
+
+    let env = format!("{ENV_PREFIX}{}", id.to_uppercase());
+    match arg.get_action() {
+        ArgAction::SetTrue => arg.env(env).value_parser(BoolishValueParser::new()),
+        _ => arg.env(env),
+    }
+}
+
+impl Args {
+    /// Parse the arguments, with every flag also able to be given by an environment variable,
+    /// e.g. `RC_CONFIG_PATH=~/team.yml` for `--config-path` or `RC_FORCE=true` for `--force`.
+    /// Flags given as arguments take priority over the environment variables.
+    pub fn parse_with_env() -> Self {
+        let matches = Self::command().mut_args(add_env).get_matches();
+        Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    Human,
+    Json,
+}
+
+/// When to use colors.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ColorPolicy {
+    /// If stdout is a terminal and `NO_COLOR` isn't set.
+    Auto,
+    Always,
+    Never,
 }
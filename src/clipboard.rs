@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use log::debug;
+
+use crate::error::{Error, Result};
+
+/// Clipboard commands to try, in order, with their arguments for reading from stdin.
+const CLIPBOARD_COMMANDS: [(&str, &[&str]); 5] = [
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("clip", &[]),
+];
+
+/// Clipboard commands to try, in order, with their arguments for writing to stdout.
+const PASTE_COMMANDS: [(&str, &[&str]); 5] = [
+    ("pbpaste", &[]),
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-out"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("powershell", &["-NoProfile", "-Command", "Get-Clipboard"]),
+];
+
+/// Copy the text to the system clipboard, using the first clipboard command that is available.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    for (program, args) in CLIPBOARD_COMMANDS {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                debug!("Could not run clipboard command `{program}`: {e}");
+                continue;
+            }
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+
+        if child.wait()?.success() {
+            return Ok(());
+        }
+
+        debug!("Clipboard command `{program}` failed");
+    }
+
+    Err(Error::NoClipboard)
+}
+
+/// Read the text from the system clipboard, using the first clipboard command that is available.
+pub fn paste_from_clipboard() -> Result<String> {
+    for (program, args) in PASTE_COMMANDS {
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+
+        match output {
+            Ok(output) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+            }
+            Ok(_) => debug!("Clipboard command `{program}` failed"),
+            Err(e) => debug!("Could not run clipboard command `{program}`: {e}"),
+        }
+    }
+
+    Err(Error::NoClipboardPaste)
+}
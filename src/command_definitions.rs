@@ -1,20 +1,138 @@
 use crate::error::{Error, Result};
+use crate::kubectl::KubectlDefinition;
 use crossterm::style::Color;
-use std::collections::HashMap;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug, Clone)]
+use crate::container::ContainerDefinition;
+use crate::secrets::ConfigValue;
+use crate::tmux::TmuxTarget;
+use crate::version;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(from = "ParameterConfig", into = "ParameterConfig")]
 pub struct ParameterDefinition {
     pub name: String,
+    /// The default as it's shown and filled in, which is `!secret name` for a secret.
     pub default: Option<String>,
+    /// The name of the secret the default is, if it is one. It's only read when the command runs.
+    pub secret: Option<String>,
+}
+
+/// A parameter as it's written in the config.
+#[derive(Deserialize, Serialize)]
+struct ParameterConfig {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<ConfigValue>,
+}
+
+impl ParameterDefinition {
+    pub fn new(name: String, default: Option<ConfigValue>) -> Self {
+        Self {
+            name,
+            default: default.as_ref().map(ConfigValue::to_string),
+            secret: default
+                .as_ref()
+                .and_then(ConfigValue::secret_name)
+                .map(str::to_string),
+        }
+    }
+}
+
+impl From<ParameterConfig> for ParameterDefinition {
+    fn from(value: ParameterConfig) -> Self {
+        ParameterDefinition::new(value.name, value.default)
+    }
+}
+
+impl From<ParameterDefinition> for ParameterConfig {
+    fn from(value: ParameterDefinition) -> Self {
+        let default = match value.secret {
+            Some(name) => Some(ConfigValue::Secret(name)),
+            None => value.default.map(ConfigValue::Plain),
+        };
+
+        ParameterConfig {
+            name: value.name,
+            default,
+        }
+    }
+}
+
+/// The parameters followed by `overrides`, which replace the parameters with the same name.
+pub fn merge_parameters(
+    parameters: &[ParameterDefinition],
+    overrides: &[ParameterDefinition],
+) -> Vec<ParameterDefinition> {
+    parameters
+        .iter()
+        .filter(|parameter| {
+            overrides
+                .iter()
+                .all(|override_parameter| override_parameter.name != parameter.name)
+        })
+        .chain(overrides)
+        .cloned()
+        .collect()
+}
+
+/// Whether a command should be confirmed before running.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmPolicy {
+    /// Always confirm, even if `--force` is given.
+    Always,
+    /// Never confirm, the command runs as soon as it is selected.
+    Never,
+    /// Confirm unless `--force` is given.
+    #[default]
+    Default,
+}
+
+/// Operating systems a command can be limited to.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Linux,
+    Macos,
+    Windows,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl Platform {
+    /// The platform `rc` is running on, if it is one commands can be limited to.
+    pub fn current() -> Option<Self> {
+        match std::env::consts::OS {
+            "linux" => Some(Self::Linux),
+            "macos" => Some(Self::Macos),
+            "windows" => Some(Self::Windows),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(match self {
+            Self::Linux => "linux",
+            Self::Macos => "macos",
+            Self::Windows => "windows",
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ColorDefinition {
+    #[serde(skip_serializing_if = "Option::is_none")]
     rgb: Option<(u8, u8, u8)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     ansi: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
 }
 
@@ -59,20 +177,106 @@ impl ColorDefinition {
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// Parameter values and environment variables for one variant of a command, e.g. the
+/// environment it deploys to. Each variant is listed as a command of its own.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct VariantDefinition {
+    /// Values used as the defaults of the parameters, by parameter name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<HashMap<String, ConfigValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<HashMap<String, ConfigValue>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct CommandMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub foreground_color: Option<ColorDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub background_color: Option<ColorDefinition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// Optional fields are left out when serialized, so written definitions only contain what is set.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct CommandDefinition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Other IDs the command can be run with, e.g. `d` for `deploy`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
+    /// ID of another command to inherit from. Its command is put before this one's, and its
+    /// parameters, environment and other fields are used where this one doesn't set them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
     pub command: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub working_directory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub parameters: Option<Vec<ParameterDefinition>>,
-    pub environment: Option<HashMap<String, String>>,
+    /// Names of the config's `parameter_sets` to add to the parameters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameter_sets: Option<Vec<String>>,
+    /// Variants of the command by name, which are listed instead of it. Each is run with the ID
+    /// followed by `@` and the variant's name, e.g. `deploy@prod`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variants: Option<BTreeMap<String, VariantDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<HashMap<String, ConfigValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<CommandMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success_codes: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm: Option<ConfirmPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dangerous: Option<bool>,
+    /// Start the parameters from the values last entered for them, rather than their defaults.
+    /// Overrides the `remember_values` setting.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remember_values: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmux: Option<TmuxTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_output: Option<String>,
+    /// How long the command is expected to take at most, e.g. `10m`. A warning is shown if it
+    /// takes longer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warn_after: Option<String>,
+    /// Platforms the command can be run on, or `None` for all of them. It is hidden on others.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<Vec<Platform>>,
+    /// Versions of `rc` the command needs, e.g. `>=0.6` if it uses newer fields. It is hidden
+    /// on older versions, which don't try to read the rest of it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires_version: Option<String>,
+    /// Binaries that must be on the `PATH` to run the command, e.g. `docker`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires: Option<Vec<String>>,
+    /// Shown when a binary the command `requires` is missing, e.g. how to install it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_hint: Option<String>,
+    /// Container to run the command in, instead of on this machine.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<ContainerDefinition>,
+    /// Kubernetes pod to run the command in, with `kubectl exec`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kubectl: Option<KubectlDefinition>,
+    /// ID of a command to run after this one if it succeeds, with the same parameter values.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub and_then: Option<String>,
+    /// ID of a command to run after this one if it fails, e.g. to roll back, with the same
+    /// parameter values.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub or_else: Option<String>,
+    /// Name of the shared source the command came from, or `None` for local commands.
+    #[serde(skip)]
+    pub source: Option<String>,
 }
 
 fn color_from_metadata_attribute(
@@ -84,7 +288,183 @@ fn color_from_metadata_attribute(
     }
 }
 
+/// Key to identify a command across runs, e.g. in the history: its ID if it has one, otherwise
+/// its command template.
+fn history_key(id: &Option<String>, command: &[String]) -> String {
+    id.clone().unwrap_or_else(|| command.join(" "))
+}
+
 impl CommandDefinition {
+    pub fn history_key(&self) -> String {
+        history_key(&self.id, &self.command)
+    }
+
+    /// The command's ID followed by its aliases, which it can all be run with.
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        self.id.iter().chain(self.aliases.iter().flatten())
+    }
+
+    /// Whether the command needs a newer version of `rc` than this one.
+    pub fn needs_upgrade(&self) -> bool {
+        self.requires_version
+            .as_deref()
+            .is_some_and(|requirement| !version::is_supported(requirement).unwrap_or(true))
+    }
+
+    /// Whether the command can be run on the current platform, with this version of `rc`.
+    pub fn is_available(&self) -> bool {
+        if self.needs_upgrade() {
+            return false;
+        }
+
+        match &self.platforms {
+            Some(platforms) => {
+                Platform::current().is_some_and(|current| platforms.contains(&current))
+            }
+            None => true,
+        }
+    }
+
+    /// Error if the command can't be run on the current platform, or with this version of `rc`.
+    pub fn check_available(&self) -> Result<()> {
+        if self.is_available() {
+            return Ok(());
+        }
+
+        if let Some(requirement) = self
+            .requires_version
+            .as_ref()
+            .filter(|_| self.needs_upgrade())
+        {
+            return Err(Error::UpgradeNeeded {
+                command: self.history_key(),
+                requirement: requirement.clone(),
+            });
+        }
+
+        Err(Error::UnavailableOnPlatform {
+            command: self.history_key(),
+            platforms: self.platforms.iter().flatten().join(", "),
+        })
+    }
+
+    /// This command with what it doesn't set taken from `base`, the command it extends. The
+    /// command is appended to the base's, parameters replace the base's parameters with the same
+    /// name, and environment variables are added to the base's.
+    fn inherit(&self, base: &CommandDefinition) -> CommandDefinition {
+        let parameters = match (&base.parameters, &self.parameters) {
+            (Some(base_parameters), Some(parameters)) => {
+                Some(merge_parameters(base_parameters, parameters))
+            }
+            (base_parameters, parameters) => parameters.clone().or_else(|| base_parameters.clone()),
+        };
+
+        let environment = match (&base.environment, &self.environment) {
+            (Some(base_environment), Some(environment)) => {
+                let mut merged = base_environment.clone();
+                merged.extend(environment.clone());
+                Some(merged)
+            }
+            (base_environment, environment) => {
+                environment.clone().or_else(|| base_environment.clone())
+            }
+        };
+
+        CommandDefinition {
+            command: base.command.iter().chain(&self.command).cloned().collect(),
+            parameters,
+            environment,
+            group: self.group.clone().or_else(|| base.group.clone()),
+            tags: self.tags.clone().or_else(|| base.tags.clone()),
+            working_directory: self
+                .working_directory
+                .clone()
+                .or_else(|| base.working_directory.clone()),
+            metadata: self.metadata.clone().or_else(|| base.metadata.clone()),
+            success_codes: self
+                .success_codes
+                .clone()
+                .or_else(|| base.success_codes.clone()),
+            confirm: self.confirm.or(base.confirm),
+            dangerous: self.dangerous.or(base.dangerous),
+            remember_values: self.remember_values.or(base.remember_values),
+            tmux: self.tmux.or(base.tmux),
+            log_output: self.log_output.clone().or_else(|| base.log_output.clone()),
+            warn_after: self.warn_after.clone().or_else(|| base.warn_after.clone()),
+            platforms: self.platforms.clone().or_else(|| base.platforms.clone()),
+            requires_version: self
+                .requires_version
+                .clone()
+                .or_else(|| base.requires_version.clone()),
+            requires: self.requires.clone().or_else(|| base.requires.clone()),
+            install_hint: self
+                .install_hint
+                .clone()
+                .or_else(|| base.install_hint.clone()),
+            container: self.container.clone().or_else(|| base.container.clone()),
+            kubectl: self.kubectl.clone().or_else(|| base.kubectl.clone()),
+            and_then: self.and_then.clone().or_else(|| base.and_then.clone()),
+            or_else: self.or_else.clone().or_else(|| base.or_else.clone()),
+            variants: self.variants.clone().or_else(|| base.variants.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// The command for one of its variants, with the variant's parameter values as defaults and
+    /// its environment variables added.
+    fn variant(&self, variant_name: &str, variant: &VariantDefinition) -> CommandDefinition {
+        let mut parameters = self.parameters.clone().unwrap_or_default();
+        for (name, value) in variant.parameters.iter().flatten() {
+            match parameters
+                .iter_mut()
+                .find(|parameter| parameter.name == *name)
+            {
+                Some(parameter) => {
+                    *parameter = ParameterDefinition::new(name.clone(), Some(value.clone()));
+                }
+                None => {
+                    parameters.push(ParameterDefinition::new(name.clone(), Some(value.clone())))
+                }
+            }
+        }
+
+        let mut environment = self.environment.clone().unwrap_or_default();
+        environment.extend(variant.environment.clone().unwrap_or_default());
+
+        let description = self
+            .name
+            .clone()
+            .or_else(|| self.id.clone())
+            .unwrap_or_else(|| self.command.join(" "));
+
+        CommandDefinition {
+            id: self.id.as_ref().map(|id| format!("{id}@{variant_name}")),
+            aliases: self.aliases.as_ref().map(|aliases| {
+                aliases
+                    .iter()
+                    .map(|alias| format!("{alias}@{variant_name}"))
+                    .collect()
+            }),
+            name: Some(format!("{description} ({variant_name})")),
+            parameters: (!parameters.is_empty()).then_some(parameters),
+            environment: (!environment.is_empty()).then_some(environment),
+            variants: None,
+            ..self.clone()
+        }
+    }
+
+    /// The secret's name for each parameter whose default is a secret.
+    fn secret_parameters(&self) -> Option<BTreeMap<String, String>> {
+        let secret_parameters = self
+            .parameters
+            .iter()
+            .flatten()
+            .filter_map(|parameter| Some((parameter.name.clone(), parameter.secret.clone()?)))
+            .collect::<BTreeMap<String, String>>();
+
+        (!secret_parameters.is_empty()).then_some(secret_parameters)
+    }
+
     pub fn foreground_color(&self) -> Result<Option<Color>> {
         if let Some(metadata) = &self.metadata {
             color_from_metadata_attribute(&metadata.foreground_color)
@@ -104,21 +484,285 @@ impl CommandDefinition {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct CommandExecutionTemplate {
+    pub id: Option<String>,
     pub command: Vec<String>,
     pub working_directory: Option<String>,
     pub template_context: Option<HashMap<String, String>>,
-    pub environment: Option<HashMap<String, String>>,
+    pub environment: Option<HashMap<String, ConfigValue>>,
+    /// The secret's name for each parameter whose default is a secret.
+    pub secret_parameters: Option<BTreeMap<String, String>>,
+    pub success_codes: Option<Vec<i32>>,
+    pub confirm: Option<ConfirmPolicy>,
+    pub dangerous: Option<bool>,
+    pub tmux: Option<TmuxTarget>,
+    pub log_output: Option<String>,
+    pub warn_after: Option<String>,
+    pub requires: Option<Vec<String>>,
+    pub install_hint: Option<String>,
+    pub container: Option<ContainerDefinition>,
+    pub kubectl: Option<KubectlDefinition>,
+    pub and_then: Option<String>,
+    pub or_else: Option<String>,
 }
 
 impl CommandExecutionTemplate {
     pub fn from_command_definition(value: &CommandDefinition) -> Self {
         Self {
+            id: value.id.clone(),
             command: value.command.clone(),
             working_directory: value.working_directory.clone(),
             template_context: None,
             environment: value.environment.clone(),
+            secret_parameters: value.secret_parameters(),
+            success_codes: value.success_codes.clone(),
+            confirm: value.confirm,
+            dangerous: value.dangerous,
+            tmux: value.tmux,
+            log_output: value.log_output.clone(),
+            warn_after: value.warn_after.clone(),
+            requires: value.requires.clone(),
+            install_hint: value.install_hint.clone(),
+            container: value.container.clone(),
+            kubectl: value.kubectl.clone(),
+            and_then: value.and_then.clone(),
+            or_else: value.or_else.clone(),
+        }
+    }
+
+    pub fn history_key(&self) -> String {
+        history_key(&self.id, &self.command)
+    }
+
+    /// If the command is marked as dangerous, the ID that must be typed to confirm running it.
+    pub fn dangerous_id(&self) -> Option<&str> {
+        if self.dangerous.unwrap_or(false) {
+            self.id.as_deref()
+        } else {
+            None
+        }
+    }
+}
+
+/// Find the index of the command to run, either from its index or its ID (or an alias).
+pub fn get_command_index(
+    command_definitions: &[CommandDefinition],
+    index_or_id: &str,
+) -> Result<usize> {
+    if let Ok(index) = index_or_id.parse::<usize>() {
+        if index >= command_definitions.len() {
+            return Err(Error::IndexOutOfRange(index));
+        }
+
+        return Ok(index);
+    }
+
+    if let Some(index) = command_definitions
+        .iter()
+        .position(|command_definition| command_definition.ids().any(|id| id == index_or_id))
+    {
+        return Ok(index);
+    }
+
+    // IDs that only differ by case can be different commands, so this is only used if there's
+    // just one
+    let case_insensitive_matches: Vec<usize> = command_definitions
+        .iter()
+        .positions(|command_definition| {
+            command_definition
+                .ids()
+                .any(|id| id.eq_ignore_ascii_case(index_or_id))
+        })
+        .collect();
+    if let [index] = case_insensitive_matches[..] {
+        return Ok(index);
+    }
+
+    Err(Error::CommandNotFound {
+        id: index_or_id.to_string(),
+        suggestions: suggest_command_ids(command_definitions, index_or_id),
+    })
+}
+
+/// How many IDs are suggested when no command has the ID given.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// IDs that `id` is likely a typo of (e.g. `deplyo` for `deploy`), closest first.
+fn get_typo_matches<'a>(command_definitions: &'a [CommandDefinition], id: &str) -> Vec<&'a String> {
+    let id = id.to_lowercase();
+    let max_distance = (id.chars().count() / 3).max(1);
+
+    command_definitions
+        .iter()
+        .flat_map(CommandDefinition::ids)
+        .unique()
+        .map(|other| {
+            (
+                strsim::damerau_levenshtein(&id, &other.to_lowercase()),
+                other,
+            )
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .sorted_by_key(|(distance, _)| *distance)
+        .map(|(_, other)| other)
+        .collect()
+}
+
+/// IDs that `id` may have been meant to be: those it's likely a typo of, closest first, then
+/// those it fuzzy matches (e.g. `dpl` for `deploy`), best first.
+pub fn suggest_command_ids(command_definitions: &[CommandDefinition], id: &str) -> Vec<String> {
+    // Very short IDs fuzzy match too many others to be useful
+    let matcher = SkimMatcherV2::default();
+    let fuzzy_matches = command_definitions
+        .iter()
+        .flat_map(CommandDefinition::ids)
+        .filter(|_| id.chars().count() >= 3)
+        .filter_map(|other| Some((matcher.fuzzy_match(other, id)?, other)))
+        .sorted_by_key(|(score, _)| -score)
+        .map(|(_, other)| other);
+
+    get_typo_matches(command_definitions, id)
+        .into_iter()
+        .chain(fuzzy_matches)
+        .unique()
+        .take(MAX_SUGGESTIONS)
+        .cloned()
+        .collect()
+}
+
+/// The ID `id` is likely a typo of, if there's only one, so it can be offered to be run instead.
+pub fn find_close_command_id(
+    command_definitions: &[CommandDefinition],
+    id: &str,
+) -> Option<String> {
+    match get_typo_matches(command_definitions, id)[..] {
+        [close_id] => Some(close_id.clone()),
+        _ => None,
+    }
+}
+
+/// Check that command IDs and aliases are unique, and that commands marked as dangerous have an
+/// ID (which must be typed to confirm running them).
+pub fn validate_command_ids(command_definitions: &[CommandDefinition]) -> Result<()> {
+    let mut seen_ids = HashSet::new();
+
+    for command_definition in command_definitions {
+        if command_definition.id.is_none() && command_definition.dangerous.unwrap_or(false) {
+            return Err(Error::DangerousCommandWithoutId(
+                command_definition.command.join(" "),
+            ));
+        }
+
+        for id in command_definition.ids() {
+            if id.parse::<usize>().is_ok() {
+                return Err(Error::NumericCommandId(id.clone()));
+            }
+
+            if !seen_ids.insert(id.as_str()) {
+                return Err(Error::DuplicateCommandId(id.clone()));
+            }
         }
     }
+
+    Ok(())
+}
+
+/// Replace the commands that have `variants` with a command for each variant.
+pub fn expand_variants(command_definitions: Vec<CommandDefinition>) -> Vec<CommandDefinition> {
+    command_definitions
+        .into_iter()
+        .flat_map(|command_definition| match &command_definition.variants {
+            Some(variants) if !variants.is_empty() => variants
+                .iter()
+                .map(|(variant_name, variant)| command_definition.variant(variant_name, variant))
+                .collect(),
+            _ => vec![command_definition],
+        })
+        .collect()
+}
+
+/// Resolve the command at `index`, and the commands it extends, which are stored in `resolved` so
+/// each is only resolved once. `extending` is the chain of commands being resolved, to find cycles.
+fn resolve_command(
+    index: usize,
+    command_definitions: &[CommandDefinition],
+    indexes_by_id: &HashMap<&str, usize>,
+    resolved: &mut Vec<Option<CommandDefinition>>,
+    extending: &mut Vec<usize>,
+) -> Result<CommandDefinition> {
+    if let Some(command_definition) = &resolved[index] {
+        return Ok(command_definition.clone());
+    }
+
+    let command_definition = &command_definitions[index];
+    let resolved_definition = match &command_definition.extends {
+        None => command_definition.clone(),
+        Some(base_id) => {
+            let base_index =
+                *indexes_by_id
+                    .get(base_id.as_str())
+                    .ok_or_else(|| Error::UnknownBaseCommand {
+                        command: command_definition.history_key(),
+                        base: base_id.clone(),
+                    })?;
+
+            extending.push(index);
+            if extending.contains(&base_index) {
+                return Err(Error::CommandExtendsCycle(
+                    extending
+                        .iter()
+                        .map(|i| command_definitions[*i].history_key())
+                        .chain([command_definitions[base_index].history_key()])
+                        .join("` -> `"),
+                ));
+            }
+            let base = resolve_command(
+                base_index,
+                command_definitions,
+                indexes_by_id,
+                resolved,
+                extending,
+            )?;
+            extending.pop();
+
+            command_definition.inherit(&base)
+        }
+    };
+
+    resolved[index] = Some(resolved_definition.clone());
+    Ok(resolved_definition)
+}
+
+/// Fill in the commands that have `extends` from the commands they extend, which can themselves
+/// extend others.
+pub fn resolve_extends(
+    command_definitions: Vec<CommandDefinition>,
+) -> Result<Vec<CommandDefinition>> {
+    if command_definitions
+        .iter()
+        .all(|command_definition| command_definition.extends.is_none())
+    {
+        return Ok(command_definitions);
+    }
+
+    let mut indexes_by_id = HashMap::new();
+    for (i, command_definition) in command_definitions.iter().enumerate() {
+        for id in command_definition.ids() {
+            indexes_by_id.entry(id.as_str()).or_insert(i);
+        }
+    }
+
+    let mut resolved = vec![None; command_definitions.len()];
+    (0..command_definitions.len())
+        .map(|i| {
+            resolve_command(
+                i,
+                &command_definitions,
+                &indexes_by_id,
+                &mut resolved,
+                &mut Vec::new(),
+            )
+        })
+        .collect()
 }
 
 impl Display for CommandDefinition {
@@ -134,3 +778,184 @@ impl Display for CommandExecutionTemplate {
         formatter.write_str(self.command.join(" ").as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Vec<CommandDefinition> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn parameter_defaults(command_definition: &CommandDefinition) -> Vec<(&str, Option<&str>)> {
+        command_definition
+            .parameters
+            .iter()
+            .flatten()
+            .map(|parameter| (parameter.name.as_str(), parameter.default.as_deref()))
+            .collect()
+    }
+
+    fn environment(command_definition: &CommandDefinition) -> BTreeMap<String, String> {
+        command_definition
+            .environment
+            .iter()
+            .flatten()
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn extends_are_resolved_through_the_chain() {
+        let resolved = resolve_extends(parse(
+            r#"
+- id: deploy-prod
+  extends: deploy
+  command: [--env, prod]
+  parameters: [{name: region, default: eu}]
+  environment: {STAGE: prod}
+- id: deploy
+  extends: kubectl
+  command: [apply]
+  parameters: [{name: region, default: us}, {name: file}]
+  environment: {STAGE: dev, DRY_RUN: "0"}
+- id: kubectl
+  command: [kubectl]
+  group: k8s
+"#,
+        ))
+        .unwrap();
+
+        assert_eq!(resolved[0].command, ["kubectl", "apply", "--env", "prod"]);
+        assert_eq!(
+            parameter_defaults(&resolved[0]),
+            [("file", None), ("region", Some("eu"))]
+        );
+        assert_eq!(
+            environment(&resolved[0]),
+            BTreeMap::from([
+                ("DRY_RUN".to_string(), "0".to_string()),
+                ("STAGE".to_string(), "prod".to_string()),
+            ])
+        );
+        assert_eq!(resolved[0].group.as_deref(), Some("k8s"));
+        assert_eq!(resolved[0].id.as_deref(), Some("deploy-prod"));
+        assert_eq!(resolved[1].command, ["kubectl", "apply"]);
+        assert_eq!(resolved[2].command, ["kubectl"]);
+    }
+
+    #[test]
+    fn extending_a_missing_command_is_an_error() {
+        let result = resolve_extends(parse("- {id: status, extends: git, command: [status]}"));
+
+        assert!(matches!(
+            result,
+            Err(Error::UnknownBaseCommand { command, base }) if command == "status" && base == "git"
+        ));
+    }
+
+    #[test]
+    fn extends_cycle_is_an_error() {
+        let result = resolve_extends(parse(
+            r#"
+- {id: a, extends: b, command: [a]}
+- {id: b, extends: c, command: [b]}
+- {id: c, extends: a, command: [c]}
+"#,
+        ));
+
+        assert!(matches!(
+            result,
+            Err(Error::CommandExtendsCycle(cycle)) if cycle == "a` -> `b` -> `c` -> `a"
+        ));
+    }
+
+    #[test]
+    fn variants_become_commands_of_their_own() {
+        let expanded = expand_variants(parse(
+            r#"
+- id: deploy
+  aliases: [d]
+  name: Deploy
+  command: [deploy, "{{region}}"]
+  parameters: [{name: region, default: eu}, {name: tag}]
+  environment: {STAGE: dev}
+  variants:
+    prod:
+      parameters: {region: us, replicas: "3"}
+      environment: {STAGE: prod}
+    dev: {}
+- id: other
+  command: [ls]
+"#,
+        ));
+
+        let ids: Vec<_> = expanded
+            .iter()
+            .map(CommandDefinition::history_key)
+            .collect();
+        assert_eq!(ids, ["deploy@dev", "deploy@prod", "other"]);
+
+        let prod = &expanded[1];
+        assert_eq!(prod.aliases, Some(vec!["d@prod".to_string()]));
+        assert_eq!(prod.name.as_deref(), Some("Deploy (prod)"));
+        assert_eq!(prod.command, ["deploy", "{{region}}"]);
+        assert_eq!(
+            parameter_defaults(prod),
+            [
+                ("region", Some("us")),
+                ("tag", None),
+                ("replicas", Some("3"))
+            ]
+        );
+        assert_eq!(environment(prod)["STAGE"], "prod");
+        assert!(prod.variants.is_none());
+
+        assert_eq!(parameter_defaults(&expanded[0])[0], ("region", Some("eu")));
+        assert_eq!(environment(&expanded[0])["STAGE"], "dev");
+    }
+
+    #[test]
+    fn variant_without_a_name_is_named_after_the_id() {
+        let expanded = expand_variants(parse(
+            "- {id: build, command: [make], variants: {release: {}}}",
+        ));
+
+        assert_eq!(expanded[0].name.as_deref(), Some("build (release)"));
+    }
+
+    #[test]
+    fn variants_are_inherited_from_the_base() {
+        let expanded = expand_variants(
+            resolve_extends(parse(
+                r#"
+- {id: base, command: [run], variants: {a: {}, b: {}}}
+- {id: child, extends: base, command: [child]}
+"#,
+            ))
+            .unwrap(),
+        );
+
+        let ids: Vec<_> = expanded
+            .iter()
+            .map(CommandDefinition::history_key)
+            .collect();
+        assert_eq!(ids, ["base@a", "base@b", "child@a", "child@b"]);
+        assert_eq!(expanded[2].command, ["run", "child"]);
+    }
+
+    #[test]
+    fn variant_ids_colliding_with_other_ids_are_an_error() {
+        let expanded = expand_variants(parse(
+            r#"
+- {id: deploy, command: [deploy], variants: {prod: {}}}
+- {id: deploy@prod, command: [deploy, --prod]}
+"#,
+        ));
+
+        assert!(matches!(
+            validate_command_ids(&expanded),
+            Err(Error::DuplicateCommandId(id)) if id == "deploy@prod"
+        ));
+    }
+}
@@ -1,14 +1,17 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
-use std::io::{stdin, stdout, Write};
-use std::time::Duration;
+use std::fs::File;
+use std::io::{stdin, stdout, BufRead, BufReader, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
 
 use crossterm::cursor::MoveTo;
 use crossterm::event::{
     DisableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
 };
-use crossterm::style::Color::{DarkBlue, DarkGreen, Reset, Yellow};
+use crossterm::style::Color::Reset;
 use crossterm::style::{
     Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor,
 };
@@ -16,27 +19,74 @@ use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType};
 use crossterm::{cursor, event, execute, queue, terminal, ExecutableCommand};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
+use itertools::Itertools;
+use log::warn;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::command_definitions::{CommandDefinition, CommandExecutionTemplate};
+use crate::command_definitions::{get_command_index, CommandDefinition, CommandExecutionTemplate};
 use crate::command_selection::CommandIndex::Normal;
-use crate::command_selection::CycleDirection::{Down, Up};
+use crate::command_selection::CycleDirection::{Down, First, Last, PageDown, PageUp, Up};
 use crate::error::{Error, Result};
+use crate::line_editor;
+use crate::settings::SortMode;
+use crate::theme::{self, Theme};
+use crate::watch::FileWatcher;
 use crate::LAST_COMMAND_OPTION;
 
 pub enum CommandChoice {
     Index(usize),
-    Rerun(CommandExecutionTemplate),
+    Rerun(Box<CommandExecutionTemplate>),
     Quit,
 }
 
+/// What to do with the selected command, chosen from the actions menu.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CommandAction {
+    Run,
+    DryRun,
+    NewTerminal,
+    Copy,
+    Edit,
+    History,
+}
+
+/// Actions in the actions menu, with the key to choose them.
+const COMMAND_ACTIONS: [(char, &str, CommandAction); 6] = [
+    ('r', "Run", CommandAction::Run),
+    ('d', "Dry run", CommandAction::DryRun),
+    ('t', "Run in new terminal", CommandAction::NewTerminal),
+    ('c', "Copy command", CommandAction::Copy),
+    ('e', "Edit definition", CommandAction::Edit),
+    ('h', "Show history", CommandAction::History),
+];
+
 pub enum RunChoice {
     Yes,
     No,
     ChangeParams,
 }
 
+/// Digits typed within this time of each other are combined into one command number.
+const QUICK_SELECT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Number of rows taken by the preview pane, including its separator.
+const PREVIEW_HEIGHT: u16 = 7;
+
+/// Whether to prompt with plain lines of text, instead of the command list, parameter form and
+/// line editing, which need raw mode.
+static PLAIN_PROMPTS: AtomicBool = AtomicBool::new(false);
+
+/// The terminal, for reading prompts when stdin is piped.
+#[cfg(windows)]
+pub const TTY_PATH: &str = "CONIN$";
+#[cfg(not(windows))]
+pub const TTY_PATH: &str = "/dev/tty";
+
 struct DisplayMode {
     is_filtering: bool,
+    show_preview: bool,
+    sort_mode: SortMode,
+    theme: Theme,
 }
 
 struct ViewportState {
@@ -45,23 +95,92 @@ struct ViewportState {
     width: u16,
 }
 
-pub fn prompt_value(variable_name: &str, default_value: Option<&String>) -> Result<String> {
-    loop {
-        if default_value.is_some() {
-            print!(
-                "Please give value for `{variable_name}` [{}]: ",
-                default_value.as_ref().unwrap()
-            );
-        } else {
-            print!("Please give value for `{variable_name}`: ");
+/// Prompt with plain lines of text from now on, e.g. for screen readers.
+pub fn use_plain_prompts() {
+    PLAIN_PROMPTS.store(true, AtomicOrdering::SeqCst);
+}
+
+pub fn is_using_plain_prompts() -> bool {
+    PLAIN_PROMPTS.load(AtomicOrdering::SeqCst)
+}
+
+/// Switch to plain prompts if raw mode can't be used, e.g. in some IDE consoles, instead of
+/// failing when the command list is shown.
+pub fn check_raw_mode_is_available() {
+    if is_using_plain_prompts() {
+        return;
+    }
+
+    if let Err(e) = enable_raw_mode().and_then(|()| disable_raw_mode()) {
+        warn!("The terminal can't be put in raw mode, so plain prompts will be used: {e}");
+        use_plain_prompts();
+    }
+}
+
+/// Lines can only be edited on a terminal, otherwise they're read as they are.
+fn can_edit_lines() -> bool {
+    stdout().is_terminal() && !is_using_plain_prompts()
+}
+
+/// Read a line typed by the user. If stdin is piped to `rc`, it's left for the command and the
+/// line is read from the terminal instead.
+fn read_input_line() -> Result<String> {
+    let mut input = String::new();
+
+    if !stdin().is_terminal() {
+        if let Ok(tty) = File::open(TTY_PATH) {
+            BufReader::new(tty).read_line(&mut input)?;
+            return Ok(input);
         }
-        stdout().flush()?;
+    }
+
+    stdin().read_line(&mut input)?;
+    Ok(input)
+}
+
+/// Prefix for choosing one of the recent values by its number when prompting, e.g. `!1`.
+const RECENT_VALUE_PREFIX: char = '!';
+
+/// Prompt for the parameter's value. Values it was given recently are listed first, and can be
+/// chosen by their number or recalled with the up key.
+pub fn prompt_value(
+    variable_name: &str,
+    default_value: Option<&String>,
+    recent_values: &[String],
+) -> Result<String> {
+    if !recent_values.is_empty() {
+        println!("Recent values for `{variable_name}`:");
+        for (i, value) in recent_values.iter().enumerate() {
+            println!("  {RECENT_VALUE_PREFIX}{}  {value}", i + 1);
+        }
+    }
 
-        let mut input = String::new();
-        stdin().read_line(&mut input)?;
+    let prompt = match default_value {
+        Some(default_value) => {
+            format!("Please give value for `{variable_name}` [{default_value}]: ")
+        }
+        None => format!("Please give value for `{variable_name}`: "),
+    };
+
+    loop {
+        let input = if can_edit_lines() {
+            line_editor::read_line(&prompt, "", recent_values)?
+        } else {
+            print!("{prompt}");
+            stdout().flush()?;
+            read_input_line()?
+        };
 
         let read_value = input.trim().to_string();
 
+        let recent_value = read_value
+            .strip_prefix(RECENT_VALUE_PREFIX)
+            .and_then(|number| number.parse::<usize>().ok())
+            .and_then(|number| recent_values.get(number.wrapping_sub(1)));
+        if let Some(recent_value) = recent_value {
+            return Ok(recent_value.clone());
+        }
+
         if !read_value.is_empty() {
             return Ok(read_value);
         }
@@ -72,7 +191,12 @@ pub fn prompt_value(variable_name: &str, default_value: Option<&String>) -> Resu
     }
 }
 
-pub fn confirm_command_should_run(has_params: bool) -> Result<RunChoice> {
+/// Confirm if the command should be run. If `dangerous_id` is given, the command is dangerous and
+/// the ID must be typed out in full to run it.
+pub fn confirm_command_should_run(
+    has_params: bool,
+    dangerous_id: Option<&str>,
+) -> Result<RunChoice> {
     loop {
         let prompt_change_params = if has_params {
             "/[c]hange parameters"
@@ -80,16 +204,26 @@ pub fn confirm_command_should_run(has_params: bool) -> Result<RunChoice> {
             ""
         };
 
-        print!("Are you sure you want to run? ([Y]es/[n]o{prompt_change_params}): ");
+        if let Some(dangerous_id) = dangerous_id {
+            print!("This command is dangerous! Type `{dangerous_id}` to run it, or ([n]o{prompt_change_params}): ");
+        } else {
+            print!("Are you sure you want to run? ([Y]es/[n]o{prompt_change_params}): ");
+        }
         stdout().flush()?;
 
         // Read user input
-        let mut input = String::new();
-        stdin().read_line(&mut input)?;
+        let input = read_input_line()?;
+
+        if let Some(dangerous_id) = dangerous_id {
+            if input.trim() == dangerous_id {
+                return Ok(RunChoice::Yes);
+            }
+        }
 
         let lowercase_input = input.trim().to_lowercase();
 
-        if lowercase_input.as_str() == "y" || lowercase_input.is_empty() {
+        if dangerous_id.is_none() && (lowercase_input.as_str() == "y" || lowercase_input.is_empty())
+        {
             return Ok(RunChoice::Yes);
         }
 
@@ -103,22 +237,185 @@ pub fn confirm_command_should_run(has_params: bool) -> Result<RunChoice> {
     }
 }
 
+/// Ask if the missing working directory should be created.
+pub fn confirm_create_directory(working_directory: &str) -> Result<bool> {
+    print!("Working directory `{working_directory}` does not exist. Create it? ([y]es/[N]o): ");
+    stdout().flush()?;
+
+    let input = read_input_line()?;
+
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Ask if the command with the suggested ID should be run, when no command has the ID given.
+pub fn confirm_suggested_command(id: &str, suggestion: &str) -> Result<bool> {
+    print!("No command with ID `{id}` was found. Run `{suggestion}` instead? ([Y]es/[n]o): ");
+    stdout().flush()?;
+
+    let input = read_input_line()?;
+    let input = input.trim();
+
+    Ok(input.is_empty() || input.eq_ignore_ascii_case("y"))
+}
+
+/// Ask if the command definitions should be created, when there aren't any yet.
+pub fn confirm_create_config(config_path: &str) -> Result<bool> {
+    print!(
+        "No commands are defined yet, as `{config_path}` does not exist. Create it? ([Y]es/[n]o): "
+    );
+    stdout().flush()?;
+
+    let input = read_input_line()?;
+    let input = input.trim();
+
+    Ok(input.is_empty() || input.eq_ignore_ascii_case("y"))
+}
+
+/// Ask if the shell's aliases and functions should be imported into the new command definitions.
+pub fn confirm_import_shell_commands() -> Result<bool> {
+    print!("Import your shell aliases and functions as commands? ([Y]es/[n]o): ");
+    stdout().flush()?;
+
+    let input = read_input_line()?;
+    let input = input.trim();
+
+    Ok(input.is_empty() || input.eq_ignore_ascii_case("y"))
+}
+
+pub fn confirm_remove_command(description: &str) -> Result<bool> {
+    print!("Remove `{description}` from the command definitions? ([y]es/[N]o): ");
+    stdout().flush()?;
+
+    let input = read_input_line()?;
+
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Prompt for a line of text, which starts as `initial` and can be edited. Without a terminal to
+/// edit it on, `initial` is used if nothing is typed.
+pub fn prompt_text(prompt: &str, initial: &str) -> Result<String> {
+    if can_edit_lines() {
+        return Ok(line_editor::read_line(prompt, initial, &[])?
+            .trim()
+            .to_string());
+    }
+
+    print!("{prompt}");
+    stdout().flush()?;
+
+    let input = read_input_line()?;
+    let input = input.trim();
+
+    Ok(if input.is_empty() { initial } else { input }.to_string())
+}
+
+/// Ask for an ID to save a command to the command definitions with. Returns `None` if it
+/// shouldn't be saved.
+pub fn prompt_for_command_id(config_path: &str) -> Result<Option<String>> {
+    let id = prompt_text(
+        &format!("To save the command to `{config_path}`, give it an ID (or leave empty to not save it): "),
+        "",
+    )?;
+
+    Ok((!id.is_empty()).then_some(id))
+}
+
+/// Print the commands as a numbered list and read the number or ID of one to run, without raw
+/// mode, for screen readers and terminals that can't show the command list.
+pub fn prompt_for_command_choice_plain(
+    command_definitions: &[CommandDefinition],
+    last_command: Option<&CommandExecutionTemplate>,
+) -> Result<(CommandChoice, CommandAction)> {
+    // Numbered from 1, the same as in the command list
+    let number_width = command_definitions.len().to_string().len();
+
+    for (i, command_definition) in command_definitions.iter().enumerate() {
+        if !command_definition.is_available() {
+            continue;
+        }
+
+        // Multi-line function bodies are shown on one line
+        let command = command_definition.command.join(" ").replace('\n', "; ");
+        match &command_definition.name {
+            Some(name) => println!("{:>number_width$}. {name}: {command}", i + 1),
+            None => println!("{:>number_width$}. {command}", i + 1),
+        }
+    }
+
+    if let Some(last_command) = last_command {
+        println!("{LAST_COMMAND_OPTION:>number_width$}. Rerun: {last_command}");
+    }
+
+    loop {
+        print!("Choose a command by its number or ID (or q to quit): ");
+        stdout().flush()?;
+
+        let input = read_input_line()?;
+        // Nothing more can be read, e.g. stdin was closed
+        if input.is_empty() {
+            println!();
+            return Ok((CommandChoice::Quit, CommandAction::Run));
+        }
+
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
+
+        if input == "q" {
+            return Ok((CommandChoice::Quit, CommandAction::Run));
+        }
+
+        if let Some(last_command) = last_command {
+            if input == LAST_COMMAND_OPTION.to_string() {
+                return Ok((
+                    CommandChoice::Rerun(Box::new(last_command.clone())),
+                    CommandAction::Run,
+                ));
+            }
+        }
+
+        let index = match input.parse::<usize>() {
+            Ok(number) => number.checked_sub(1),
+            Err(_) => get_command_index(command_definitions, input).ok(),
+        };
+        let is_available = |index: usize| {
+            command_definitions
+                .get(index)
+                .is_some_and(CommandDefinition::is_available)
+        };
+
+        match index {
+            Some(index) if is_available(index) => {
+                return Ok((CommandChoice::Index(index), CommandAction::Run));
+            }
+            _ => println!("There's no command `{input}`."),
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Hash, Clone)]
 enum CommandIndex {
     Normal(usize),
     Rerun,
+    Group(String),
 }
 
 impl Display for CommandIndex {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            CommandIndex::Normal(i) => f.write_str(format!("{}", i+1).as_str()),
+            CommandIndex::Normal(i) => f.write_str(format!("{}", i + 1).as_str()),
             CommandIndex::Rerun => f.write_str("r"),
+            CommandIndex::Group(_) => Ok(()),
         }
     }
 }
 
-fn print_header(header_mode: &DisplayMode, selected_index: usize, command_display_count: usize) -> Result<()> {
+fn print_header(
+    header_mode: &DisplayMode,
+    selected_index: usize,
+    command_display_count: usize,
+) -> Result<()> {
     let mut stdout = stdout();
     let (width, _) = terminal::size()?;
 
@@ -129,25 +426,68 @@ fn print_header(header_mode: &DisplayMode, selected_index: usize, command_displa
     let instructions = if header_mode.is_filtering {
         "<esc>: Stop Filtering".to_string()
     } else {
-        format!("/: Begin Filtering   |   {}/{}   |   q: Quit", pad_to_width_of(selected_index + 1, command_display_count), command_display_count)
+        let sort_description = match header_mode.sort_mode {
+            SortMode::Definition => "config order",
+            SortMode::Frecency => "most used",
+        };
+        format!("/: Begin Filtering   |   {}/{}   |   <tab>: Preview   |   <space>: Actions   |   s: Sort ({sort_description})   |   q: Quit", pad_to_width_of(selected_index + 1, command_display_count), command_display_count)
     };
 
-    let right_padding = " ".repeat(width as usize - left_padding_size - instructions.len());
-
+    let instructions = truncate_to_width(
+        &instructions,
+        (width as usize).saturating_sub(left_padding_size),
+    );
+    let right_padding =
+        " ".repeat((width as usize).saturating_sub(left_padding_size + instructions.width()));
+
+    queue!(stdout, MoveTo(0, 0))?;
+    theme::queue_highlight(
+        &mut stdout,
+        header_mode.theme.header_background,
+        header_mode.theme.header_foreground,
+    )?;
     queue!(
         stdout,
-        MoveTo(0, 0),
-        SetBackgroundColor(DarkGreen),
         Print(left_padding),
         Print(instructions),
         Print(right_padding),
-        SetBackgroundColor(Reset),
-        SetForegroundColor(Reset),
     )?;
+    theme::queue_end_highlight(&mut stdout)?;
 
     Ok(())
 }
 
+/// Truncate text so it fits in `width` columns of the terminal, taking into account wide
+/// characters (e.g. emoji and CJK). Truncated text ends with an ellipsis.
+pub(crate) fn truncate_to_width(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+
+    let ellipsis = '…';
+    let available_width = width.saturating_sub(ellipsis.width().unwrap_or(1));
+
+    let mut truncated = String::new();
+    let mut truncated_width = 0;
+
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+
+        if truncated_width + char_width > available_width {
+            break;
+        }
+
+        truncated.push(c);
+        truncated_width += char_width;
+    }
+
+    if width > 0 {
+        truncated.push(ellipsis);
+    }
+
+    truncated
+}
+
 fn pad_to_width_of<T: Display>(value: T, max_number: usize) -> String {
     let width = format!("{}", max_number).len();
     format!("{:>width$}", value.to_string())
@@ -159,6 +499,8 @@ fn clear_and_write_command_row(
     command_index: &CommandIndex,
     is_selected: bool,
     terminal_width: Option<u16>,
+    match_indices: Option<&Vec<usize>>,
+    theme: &Theme,
 ) -> Result<()> {
     let mut stdout = stdout();
     let terminal_width = terminal_width.unwrap_or_else(|| {
@@ -172,20 +514,25 @@ fn clear_and_write_command_row(
     let fw_index = format!("[{index_as_string}]");
 
     let command_definition = commands_to_display.get(command_index).unwrap();
-    let content = format!("{fw_index} {command_definition}");
-
-    let padding = if content.len() < (terminal_width as usize) {
-        " ".repeat(terminal_width as usize - content.len())
+    let prefix = if let CommandIndex::Group(_) = command_index {
+        String::new()
     } else {
-        "".to_string()
+        format!("{fw_index} ")
     };
+    let description = truncate_to_width(
+        &command_definition.to_string(),
+        (terminal_width as usize).saturating_sub(prefix.width()),
+    );
+    let content = format!("{prefix}{description}");
+
+    let padding = " ".repeat((terminal_width as usize).saturating_sub(content.width()));
 
     if is_selected {
-        queue!(
-            stdout,
-            SetAttribute(Attribute::Bold),
-            SetBackgroundColor(DarkBlue),
-            SetForegroundColor(Yellow),
+        queue!(stdout, SetAttribute(Attribute::Bold))?;
+        theme::queue_highlight(
+            &mut stdout,
+            theme.selected_background,
+            theme.selected_foreground,
         )?;
     }
 
@@ -213,7 +560,14 @@ fn clear_and_write_command_row(
         )?;
     }
 
-    queue!(stdout, Print(content), Print(padding),)?;
+    // After the colors, as without colors setting them resets the attributes
+    if !is_selected && matches!(command_definition, CommandForDisplay::Group { .. }) {
+        queue!(stdout, SetAttribute(Attribute::Bold))?;
+    }
+
+    queue!(stdout, Print(prefix))?;
+    print_with_match_highlights(&description, match_indices)?;
+    queue!(stdout, Print(padding),)?;
 
     queue!(
         stdout,
@@ -226,15 +580,43 @@ fn clear_and_write_command_row(
     Ok(())
 }
 
+/// Print the text, underlining the characters at `match_indices` (from fuzzy matching).
+fn print_with_match_highlights(text: &str, match_indices: Option<&Vec<usize>>) -> Result<()> {
+    let mut stdout = stdout();
+
+    let Some(match_indices) = match_indices else {
+        queue!(stdout, Print(text))?;
+        return Ok(());
+    };
+
+    for (i, c) in text.chars().enumerate() {
+        if match_indices.contains(&i) {
+            queue!(
+                stdout,
+                SetAttribute(Attribute::Underlined),
+                Print(c),
+                SetAttribute(Attribute::NoUnderline)
+            )?;
+        } else {
+            queue!(stdout, Print(c))?;
+        }
+    }
+
+    Ok(())
+}
+
 fn print_commands_with_selection(
     commands_to_display: &HashMap<CommandIndex, CommandForDisplay>,
     indexes_to_display: &[CommandIndex],
     selected_index: usize,
     viewport: &ViewportState,
+    match_indices: &HashMap<CommandIndex, Vec<usize>>,
+    theme: &Theme,
 ) -> Result<()> {
     let mut stdout = stdout();
 
-    let visible_commands = indexes_to_display.iter()
+    let visible_commands = indexes_to_display
+        .iter()
         .skip(viewport.offset)
         .take(viewport.height as usize);
 
@@ -246,7 +628,9 @@ fn print_commands_with_selection(
             commands_to_display,
             index,
             is_selected,
-            Some(viewport.width),
+            Some(get_list_width(viewport, indexes_to_display.len())),
+            match_indices.get(index),
+            theme,
         )?;
         queue!(stdout, cursor::MoveToNextLine(1))?;
     }
@@ -255,12 +639,218 @@ fn print_commands_with_selection(
         return Err(Error::Stdio(e));
     }
 
+    print_scrollbar(viewport, indexes_to_display.len(), theme)
+}
+
+/// Width of the command list, which leaves room for the scrollbar if not all rows fit.
+fn get_list_width(viewport: &ViewportState, row_count: usize) -> u16 {
+    if row_count > viewport.height as usize {
+        viewport.width.saturating_sub(1)
+    } else {
+        viewport.width
+    }
+}
+
+/// Print a scrollbar on the right edge of the command list, if not all rows fit.
+fn print_scrollbar(viewport: &ViewportState, row_count: usize, theme: &Theme) -> Result<()> {
+    let height = viewport.height as usize;
+
+    if height == 0 || row_count <= height {
+        return Ok(());
+    }
+
+    let mut stdout = stdout();
+
+    let thumb_size = (height * height / row_count).max(1);
+    let max_offset = row_count - height;
+    let thumb_start = viewport.offset.min(max_offset) * (height - thumb_size) / max_offset;
+    let column = viewport.width.saturating_sub(1);
+
+    queue!(stdout, SetForegroundColor(theme.separator))?;
+
+    for row in 0..height {
+        let is_thumb = row >= thumb_start && row < thumb_start + thumb_size;
+        let symbol = if is_thumb { '█' } else { '│' };
+
+        queue!(stdout, MoveTo(column, row as u16 + 1), Print(symbol))?;
+    }
+
+    queue!(stdout, SetForegroundColor(Reset))?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Rows available for the command list: the terminal height minus the header, filter line and
+/// preview pane (if shown).
+fn get_viewport_height(terminal_height: u16, display_mode: &DisplayMode) -> u16 {
+    let preview_height = if display_mode.show_preview {
+        PREVIEW_HEIGHT
+    } else {
+        0
+    };
+
+    terminal_height.saturating_sub(2 + preview_height)
+}
+
+fn get_preview_lines(command_for_display: &CommandForDisplay) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    match command_for_display {
+        CommandForDisplay::Normal(command_definition) => {
+            if let Some(name) = &command_definition.name {
+                lines.push(format!("Name: {name}"));
+            }
+
+            if let Some(id) = &command_definition.id {
+                lines.push(format!("ID: {id}"));
+            }
+
+            if let Some(aliases) = &command_definition.aliases {
+                lines.push(format!("Aliases: {}", aliases.join(", ")));
+            }
+
+            if let Some(group) = &command_definition.group {
+                lines.push(format!("Group: {group}"));
+            }
+
+            if let Some(tags) = &command_definition.tags {
+                lines.push(format!("Tags: {}", tags.join(", ")));
+            }
+
+            if let Some(source) = &command_definition.source {
+                lines.push(format!("Source: {source}"));
+            }
+
+            if let Some(platforms) = &command_definition.platforms {
+                lines.push(format!("Platforms: {}", platforms.iter().join(", ")));
+            }
+
+            if let Some(requires) = &command_definition.requires {
+                lines.push(format!("Requires: {}", requires.join(", ")));
+            }
+
+            lines.push(format!("Command: {}", command_definition.command.join(" ")));
+
+            if let Some(parameters) = &command_definition.parameters {
+                let parameters = parameters
+                    .iter()
+                    .map(|parameter| match &parameter.default {
+                        Some(default) => format!("{} [{default}]", parameter.name),
+                        None => parameter.name.clone(),
+                    })
+                    .join(", ");
+                lines.push(format!("Parameters: {parameters}"));
+            }
+
+            if let Some(working_directory) = &command_definition.working_directory {
+                lines.push(format!("Working directory: {working_directory}"));
+            }
+
+            if let Some(environment) = &command_definition.environment {
+                lines.push(format!("Environment: {}", format_key_values(environment)));
+            }
+
+            if let Some(warn_after) = &command_definition.warn_after {
+                lines.push(format!("Expected to take: up to {warn_after}"));
+            }
+        }
+        CommandForDisplay::Rerun(last_command) => {
+            lines.push(format!("Last command: {}", last_command.command.join(" ")));
+
+            if let Some(template_context) = &last_command.template_context {
+                lines.push(format!(
+                    "Parameters: {}",
+                    format_key_values(template_context)
+                ));
+            }
+
+            if let Some(working_directory) = &last_command.working_directory {
+                lines.push(format!("Working directory: {working_directory}"));
+            }
+
+            if let Some(environment) = &last_command.environment {
+                lines.push(format!("Environment: {}", format_key_values(environment)));
+            }
+        }
+        CommandForDisplay::Group { name, count, .. } => {
+            lines.push(format!("Group: {name}"));
+            lines.push(format!("Commands: {count}"));
+        }
+    }
+
+    lines
+}
+
+fn format_key_values<V: Display + Ord>(values: &HashMap<String, V>) -> String {
+    values
+        .iter()
+        .sorted()
+        .map(|(key, value)| format!("{key}={value}"))
+        .join(", ")
+}
+
+/// Print the preview pane for the highlighted command at the bottom of the terminal.
+fn print_preview(
+    command_for_display: Option<&CommandForDisplay>,
+    width: u16,
+    theme: &Theme,
+) -> Result<()> {
+    let mut stdout = stdout();
+    let (_, height) = terminal::size()?;
+
+    let top_row = height.saturating_sub(PREVIEW_HEIGHT);
+    let lines = command_for_display
+        .map(get_preview_lines)
+        .unwrap_or_default();
+
+    queue!(
+        stdout,
+        MoveTo(0, top_row),
+        Clear(ClearType::CurrentLine),
+        SetForegroundColor(theme.separator),
+        Print("─".repeat(width as usize)),
+        SetForegroundColor(Reset),
+    )?;
+
+    for row in 1..PREVIEW_HEIGHT {
+        queue!(
+            stdout,
+            MoveTo(0, top_row + row),
+            Clear(ClearType::CurrentLine)
+        )?;
+
+        if let Some(line) = lines.get(row as usize - 1) {
+            queue!(stdout, Print(truncate_to_width(line, width as usize)))?;
+        }
+    }
+
+    stdout.flush()?;
+
     Ok(())
 }
 
 enum CycleDirection {
     Up,
     Down,
+    PageUp,
+    PageDown,
+    First,
+    Last,
+}
+
+/// Scroll the viewport so that the command at `index` is visible.
+/// Returns true if the viewport offset changed.
+fn scroll_to_index(index: usize, viewport: &mut ViewportState) -> bool {
+    let previous_offset = viewport.offset;
+
+    if index < viewport.offset {
+        viewport.offset = index;
+    } else if index >= viewport.offset + viewport.height as usize {
+        viewport.offset = index.saturating_sub((viewport.height as usize).saturating_sub(1));
+    }
+
+    viewport.offset != previous_offset
 }
 
 fn move_selected_index(
@@ -300,23 +890,81 @@ fn move_selected_index(
                 viewport_changed = true;
             }
         }
+        Some(PageUp) => {
+            new_index = new_index.saturating_sub((viewport.height as usize).max(1));
+            viewport_changed = scroll_to_index(new_index, viewport);
+        }
+        Some(PageDown) => {
+            new_index =
+                (new_index + (viewport.height as usize).max(1)).min(commands_to_display_length - 1);
+            viewport_changed = scroll_to_index(new_index, viewport);
+        }
+        Some(First) => {
+            new_index = 0;
+            viewport_changed = scroll_to_index(new_index, viewport);
+        }
+        Some(Last) => {
+            new_index = commands_to_display_length - 1;
+            viewport_changed = scroll_to_index(new_index, viewport);
+        }
         None => {}
     }
 
     (new_index, viewport_changed)
 }
 
+/// What was found by the last filter, so typing more of the filter doesn't start again. A command
+/// that didn't match can't match a longer filter, so only the previous matches need checking.
+#[derive(Default)]
+struct FilterCache {
+    predicate: String,
+    matches: Option<HashSet<CommandIndex>>,
+    /// Each command's description and search text, which don't change while filtering.
+    search_texts: HashMap<CommandIndex, (String, String)>,
+}
+
+/// Filter the commands to display. When fuzzy matching, commands are sorted by how well they
+/// match (best first), otherwise by definition order, or by score (highest first) if `sort_scores`
+/// is given. The last command is always at the end.
+///
+/// Also returns the indices of the matched characters in each command's description.
 fn filter_displayed_indexes(
     command_lookup: &HashMap<CommandIndex, CommandForDisplay>,
     predicate: &str,
-) -> Vec<CommandIndex> {
+    sort_scores: Option<&HashMap<CommandIndex, u64>>,
+    cache: &mut FilterCache,
+) -> (Vec<CommandIndex>, HashMap<CommandIndex, Vec<usize>>) {
     let matcher = SkimMatcherV2::default();
     let predicate_index = predicate.parse::<usize>().ok();
+    let is_fuzzy_matching = predicate_index.is_none() && !predicate.is_empty();
+
+    let mut match_scores: HashMap<CommandIndex, u64> = HashMap::new();
+    let mut match_indices: HashMap<CommandIndex, Vec<usize>> = HashMap::new();
+
+    let previous_matches = cache
+        .matches
+        .take()
+        .filter(|_| is_fuzzy_matching && predicate.starts_with(&cache.predicate));
 
     let mut filtered: Vec<CommandIndex> = command_lookup
         .iter()
+        .filter(|(i, _)| {
+            previous_matches
+                .as_ref()
+                .is_none_or(|matches| matches.contains(*i))
+        })
         .filter_map(|(i, command_for_display)| {
-            let command_description = command_for_display.to_string();
+            if let CommandForDisplay::Group { .. } = command_for_display {
+                return None;
+            }
+
+            let (command_description, search_text) =
+                cache.search_texts.entry(i.clone()).or_insert_with(|| {
+                    (
+                        command_for_display.to_string(),
+                        command_for_display.get_search_text(),
+                    )
+                });
 
             if let Some(pred_idx) = predicate_index {
                 // Index-based filtering
@@ -324,73 +972,306 @@ fn filter_displayed_indexes(
                     .contains(&pred_idx.to_string())
                     .then_some(i.clone())
             } else {
-                // Fuzzy name-based filtering
-                matcher
-                    .fuzzy_match(&command_description, predicate)
-                    .map(|_| i.clone())
+                // Fuzzy filtering on the name, falling back to the command itself, ID and tags
+                let (score, indices) = match matcher.fuzzy_indices(command_description, predicate) {
+                    Some((score, indices)) => (score, Some(indices)),
+                    None => (matcher.fuzzy_match(search_text, predicate)?, None),
+                };
+
+                if is_fuzzy_matching {
+                    match_scores.insert(i.clone(), u64::try_from(score).unwrap_or(0));
+
+                    if let Some(indices) = indices {
+                        match_indices.insert(i.clone(), indices);
+                    }
+                }
+
+                Some(i.clone())
             }
         })
         .collect();
 
+    cache.predicate = predicate.to_string();
+    if is_fuzzy_matching {
+        cache.matches = Some(filtered.iter().cloned().collect());
+    }
+
+    let sort_scores = if is_fuzzy_matching {
+        Some(&match_scores)
+    } else {
+        sort_scores
+    };
+
     filtered.sort_by(|k1, k2| match (k1, k2) {
-        (Normal(i1), Normal(i2)) => i1.cmp(i2),
+        (Normal(i1), Normal(i2)) => match sort_scores {
+            Some(sort_scores) => {
+                let score1 = sort_scores.get(k1).unwrap_or(&0);
+                let score2 = sort_scores.get(k2).unwrap_or(&0);
+                score2.cmp(score1).then(i1.cmp(i2))
+            }
+            None => i1.cmp(i2),
+        },
         (_, Normal(_)) => Ordering::Greater,
         (Normal(_), _) => Ordering::Less,
         _ => Ordering::Equal,
     });
 
-    filtered
+    (filtered, match_indices)
+}
+
+/// Insert a header row before the commands of each group, after the commands without a group.
+/// Commands in collapsed groups are hidden, unless filtering. The last command is always at the end.
+fn group_displayed_indexes(
+    indexes: Vec<CommandIndex>,
+    command_lookup: &mut HashMap<CommandIndex, CommandForDisplay>,
+    collapsed_groups: &HashSet<String>,
+    is_filtering: bool,
+) -> Vec<CommandIndex> {
+    command_lookup.retain(|index, _| !matches!(index, CommandIndex::Group(_)));
+
+    let mut rows = Vec::new();
+    let mut rerun_rows = Vec::new();
+    let mut groups: Vec<(String, Vec<CommandIndex>)> = Vec::new();
+
+    for index in indexes {
+        let group = match command_lookup.get(&index) {
+            Some(CommandForDisplay::Normal(command_definition)) => command_definition.group.clone(),
+            _ => None,
+        };
+
+        match group {
+            None if index == CommandIndex::Rerun => rerun_rows.push(index),
+            None => rows.push(index),
+            Some(group) => match groups.iter_mut().find(|(name, _)| *name == group) {
+                Some((_, group_indexes)) => group_indexes.push(index),
+                None => groups.push((group, vec![index])),
+            },
+        }
+    }
+
+    for (name, group_indexes) in groups {
+        let is_collapsed = !is_filtering && collapsed_groups.contains(&name);
+        let header_index = CommandIndex::Group(name.clone());
+
+        command_lookup.insert(
+            header_index.clone(),
+            CommandForDisplay::Group {
+                name,
+                count: group_indexes.len(),
+                is_collapsed,
+            },
+        );
+        rows.push(header_index);
+
+        if !is_collapsed {
+            rows.extend(group_indexes);
+        }
+    }
+
+    rows.extend(rerun_rows);
+
+    rows
 }
 
 enum CommandForDisplay {
-    Normal(CommandDefinition),
-    Rerun(CommandExecutionTemplate),
+    Normal(Box<CommandDefinition>),
+    Rerun(Box<CommandExecutionTemplate>),
+    Group {
+        name: String,
+        count: usize,
+        is_collapsed: bool,
+    },
 }
 
 impl Display for CommandForDisplay {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            CommandForDisplay::Normal(n) => write!(f, "{}", n),
+            CommandForDisplay::Normal(n) => match &n.source {
+                Some(source) => write!(f, "{} [{source}]", n),
+                None => write!(f, "{}", n),
+            },
             CommandForDisplay::Rerun(r) => write!(f, "{}", r),
+            CommandForDisplay::Group {
+                name,
+                count,
+                is_collapsed,
+            } => {
+                let marker = if *is_collapsed { "▶" } else { "▼" };
+                write!(f, "{marker} {name} ({count})")
+            }
         }
     }
 }
 
-pub fn prompt_for_command_choice(
-    command_definitions: &[CommandDefinition],
-    last_command: Option<&CommandExecutionTemplate>,
-) -> Result<CommandChoice> {
-    let mut stdout = stdout();
+impl CommandForDisplay {
+    /// Text to search when filtering, other than the description.
+    fn get_search_text(&self) -> String {
+        match self {
+            CommandForDisplay::Normal(command_definition) => {
+                let mut search_terms = vec![command_definition.command.join(" ")];
 
-    let mut selected_index: usize = 0;
-    enable_raw_mode()?;
+                search_terms.extend(command_definition.ids().cloned());
 
-    let _raw_mode_guard = RawModeGuard; // When this goes out of scope, raw mode and mouse capture is disabled
-    stdout.execute(event::EnableMouseCapture)?;
+                if let Some(tags) = &command_definition.tags {
+                    search_terms.extend(tags.iter().cloned());
+                }
 
-    let mut should_reprint = true;
-    let mut typed_index = String::new();
-    let mut filter_text = String::new();
-    let mut display_mode = DisplayMode {
-        is_filtering: false,
-    };
+                search_terms.join(" ")
+            }
+            CommandForDisplay::Rerun(last_command) => last_command.command.join(" "),
+            CommandForDisplay::Group { name, .. } => name.clone(),
+        }
+    }
+}
+
+/// The group of the command displayed at `index`, if it is a command in a group or a group header.
+fn get_group_of(
+    command_lookup: &HashMap<CommandIndex, CommandForDisplay>,
+    index: &CommandIndex,
+) -> Option<String> {
+    match command_lookup.get(index) {
+        Some(CommandForDisplay::Normal(command_definition)) => command_definition.group.clone(),
+        Some(CommandForDisplay::Group { name, .. }) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Reloads the commands when the files they are defined in change.
+pub struct CommandReloader<'a> {
+    watcher: FileWatcher,
+    load: Box<dyn Fn() -> Result<Vec<CommandDefinition>> + 'a>,
+}
+
+impl<'a> CommandReloader<'a> {
+    pub fn new(
+        paths: &[PathBuf],
+        load: impl Fn() -> Result<Vec<CommandDefinition>> + 'a,
+    ) -> Result<Self> {
+        Ok(CommandReloader {
+            watcher: FileWatcher::new(paths)?,
+            load: Box::new(load),
+        })
+    }
+
+    /// The reloaded commands, if their files have changed.
+    fn reload_if_changed(&self) -> Option<Result<Vec<CommandDefinition>>> {
+        self.watcher.has_changed().then(|| (self.load)())
+    }
+}
+
+/// Frecency scores of the commands, by their index.
+fn get_frecency_by_index(
+    command_definitions: &[CommandDefinition],
+    frecency_scores: &HashMap<String, u64>,
+) -> HashMap<CommandIndex, u64> {
+    command_definitions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, cd)| {
+            frecency_scores
+                .get(&cd.history_key())
+                .map(|score| (CommandIndex::Normal(i), *score))
+        })
+        .collect()
+}
+
+fn get_sort_scores<'a>(
+    display_mode: &DisplayMode,
+    frecency_by_index: &'a HashMap<CommandIndex, u64>,
+) -> Option<&'a HashMap<CommandIndex, u64>> {
+    match display_mode.sort_mode {
+        SortMode::Definition => None,
+        SortMode::Frecency => Some(frecency_by_index),
+    }
+}
 
+fn build_command_display(
+    command_definitions: &[CommandDefinition],
+    last_command: Option<&CommandExecutionTemplate>,
+) -> HashMap<CommandIndex, CommandForDisplay> {
+    // Commands for other platforms are hidden, but keep their indexes
     let mut command_display: HashMap<CommandIndex, CommandForDisplay> = command_definitions
         .iter()
         .enumerate()
+        .filter(|(_, cd)| cd.is_available())
         .map(|(i, cd)| {
             (
                 CommandIndex::Normal(i),
-                CommandForDisplay::Normal(cd.clone()),
+                CommandForDisplay::Normal(Box::new(cd.clone())),
             )
         })
         .collect();
 
     if let Some(lc) = last_command {
-        command_display.insert(CommandIndex::Rerun, CommandForDisplay::Rerun(lc.clone()));
+        command_display.insert(
+            CommandIndex::Rerun,
+            CommandForDisplay::Rerun(Box::new(lc.clone())),
+        );
     }
 
-    let mut indexes_to_display = filter_displayed_indexes(&command_display, &filter_text);
+    command_display
+}
+
+/// Show the list of commands to choose from. If `command_reloader` is given, the commands are
+/// reloaded when their files change, so `command_definitions` may be updated before the chosen
+/// index is returned.
+pub fn prompt_for_command_choice(
+    command_definitions: &mut Vec<CommandDefinition>,
+    command_reloader: Option<&CommandReloader>,
+    last_command: Option<&CommandExecutionTemplate>,
+    frecency_scores: &HashMap<String, u64>,
+    sort_mode: SortMode,
+    theme: Theme,
+) -> Result<(CommandChoice, CommandAction)> {
+    let mut stdout = stdout();
+
+    let mut selected_index: usize = 0;
+    enable_raw_mode()?;
+
+    let _raw_mode_guard = RawModeGuard; // When this goes out of scope, raw mode and mouse capture is disabled
+                                        // The list can still be used with the keyboard if the terminal doesn't support the mouse
+    if let Err(e) = stdout.execute(event::EnableMouseCapture) {
+        warn!("Mouse clicks and scrolling won't work in the command list: {e}");
+    }
+
+    let mut should_reprint = true;
+    let mut typed_index = String::new();
+    // Digits typed to quick select a command by its number, and when the last one was typed
+    let mut quick_select_digits = String::new();
+    let mut last_quick_select_time = Instant::now();
+    let mut filter_text = String::new();
+    let mut display_mode = DisplayMode {
+        is_filtering: false,
+        show_preview: false,
+        sort_mode,
+        theme,
+    };
+
+    let mut frecency_by_index = get_frecency_by_index(command_definitions, frecency_scores);
+
+    let mut command_display = build_command_display(command_definitions, last_command);
+    // Why the commands couldn't be reloaded, e.g. the config was saved part way through an edit
+    let mut reload_error: Option<String> = None;
+
+    let mut collapsed_groups: HashSet<String> = HashSet::new();
+    // Row to select after the list is next rebuilt, e.g. a group header after collapsing its group
+    let mut row_to_select: Option<CommandIndex> = None;
+
+    let mut filter_cache = FilterCache::default();
+    // Whether the list needs filtering and redrawing once there are no more keys to handle
+    let mut is_filter_pending = false;
+    let (filtered_indexes, mut match_indices) = filter_displayed_indexes(
+        &command_display,
+        &filter_text,
+        get_sort_scores(&display_mode, &frecency_by_index),
+        &mut filter_cache,
+    );
+    let mut indexes_to_display = group_displayed_indexes(
+        filtered_indexes,
+        &mut command_display,
+        &collapsed_groups,
+        display_mode.is_filtering,
+    );
 
     let mut down_row: Option<u16> = None;
     let mut index_change_direction: Option<CycleDirection> = None;
@@ -399,23 +1280,91 @@ pub fn prompt_for_command_choice(
 
     let mut viewport = ViewportState {
         offset: 0,
-        height: height.saturating_sub(2), // Subtract 2 for header and filter line
+        height: get_viewport_height(height, &display_mode),
         width,
     };
 
     loop {
+        if let Some(reload_result) = command_reloader.and_then(CommandReloader::reload_if_changed) {
+            match reload_result {
+                Ok(reloaded_command_definitions) => {
+                    // Keep the same command selected, wherever it is now
+                    let selected_key = match indexes_to_display.get(selected_index) {
+                        Some(Normal(i)) => command_definitions
+                            .get(*i)
+                            .map(CommandDefinition::history_key),
+                        _ => None,
+                    };
+
+                    *command_definitions = reloaded_command_definitions;
+                    frecency_by_index = get_frecency_by_index(command_definitions, frecency_scores);
+                    command_display = build_command_display(command_definitions, last_command);
+                    filter_cache = FilterCache::default();
+                    reload_error = None;
+
+                    row_to_select = match selected_key {
+                        Some(key) => command_definitions
+                            .iter()
+                            .position(|cd| cd.history_key() == key)
+                            .map(Normal),
+                        None => indexes_to_display.get(selected_index).cloned(),
+                    };
+                }
+                Err(e) => reload_error = Some(e.to_string()),
+            }
+
+            should_reprint = true;
+        }
+
+        // While typing the filter, keys already typed are handled before filtering and redrawing,
+        // so filtering long lists doesn't make typing lag behind
+        if should_reprint && display_mode.is_filtering && event::poll(Duration::ZERO)? {
+            should_reprint = false;
+            is_filter_pending = true;
+        } else if is_filter_pending && !event::poll(Duration::ZERO)? {
+            should_reprint = true;
+            is_filter_pending = false;
+        }
+
         if should_reprint {
             let indexes_before = indexes_to_display.clone();
-            indexes_to_display = filter_displayed_indexes(&command_display, &filter_text);
+            let filtered_indexes;
+            (filtered_indexes, match_indices) = filter_displayed_indexes(
+                &command_display,
+                &filter_text,
+                get_sort_scores(&display_mode, &frecency_by_index),
+                &mut filter_cache,
+            );
+            indexes_to_display = group_displayed_indexes(
+                filtered_indexes,
+                &mut command_display,
+                &collapsed_groups,
+                display_mode.is_filtering,
+            );
 
             if indexes_before == indexes_to_display {
                 selected_index = typed_index.parse::<usize>().unwrap_or(0);
             } else {
-                (selected_index, _) =
-                    move_selected_index(selected_index, &mut viewport, indexes_to_display.len(), None);
+                (selected_index, _) = move_selected_index(
+                    selected_index,
+                    &mut viewport,
+                    indexes_to_display.len(),
+                    None,
+                );
                 typed_index = selected_index.to_string();
             }
 
+            // The list may have shrunk, e.g. after collapsing a group
+            selected_index = selected_index.min(indexes_to_display.len().saturating_sub(1));
+
+            if let Some(row) = row_to_select.take() {
+                if let Some(position) = indexes_to_display.iter().position(|index| *index == row) {
+                    selected_index = position;
+                    scroll_to_index(selected_index, &mut viewport);
+                    typed_index = selected_index.to_string();
+                }
+            }
+
             queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
 
             print_header(&display_mode, selected_index, indexes_to_display.len())?;
@@ -433,7 +1382,9 @@ pub fn prompt_for_command_choice(
                     &command_display,
                     &indexes_to_display,
                     selected_index,
-                    &viewport
+                    &viewport,
+                    &match_indices,
+                    &display_mode.theme,
                 )?;
             }
 
@@ -446,6 +1397,28 @@ pub fn prompt_for_command_choice(
                 )?;
             }
 
+            if let Some(reload_error) = &reload_error {
+                let separator = if display_mode.is_filtering { "   " } else { "" };
+                queue!(
+                    stdout,
+                    SetForegroundColor(Color::Red),
+                    Print(format!(
+                        "{separator}Could not reload the commands: {reload_error}"
+                    )),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            }
+
+            if display_mode.show_preview {
+                print_preview(
+                    indexes_to_display
+                        .get(selected_index)
+                        .and_then(|index| command_display.get(index)),
+                    viewport.width,
+                    &display_mode.theme,
+                )?;
+            }
+
             stdout.flush()?;
             should_reprint = false;
         }
@@ -467,16 +1440,30 @@ pub fn prompt_for_command_choice(
                             }
                             MouseEventKind::Up(button) => {
                                 if button == MouseButton::Left {
-                                    if let Some(down_row) = down_row {
-                                        let clicked_index = (down_row - 1) as usize + viewport.offset;
-
-                                        if clicked_index < indexes_to_display.len() {
+                                    // Only rows in the command list can be clicked
+                                    if let Some(down_row) =
+                                        down_row.filter(|row| *row > 0 && *row <= viewport.height)
+                                    {
+                                        let clicked_index =
+                                            (down_row - 1) as usize + viewport.offset;
+
+                                        if let Some(CommandIndex::Group(name)) =
+                                            indexes_to_display.get(clicked_index)
+                                        {
+                                            // Clicking a group header expands/collapses it
+                                            toggle_group(&mut collapsed_groups, name);
+                                            row_to_select = Some(CommandIndex::Group(name.clone()));
+                                            should_reprint = true;
+                                        } else if clicked_index < indexes_to_display.len() {
                                             clear_and_write_command_row(
                                                 selected_index as u16 + 1,
                                                 &command_display,
                                                 &indexes_to_display[selected_index],
                                                 false,
                                                 None,
+                                                match_indices
+                                                    .get(&indexes_to_display[selected_index]),
+                                                &display_mode.theme,
                                             )?;
 
                                             clear_and_write_command_row(
@@ -485,6 +1472,9 @@ pub fn prompt_for_command_choice(
                                                 &indexes_to_display[clicked_index],
                                                 true,
                                                 None,
+                                                match_indices
+                                                    .get(&indexes_to_display[clicked_index]),
+                                                &display_mode.theme,
                                             )?;
 
                                             selected_index = clicked_index;
@@ -493,14 +1483,23 @@ pub fn prompt_for_command_choice(
                                                 MoveTo(0, indexes_to_display.len() as u16 + 1)
                                             )?;
                                             match indexes_to_display[clicked_index] {
-                                                Normal(i) => return Ok(CommandChoice::Index(i)),
+                                                Normal(i) => {
+                                                    return Ok((
+                                                        CommandChoice::Index(i),
+                                                        CommandAction::Run,
+                                                    ))
+                                                }
                                                 CommandIndex::Rerun => {
                                                     if let Some(last_command) = last_command {
-                                                        return Ok(CommandChoice::Rerun(
-                                                            last_command.clone(),
+                                                        return Ok((
+                                                            CommandChoice::Rerun(Box::new(
+                                                                last_command.clone(),
+                                                            )),
+                                                            CommandAction::Run,
                                                         ));
                                                     };
                                                 }
+                                                CommandIndex::Group(_) => {}
                                             }
                                         }
                                     }
@@ -527,15 +1526,79 @@ pub fn prompt_for_command_choice(
                                 Some(Down)
                             };
                         }
+                        KeyCode::Right | KeyCode::Char(' ')
+                            if !display_mode.is_filtering
+                                && matches!(
+                                    indexes_to_display.get(selected_index),
+                                    Some(Normal(_) | CommandIndex::Rerun)
+                                ) =>
+                        {
+                            let selected_row = indexes_to_display[selected_index].clone();
+                            let menu_row =
+                                (selected_index.saturating_sub(viewport.offset) + 2) as u16;
+
+                            if let Some(action) = prompt_for_action(
+                                menu_row,
+                                matches!(selected_row, Normal(_)),
+                                &display_mode.theme,
+                            )? {
+                                match selected_row {
+                                    Normal(i) => return Ok((CommandChoice::Index(i), action)),
+                                    _ => {
+                                        if let Some(last_command) = last_command {
+                                            return Ok((
+                                                CommandChoice::Rerun(Box::new(
+                                                    last_command.clone(),
+                                                )),
+                                                action,
+                                            ));
+                                        }
+                                    }
+                                }
+                            }
+
+                            should_reprint = true;
+                        }
+                        KeyCode::Left | KeyCode::Right if !display_mode.is_filtering => {
+                            // Collapse the group of the selected command, or expand a group header
+                            let selected_row = indexes_to_display.get(selected_index);
+                            if let Some(group) =
+                                selected_row.and_then(|index| get_group_of(&command_display, index))
+                            {
+                                if key_event.code == KeyCode::Left {
+                                    collapsed_groups.insert(group.clone());
+                                } else {
+                                    collapsed_groups.remove(&group);
+                                }
+                                row_to_select = Some(CommandIndex::Group(group));
+                                should_reprint = true;
+                            }
+                        }
+                        KeyCode::PageUp => index_change_direction = Some(PageUp),
+                        KeyCode::PageDown => index_change_direction = Some(PageDown),
+                        KeyCode::Home => index_change_direction = Some(First),
+                        KeyCode::End => index_change_direction = Some(Last),
                         KeyCode::Enter => {
                             if let Some(command_index) = indexes_to_display.get(selected_index) {
                                 match command_index {
-                                    Normal(i) => return Ok(CommandChoice::Index(*i)),
+                                    Normal(i) => {
+                                        return Ok((CommandChoice::Index(*i), CommandAction::Run))
+                                    }
                                     CommandIndex::Rerun => {
                                         if let Some(last_command) = last_command {
-                                            return Ok(CommandChoice::Rerun(last_command.clone()));
+                                            return Ok((
+                                                CommandChoice::Rerun(Box::new(
+                                                    last_command.clone(),
+                                                )),
+                                                CommandAction::Run,
+                                            ));
                                         };
                                     }
+                                    CommandIndex::Group(name) => {
+                                        toggle_group(&mut collapsed_groups, name);
+                                        row_to_select = Some(command_index.clone());
+                                        should_reprint = true;
+                                    }
                                 }
                             } else {
                                 execute!(stdout, Print("\x07"))?;
@@ -551,7 +1614,7 @@ pub fn prompt_for_command_choice(
                                 .modifiers
                                 .contains(crossterm::event::KeyModifiers::CONTROL) =>
                         {
-                            return Ok(CommandChoice::Quit);
+                            return Ok((CommandChoice::Quit, CommandAction::Run));
                         }
                         KeyCode::Char(c) if display_mode.is_filtering => {
                             filter_text.push(c);
@@ -562,40 +1625,88 @@ pub fn prompt_for_command_choice(
                             should_reprint = true;
                             filter_text = "".to_string();
                         }
-                        // KeyCode::Char(d) if d.is_ascii_digit() && !filter_mode => {
-                        //     typed_index.push(d);
-                        //     should_reprint = true;
-                        // }
+                        KeyCode::Char(d) if d.is_ascii_digit() => {
+                            if last_quick_select_time.elapsed() > QUICK_SELECT_TIMEOUT {
+                                quick_select_digits.clear();
+                            }
+                            last_quick_select_time = Instant::now();
+                            quick_select_digits.push(d);
+
+                            let mut position =
+                                find_row_by_number(&indexes_to_display, &quick_select_digits);
+
+                            if position.is_none() {
+                                // No command with the combined number, so start again from this digit
+                                quick_select_digits = d.to_string();
+                                position =
+                                    find_row_by_number(&indexes_to_display, &quick_select_digits);
+                            }
+
+                            if let Some(position) = position {
+                                selected_index = position;
+                                typed_index = selected_index.to_string();
+                                scroll_to_index(selected_index, &mut viewport);
+                                should_reprint = true;
+                            } else {
+                                execute!(stdout, Print("\x07"))?;
+                            }
+                        }
+                        KeyCode::Tab => {
+                            display_mode.show_preview = !display_mode.show_preview;
+
+                            let (_, height) = terminal::size()?;
+                            viewport.height = get_viewport_height(height, &display_mode);
+
+                            // Keep the selection visible if the command list has shrunk
+                            if selected_index >= viewport.offset + viewport.height as usize {
+                                viewport.offset = selected_index
+                                    .saturating_sub((viewport.height as usize).saturating_sub(1));
+                            }
+
+                            should_reprint = true;
+                        }
                         KeyCode::Char('/') => {
                             display_mode.is_filtering = true;
                             should_reprint = true;
                         }
                         KeyCode::Char('q') => {
-                            return Ok(CommandChoice::Quit);
+                            return Ok((CommandChoice::Quit, CommandAction::Run));
+                        }
+                        KeyCode::Char('s') => {
+                            display_mode.sort_mode = display_mode.sort_mode.toggled();
+                            should_reprint = true;
                         }
                         KeyCode::Char(LAST_COMMAND_OPTION) => {
                             if let Some(last_command) = last_command {
-                                return Ok(CommandChoice::Rerun(last_command.clone()));
+                                return Ok((
+                                    CommandChoice::Rerun(Box::new(last_command.clone())),
+                                    CommandAction::Run,
+                                ));
                             }
                         }
                         _ => {}
                     }
                 }
                 Event::Resize(width, height) => {
-                    let new_height = height.saturating_sub(2);
+                    let new_height = get_viewport_height(height, &display_mode);
                     viewport.width = width;
 
                     // If growing taller, try to show more items above current selection
                     match new_height.cmp(&viewport.height) {
                         Ordering::Greater if viewport.offset > 0 => {
                             let height_increase = new_height - viewport.height;
-                            viewport.offset = viewport.offset.saturating_sub(height_increase as usize);
+                            viewport.offset =
+                                viewport.offset.saturating_sub(height_increase as usize);
                         }
-                        Ordering::Less if selected_index >= viewport.offset + new_height as usize => {
-                            viewport.offset = selected_index.saturating_sub(new_height as usize - 1);
+                        Ordering::Less
+                            if selected_index >= viewport.offset + new_height as usize =>
+                        {
+                            viewport.offset =
+                                selected_index.saturating_sub(new_height as usize - 1);
 
                             if viewport.offset + new_height as usize > indexes_to_display.len() {
-                                viewport.offset = indexes_to_display.len().saturating_sub(new_height as usize);
+                                viewport.offset =
+                                    indexes_to_display.len().saturating_sub(new_height as usize);
                             }
                         }
                         _ => {}
@@ -612,8 +1723,12 @@ pub fn prompt_for_command_choice(
             match index_change_direction {
                 None => {}
                 Some(d) => {
-                    let (new_index, viewport_changed) =
-                        move_selected_index(selected_index, &mut viewport, indexes_to_display.len(), Some(&d));
+                    let (new_index, viewport_changed) = move_selected_index(
+                        selected_index,
+                        &mut viewport,
+                        indexes_to_display.len(),
+                        Some(&d),
+                    );
 
                     if viewport_changed {
                         should_reprint = true;
@@ -625,14 +1740,19 @@ pub fn prompt_for_command_choice(
                         let new_row = (new_index - viewport.offset) as u16 + 1;
 
                         // Only try to update individual rows if they're both visible
-                        if old_row > 0 && old_row <= viewport.height
-                            && new_row > 0 && new_row <= viewport.height {
+                        if old_row > 0
+                            && old_row <= viewport.height
+                            && new_row > 0
+                            && new_row <= viewport.height
+                        {
                             clear_and_write_command_row(
                                 old_row,
                                 &command_display,
                                 &indexes_to_display[selected_index],
                                 false,
-                                None,
+                                Some(get_list_width(&viewport, indexes_to_display.len())),
+                                match_indices.get(&indexes_to_display[selected_index]),
+                                &display_mode.theme,
                             )?;
 
                             clear_and_write_command_row(
@@ -640,12 +1760,31 @@ pub fn prompt_for_command_choice(
                                 &command_display,
                                 &indexes_to_display[new_index],
                                 true,
-                                None,
+                                Some(get_list_width(&viewport, indexes_to_display.len())),
+                                match_indices.get(&indexes_to_display[new_index]),
+                                &display_mode.theme,
+                            )?;
+
+                            // Clearing the rows also cleared their part of the scrollbar
+                            print_scrollbar(
+                                &viewport,
+                                indexes_to_display.len(),
+                                &display_mode.theme,
                             )?;
                         } else {
                             // If either row isn't visible, we need a full redraw
                             should_reprint = true;
                         }
+
+                        if display_mode.show_preview && !should_reprint {
+                            print_preview(
+                                indexes_to_display
+                                    .get(new_index)
+                                    .and_then(|index| command_display.get(index)),
+                                viewport.width,
+                                &display_mode.theme,
+                            )?;
+                        }
                     }
 
                     selected_index = new_index;
@@ -657,7 +1796,88 @@ pub fn prompt_for_command_choice(
     }
 }
 
-struct RawModeGuard;
+/// Show the actions menu for the selected command at `top_row`, returning the chosen action or
+/// `None` if the menu was closed.
+fn prompt_for_action(top_row: u16, can_edit: bool, theme: &Theme) -> Result<Option<CommandAction>> {
+    let mut stdout = stdout();
+
+    let actions: Vec<&(char, &str, CommandAction)> = COMMAND_ACTIONS
+        .iter()
+        .filter(|(_, _, action)| can_edit || *action != CommandAction::Edit)
+        .collect();
+
+    let (_, height) = terminal::size()?;
+    // Move the menu up if it would go off the bottom of the screen
+    let top_row = top_row.min(height.saturating_sub(actions.len() as u16));
+
+    let menu_width = actions
+        .iter()
+        .map(|(_, description, _)| description.width())
+        .max()
+        .unwrap_or(0)
+        + 8;
+
+    let mut selected_action = 0;
+
+    loop {
+        for (i, (key, description, _)) in actions.iter().enumerate() {
+            let item = format!("  [{key}] {description}");
+
+            queue!(stdout, MoveTo(4, top_row + i as u16))?;
+            // Without colors, only the selected action is drawn reversed
+            if i == selected_action {
+                theme::queue_highlight(
+                    &mut stdout,
+                    theme.selected_background,
+                    theme.selected_foreground,
+                )?;
+            } else {
+                queue!(
+                    stdout,
+                    SetBackgroundColor(theme.header_background),
+                    SetForegroundColor(theme.header_foreground),
+                )?;
+            }
+            queue!(stdout, Print(format!("{item:<menu_width$}")))?;
+            theme::queue_end_highlight(&mut stdout)?;
+        }
+        stdout.flush()?;
+
+        if let Event::Key(key_event) = event::read()? {
+            match key_event.code {
+                KeyCode::Up => {
+                    selected_action = (selected_action + actions.len() - 1) % actions.len()
+                }
+                KeyCode::Down => selected_action = (selected_action + 1) % actions.len(),
+                KeyCode::Enter | KeyCode::Right => return Ok(Some(actions[selected_action].2)),
+                KeyCode::Esc | KeyCode::Left | KeyCode::Char('q') => return Ok(None),
+                KeyCode::Char(c) => {
+                    if let Some((_, _, action)) = actions.iter().find(|(key, _, _)| *key == c) {
+                        return Ok(Some(*action));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Find the row of the command with the given number (as displayed in the list, starting at 1).
+fn find_row_by_number(indexes_to_display: &[CommandIndex], number: &str) -> Option<usize> {
+    let index = number.parse::<usize>().ok()?.checked_sub(1)?;
+
+    indexes_to_display
+        .iter()
+        .position(|command_index| *command_index == Normal(index))
+}
+
+fn toggle_group(collapsed_groups: &mut HashSet<String>, name: &str) {
+    if !collapsed_groups.remove(name) {
+        collapsed_groups.insert(name.to_string());
+    }
+}
+
+pub(crate) struct RawModeGuard;
 
 impl Drop for RawModeGuard {
     fn drop(&mut self) {
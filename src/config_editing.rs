@@ -0,0 +1,565 @@
+use std::fs;
+use std::path::Path;
+
+use crate::command_definitions::{self, CommandDefinition};
+use crate::error::{Error, Result};
+use crate::file_handling;
+use crate::migration::{self, ParameterSets};
+use crate::validation::get_command_lines;
+
+fn read_config(config_path: &str) -> Result<String> {
+    fs::read_to_string(config_path)
+        .map_err(|e| Error::io_error("config".to_string(), config_path.to_string(), e))
+}
+
+/// Where the config is copied to before it's changed.
+pub fn get_backup_path(config_path: &str) -> String {
+    format!("{config_path}.bak")
+}
+
+/// Replace the config, first copying it to the backup so the change can be undone with
+/// `rc restore-config`. Both are written atomically, so a failed write never leaves either half
+/// written.
+pub fn write_config(config_path: &str, config: &str) -> Result<()> {
+    if Path::new(config_path).exists() {
+        file_handling::write_atomically(
+            "config backup",
+            &get_backup_path(config_path),
+            &read_config(config_path)?,
+        )?;
+    }
+
+    file_handling::write_atomically("config", config_path, config)
+}
+
+/// Put back the config from before it was last changed, returning the backup's path. The config
+/// being replaced becomes the backup, so restoring again undoes it.
+pub fn restore_config(config_path: &str) -> Result<String> {
+    let backup_path = get_backup_path(config_path);
+    if !Path::new(&backup_path).exists() {
+        return Err(Error::NoConfigBackup(config_path.to_string()));
+    }
+
+    let backup = fs::read_to_string(&backup_path)
+        .map_err(|e| Error::io_error("config backup".to_string(), backup_path.clone(), e))?;
+    // A backup that can't be read isn't put in place of the config
+    migration::parse_command_definitions(&backup_path, &backup)?;
+
+    let config = fs::read_to_string(config_path).ok();
+    file_handling::write_atomically("config", config_path, &backup)?;
+    if let Some(config) = config {
+        file_handling::write_atomically("config backup", &backup_path, &config)?;
+    }
+
+    Ok(backup_path)
+}
+
+/// Serialize the command definitions as YAML list items, indented to match the config's list.
+fn to_yaml(
+    config_path: &str,
+    command_definitions: &[CommandDefinition],
+    indent: usize,
+) -> Result<String> {
+    let yaml = serde_yaml::to_string(command_definitions).map_err(|e| {
+        Error::yaml_error(
+            "writing".to_string(),
+            "config".to_string(),
+            config_path.to_string(),
+            e,
+        )
+    })?;
+
+    Ok(yaml
+        .lines()
+        .map(|line| format!("{:indent$}{line}\n", ""))
+        .collect())
+}
+
+/// The range of lines (starting at 0) of each command in the config, and the indentation of the
+/// list. Comments and blank lines after a command are left out, as they are more likely to be
+/// about the next command.
+fn get_command_ranges(
+    config_path: &str,
+    config: &str,
+) -> Result<(Vec<std::ops::Range<usize>>, usize)> {
+    let lines: Vec<&str> = config.lines().collect();
+    let command_count = migration::parse_command_definitions(config_path, config)?.len();
+
+    let command_lines = get_command_lines(config, command_count).ok_or_else(|| {
+        Error::ConfigEdit(format!(
+            "the commands in `{config_path}` could not be found, they must be listed with `- ` at the start of each"
+        ))
+    })?;
+
+    let indent = command_lines.first().map_or(0, |line| {
+        lines[line - 1].len() - lines[line - 1].trim_start().len()
+    });
+
+    let ranges = command_lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let start = line - 1;
+            let mut end = command_lines
+                .get(i + 1)
+                .map_or(lines.len(), |next| next - 1);
+
+            while end > start + 1 {
+                let trimmed = lines[end - 1].trim();
+                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                    break;
+                }
+                end -= 1;
+            }
+
+            start..end
+        })
+        .collect();
+
+    Ok((ranges, indent))
+}
+
+/// The config with the lines of the command at `index` replaced. The rest of the config is kept
+/// byte for byte, including its line endings.
+fn replace_lines(
+    config_path: &str,
+    config: &str,
+    index: usize,
+    replacement: impl FnOnce(usize) -> Result<String>,
+) -> Result<String> {
+    let (ranges, indent) = get_command_ranges(config_path, config)?;
+    let range = ranges
+        .get(index)
+        .ok_or(Error::IndexOutOfRange(index))?
+        .clone();
+
+    // Each line with its line ending, so they're written back as they were
+    let lines: Vec<&str> = config.split_inclusive('\n').collect();
+    let line_ending = if config.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let replacement = replacement(indent)?.replace('\n', line_ending);
+
+    // When removing a command, the comments directly above it (other than at the start of the
+    // file) and the blank lines separating it from the next are removed too
+    let mut start = range.start;
+    let mut end = range.end;
+    if replacement.is_empty() {
+        let comment_start = lines[..start]
+            .iter()
+            .rposition(|line| !line.trim_start().starts_with('#'))
+            .map_or(0, |i| i + 1);
+        if comment_start > 0 {
+            start = comment_start;
+        }
+
+        while lines.get(end).is_some_and(|line| line.trim().is_empty()) {
+            end += 1;
+        }
+    }
+
+    Ok(format!(
+        "{}{replacement}{}",
+        lines[..start].concat(),
+        lines[end..].concat()
+    ))
+}
+
+/// Replace the lines of the command at `index` in the config file.
+fn replace_command_lines(
+    config_path: &str,
+    index: usize,
+    replacement: impl FnOnce(usize) -> Result<String>,
+) -> Result<()> {
+    let config = read_config(config_path)?;
+    let updated_config = replace_lines(config_path, &config, index, replacement)?;
+    write_config(config_path, &updated_config)
+}
+
+/// Add the command definitions to the end of the config file's list of commands.
+pub fn append_commands(config_path: &str, command_definitions: &[CommandDefinition]) -> Result<()> {
+    let existing_config = fs::read_to_string(config_path).unwrap_or_default();
+
+    // A new config starts with the latest layout's header
+    let (header, indent) = if existing_config.trim().is_empty() {
+        (migration::config_header(), 0)
+    } else {
+        let indent =
+            get_command_ranges(config_path, &existing_config).map_or(0, |(_, indent)| indent);
+        (String::new(), indent)
+    };
+    let yaml = to_yaml(config_path, command_definitions, indent)?;
+
+    let separator = if existing_config.is_empty() || existing_config.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+
+    write_config(
+        config_path,
+        &format!("{existing_config}{separator}{header}{yaml}"),
+    )
+}
+
+/// The index in the config of the command at `index` in the commands loaded from it. Each variant
+/// is loaded as a command of its own, but is part of its command in the config, so can't be
+/// edited or removed by itself.
+pub fn get_config_index(
+    config_command_definitions: &[CommandDefinition],
+    parameter_sets: &ParameterSets,
+    index: usize,
+) -> Result<usize> {
+    let resolved_definitions = command_definitions::resolve_extends(
+        migration::add_parameter_sets(config_command_definitions.to_vec(), parameter_sets)?,
+    )?;
+
+    let mut loaded_index = 0;
+    for (config_index, command_definition) in resolved_definitions.iter().enumerate() {
+        let variants = command_definition.variants.iter().flatten();
+        let loaded_count = variants.clone().count().max(1);
+
+        if index < loaded_index + loaded_count {
+            return match variants.clone().nth(index - loaded_index) {
+                None => Ok(config_index),
+                Some((variant_name, _)) => Err(Error::ConfigEdit(format!(
+                    "`{variant_name}` is a variant of `{}`, which must be edited or removed instead",
+                    command_definition.history_key()
+                ))),
+            };
+        }
+        loaded_index += loaded_count;
+    }
+
+    Err(Error::IndexOutOfRange(index))
+}
+
+/// Replace the command at `index` in the config file, leaving the rest of the file (including
+/// comments) as it is.
+pub fn update_command(
+    config_path: &str,
+    index: usize,
+    command_definition: &CommandDefinition,
+) -> Result<()> {
+    replace_command_lines(config_path, index, |indent| {
+        to_yaml(
+            config_path,
+            std::slice::from_ref(command_definition),
+            indent,
+        )
+    })
+}
+
+/// Replace the command at `index` in the commands as they are written in the config, checking the
+/// commands can still be resolved with the change before writing it.
+pub fn replace_command(
+    config_path: &str,
+    config_command_definitions: &[CommandDefinition],
+    parameter_sets: &ParameterSets,
+    index: usize,
+    command_definition: &CommandDefinition,
+) -> Result<()> {
+    let mut updated_definitions = config_command_definitions.to_vec();
+    updated_definitions[index] = command_definition.clone();
+    file_handling::resolve_command_definitions(config_path, updated_definitions, parameter_sets)?;
+
+    update_command(config_path, index, command_definition)
+}
+
+/// Remove the command at `index` from the config file, leaving the rest of the file (including
+/// comments) as it is.
+pub fn remove_command(config_path: &str, index: usize) -> Result<()> {
+    let (ranges, _) = get_command_ranges(config_path, &read_config(config_path)?)?;
+    if ranges.len() == 1 {
+        return Err(Error::ConfigEdit(
+            "the only command can't be removed, as there must be at least one".to_string(),
+        ));
+    }
+
+    replace_command_lines(config_path, index, |_| Ok(String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    const CONFIG: &str = r#"# My commands
+version: 2
+commands:
+  # Greets
+  - id: hello
+    command: ["echo", "hello"]  # inline comment
+
+  # Flow style
+  - {id: flow, command: [ls, -la]}
+
+  - id: last
+    command:
+      - echo
+      - bye
+"#;
+
+    const FLOW_ENTRY: &str = "  - {id: flow, command: [ls, -la]}\n";
+
+    fn replace_with(config: &str, index: usize, replacement: &str) -> String {
+        replace_lines("commands.yml", config, index, |_| {
+            Ok(replacement.to_string())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn ranges_leave_out_comments_and_blank_lines_after_a_command() {
+        let (ranges, indent) = get_command_ranges("commands.yml", CONFIG).unwrap();
+
+        assert_eq!(ranges, vec![4..6, 8..9, 10..14]);
+        assert_eq!(indent, 2);
+    }
+
+    #[test]
+    fn ranges_need_commands_in_block_style() {
+        let config = "version: 2\ncommands: [{id: a, command: [ls]}, {id: b, command: [pwd]}]\n";
+
+        assert!(matches!(
+            get_command_ranges("commands.yml", config),
+            Err(Error::ConfigEdit(_))
+        ));
+    }
+
+    #[test]
+    fn replacing_a_flow_style_command_keeps_the_rest() {
+        let replacement = "  - id: flow\n    command: [ls]\n";
+
+        assert_eq!(
+            replace_with(CONFIG, 1, replacement),
+            CONFIG.replace(FLOW_ENTRY, replacement)
+        );
+    }
+
+    #[test]
+    fn replacing_a_command_keeps_its_comments() {
+        let old = "  - id: hello\n    command: [\"echo\", \"hello\"]  # inline comment\n";
+        let replacement = "  - id: hello\n    command: [echo, hi]\n";
+
+        assert_eq!(
+            replace_with(CONFIG, 0, replacement),
+            CONFIG.replace(old, replacement)
+        );
+    }
+
+    #[test]
+    fn replacing_the_last_command_without_a_final_line_ending() {
+        let config = CONFIG.trim_end();
+        let replacement = "  - id: last\n    command: [echo, bye]\n";
+        let start = config.find("  - id: last").unwrap();
+
+        assert_eq!(
+            replace_with(config, 2, replacement),
+            format!("{}{replacement}", &config[..start])
+        );
+    }
+
+    #[test]
+    fn removing_a_command_removes_its_comments_and_the_blank_line_after() {
+        let removed =
+            "  # Greets\n  - id: hello\n    command: [\"echo\", \"hello\"]  # inline comment\n\n";
+
+        assert_eq!(replace_with(CONFIG, 0, ""), CONFIG.replace(removed, ""));
+    }
+
+    #[test]
+    fn removing_the_last_command() {
+        let config = CONFIG.trim_end();
+        let start = config.find("  - id: last").unwrap();
+
+        assert_eq!(replace_with(config, 2, ""), &config[..start]);
+    }
+
+    #[test]
+    fn crlf_line_endings_are_kept() {
+        let config = CONFIG.replace('\n', "\r\n");
+        let replacement = "  - id: flow\n    command: [ls]\n";
+
+        assert_eq!(
+            replace_with(&config, 1, replacement),
+            CONFIG
+                .replace(FLOW_ENTRY, replacement)
+                .replace('\n', "\r\n")
+        );
+    }
+
+    #[test]
+    fn index_out_of_range_is_an_error() {
+        assert!(matches!(
+            replace_lines("commands.yml", CONFIG, 3, |_| Ok(String::new())),
+            Err(Error::IndexOutOfRange(3))
+        ));
+    }
+
+    #[test]
+    fn updated_command_is_written_in_place() {
+        let config_path = env::temp_dir()
+            .join(format!("rc-test-update-{}.yml", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        fs::write(&config_path, CONFIG).unwrap();
+
+        let command_definition = CommandDefinition {
+            id: Some("flow".to_string()),
+            command: vec!["ls".to_string()],
+            ..CommandDefinition::default()
+        };
+        let result = update_command(&config_path, 1, &command_definition);
+        let config = fs::read_to_string(&config_path).unwrap();
+        let backup = fs::read_to_string(get_backup_path(&config_path)).unwrap();
+        let _ = fs::remove_file(&config_path);
+        let _ = fs::remove_file(get_backup_path(&config_path));
+
+        result.unwrap();
+        assert_eq!(
+            config,
+            CONFIG.replace(FLOW_ENTRY, "  - id: flow\n    command:\n    - ls\n")
+        );
+        assert_eq!(backup, CONFIG);
+        assert_eq!(
+            migration::parse_command_definitions(&config_path, &config)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    /// Edit the command at `index` as `rc edit` does, changing its YAML with `edit`. Returns the
+    /// config after, and the commands reloaded from it.
+    fn edit_and_reload(
+        name: &str,
+        config: &str,
+        index: usize,
+        edit: impl FnOnce(String) -> String,
+    ) -> (String, Vec<CommandDefinition>) {
+        let config_path = env::temp_dir()
+            .join(format!("rc-test-edit-{name}-{}.yml", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        fs::write(&config_path, config).unwrap();
+
+        let (config_command_definitions, parameter_sets) =
+            file_handling::get_config_command_definitions(&config_path).unwrap();
+        let edited_yaml = edit(serde_yaml::to_string(&config_command_definitions[index]).unwrap());
+        let edited_definition: CommandDefinition = serde_yaml::from_str(&edited_yaml).unwrap();
+        let result = replace_command(
+            &config_path,
+            &config_command_definitions,
+            &parameter_sets,
+            index,
+            &edited_definition,
+        );
+        let edited_config = fs::read_to_string(&config_path).unwrap();
+        let command_definitions = file_handling::get_command_definitions(&config_path);
+        let _ = fs::remove_file(&config_path);
+        let _ = fs::remove_file(get_backup_path(&config_path));
+
+        result.unwrap();
+        (edited_config, command_definitions.unwrap())
+    }
+
+    #[test]
+    fn edited_command_keeps_what_it_extends() {
+        let config = "version: 2\ncommands:\n  - id: base\n    command: [git]\n  - id: status\n    extends: base\n    command: [status]\n";
+
+        let (edited_config, command_definitions) = edit_and_reload("extends", config, 1, |yaml| {
+            yaml.replace("- status", "- status\n- --short")
+        });
+
+        assert_eq!(
+            edited_config,
+            config.replace(
+                "  - id: status\n    extends: base\n    command: [status]\n",
+                "  - id: status\n    extends: base\n    command:\n    - status\n    - --short\n"
+            )
+        );
+        assert_eq!(command_definitions[1].command, ["git", "status", "--short"]);
+    }
+
+    #[test]
+    fn edited_command_keeps_its_parameter_sets() {
+        let config = "version: 2\nparameter_sets:\n  cluster: [{name: region, default: eu}]\ncommands:\n  - id: pods\n    parameter_sets: [cluster]\n    command: [kubectl, get, pods]\n";
+
+        let (edited_config, command_definitions) =
+            edit_and_reload("parameter-sets", config, 0, |yaml| {
+                yaml.replace("- pods", "- pods\n- -A")
+            });
+
+        assert!(!edited_config.contains("parameters:"));
+        assert!(edited_config.contains("    parameter_sets:\n    - cluster\n"));
+        let parameters = command_definitions[0].parameters.as_deref().unwrap();
+        assert_eq!(parameters.len(), 1);
+        assert_eq!(parameters[0].name, "region");
+        assert_eq!(
+            command_definitions[0].command,
+            ["kubectl", "get", "pods", "-A"]
+        );
+    }
+
+    const VARIANTS_CONFIG: &str = r#"version: 2
+commands:
+  - id: deploy
+    command: [deploy]
+    variants:
+      prod: {}
+      dev: {}
+  - id: status
+    extends: deploy
+    command: [status]
+    variants: {}
+  - id: logs
+    command: [logs]
+"#;
+
+    fn get_loaded_config_index(index_or_id: &str) -> Result<usize> {
+        let (config_command_definitions, parameter_sets) =
+            migration::parse_config("commands.yml", VARIANTS_CONFIG).unwrap();
+        let command_definitions = file_handling::resolve_command_definitions(
+            "commands.yml",
+            config_command_definitions.clone(),
+            &parameter_sets,
+        )
+        .unwrap();
+        let index = command_definitions::get_command_index(&command_definitions, index_or_id)?;
+
+        get_config_index(&config_command_definitions, &parameter_sets, index)
+    }
+
+    #[test]
+    fn commands_after_variants_are_found_in_the_config() {
+        assert_eq!(get_loaded_config_index("status").unwrap(), 1);
+        assert_eq!(get_loaded_config_index("logs").unwrap(), 2);
+        assert_eq!(get_loaded_config_index("3").unwrap(), 2);
+
+        let removed = "  - id: logs\n    command: [logs]\n";
+        assert_eq!(
+            replace_with(
+                VARIANTS_CONFIG,
+                get_loaded_config_index("logs").unwrap(),
+                ""
+            ),
+            VARIANTS_CONFIG.replace(removed, "")
+        );
+    }
+
+    #[test]
+    fn variants_are_not_edited_by_themselves() {
+        assert!(matches!(
+            get_loaded_config_index("deploy@prod"),
+            Err(Error::ConfigEdit(message)) if message.starts_with("`prod` is a variant of `deploy`")
+        ));
+        assert!(matches!(
+            get_loaded_config_index("0"),
+            Err(Error::ConfigEdit(message)) if message.starts_with("`dev` is a variant of `deploy`")
+        ));
+    }
+}
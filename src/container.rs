@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::execution::has_terminal;
+use crate::interpolation::shell_quote;
+use crate::secrets::ConfigValue;
+
+/// The program that runs containers.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerEngine {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerEngine {
+    pub fn binary(self) -> &'static str {
+        match self {
+            ContainerEngine::Docker => "docker",
+            ContainerEngine::Podman => "podman",
+        }
+    }
+}
+
+/// A container to run the command in, either started from an `image` for each run, or one that's
+/// already running, by its `name`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ContainerDefinition {
+    /// Image to start the container from, e.g. `node:20`. The container is removed when the
+    /// command finishes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Name of a running container to run the command in, instead of starting one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Paths to mount in a container started from the image, as `host:container`, e.g.
+    /// `.:/app`. Relative host paths are from the command's working directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mounts: Option<Vec<String>>,
+    /// Directory in the container to run the command in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workdir: Option<String>,
+    /// `docker` if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<ContainerEngine>,
+}
+
+/// The mount for the engine's `-v`, with the host path made absolute, as the engine may not
+/// accept relative paths. These are from `$PWD`, which is the working directory the command is
+/// run in.
+fn get_mount_argument(mount: &str) -> String {
+    let (host_path, container_path) = match mount.split_once(':') {
+        Some((host_path, container_path)) => (host_path, Some(container_path)),
+        None => (mount, None),
+    };
+
+    let host_path = shellexpand::tilde(host_path);
+    let host_path = if host_path.starts_with('/') {
+        shell_quote(&host_path)
+    } else {
+        match host_path.trim_start_matches("./") {
+            "" | "." => "\"$PWD\"".to_string(),
+            relative_path => format!("\"$PWD\"/{}", shell_quote(relative_path)),
+        }
+    };
+
+    match container_path {
+        Some(container_path) => format!("{host_path}:{}", shell_quote(container_path)),
+        None => host_path,
+    }
+}
+
+impl ContainerDefinition {
+    pub fn engine(&self) -> ContainerEngine {
+        self.engine.unwrap_or_default()
+    }
+
+    /// Error unless exactly one of `image` and `name` is given.
+    pub fn check(&self) -> Result<()> {
+        match (&self.image, &self.name) {
+            (Some(_), Some(_)) => Err(Error::InvalidContainer(
+                "give either `image` or `name`, not both".to_string(),
+            )),
+            (None, None) => Err(Error::InvalidContainer(
+                "`image` or `name` is required".to_string(),
+            )),
+            (None, Some(_)) if self.mounts.is_some() => Err(Error::InvalidContainer(
+                "`mounts` can only be used with `image`, a running container already has its mounts"
+                    .to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The command that runs `command` with the shell in the container. The environment
+    /// variables are passed on to it, so are set in the container too.
+    pub fn wrap_command(
+        &self,
+        command: &str,
+        environment: Option<&HashMap<String, ConfigValue>>,
+    ) -> String {
+        let mut arguments = vec![self.engine().binary().to_string()];
+
+        match &self.name {
+            Some(_) => arguments.push("exec".to_string()),
+            None => arguments.extend(["run".to_string(), "--rm".to_string()]),
+        }
+
+        arguments.push("-i".to_string());
+        if has_terminal() {
+            arguments.push("-t".to_string());
+        }
+
+        for mount in self.mounts.iter().flatten() {
+            arguments.extend(["-v".to_string(), get_mount_argument(mount)]);
+        }
+
+        for key in environment.into_iter().flat_map(HashMap::keys).sorted() {
+            arguments.extend(["-e".to_string(), shell_quote(key)]);
+        }
+
+        if let Some(workdir) = &self.workdir {
+            arguments.extend(["-w".to_string(), shell_quote(workdir)]);
+        }
+
+        let container = self.name.as_ref().or(self.image.as_ref());
+        arguments.extend(container.map(|container| shell_quote(container)));
+        arguments.extend(["sh".to_string(), "-c".to_string(), shell_quote(command)]);
+
+        arguments.join(" ")
+    }
+}
+
+impl Display for ContainerDefinition {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.image, &self.name) {
+            (_, Some(name)) => write!(formatter, "{} container `{name}`", self.engine().binary()),
+            (Some(image), None) => write!(
+                formatter,
+                "new {} container from `{image}`",
+                self.engine().binary()
+            ),
+            (None, None) => write!(formatter, "{} container", self.engine().binary()),
+        }
+    }
+}
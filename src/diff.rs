@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use crossterm::style::{Color, Stylize};
+use itertools::Itertools;
+
+use crate::theme;
+
+enum WordChange<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Split the text into words and the whitespace between them, so that joining them gives back
+/// the text.
+fn split_words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut was_whitespace = None;
+
+    for (i, c) in text.char_indices() {
+        let is_whitespace = c.is_whitespace();
+        if was_whitespace.is_some_and(|was_whitespace| was_whitespace != is_whitespace) {
+            words.push(&text[start..i]);
+            start = i;
+        }
+        was_whitespace = Some(is_whitespace);
+    }
+
+    if start < text.len() {
+        words.push(&text[start..]);
+    }
+
+    words
+}
+
+/// The words removed from and added to `old` to get `new`, keeping as many words the same as
+/// possible (the longest common subsequence).
+fn diff_words<'a>(old: &'a str, new: &'a str) -> Vec<WordChange<'a>> {
+    let old_words = split_words(old);
+    let new_words = split_words(new);
+
+    // `common[i][j]` is how many words are in common between the old words from `i` and the new
+    // words from `j`
+    let mut common = vec![vec![0; new_words.len() + 1]; old_words.len() + 1];
+    for i in (0..old_words.len()).rev() {
+        for j in (0..new_words.len()).rev() {
+            common[i][j] = if old_words[i] == new_words[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old_words.len() || j < new_words.len() {
+        if i < old_words.len() && j < new_words.len() && old_words[i] == new_words[j] {
+            changes.push(WordChange::Same(old_words[i]));
+            i += 1;
+            j += 1;
+        } else if j == new_words.len()
+            || (i < old_words.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            changes.push(WordChange::Removed(old_words[i]));
+            i += 1;
+        } else {
+            changes.push(WordChange::Added(new_words[j]));
+            j += 1;
+        }
+    }
+
+    changes
+}
+
+/// The new command, with the words that changed from the old command highlighted: the old words
+/// crossed out in red, followed by the new words in green. Without colors, they're marked like
+/// `git diff --word-diff` instead, e.g. `[-old-]{+new+}`.
+pub fn highlight_command_changes(old: &str, new: &str) -> String {
+    let is_color_enabled = theme::is_color_enabled();
+
+    diff_words(old, new)
+        .into_iter()
+        .map(|change| match change {
+            WordChange::Same(word) => word.to_string(),
+            WordChange::Removed(word) if is_color_enabled => {
+                word.with(Color::Red).crossed_out().to_string()
+            }
+            WordChange::Added(word) if is_color_enabled => {
+                word.with(Color::Green).bold().to_string()
+            }
+            WordChange::Removed(word) => format!("[-{word}-]"),
+            WordChange::Added(word) => format!("{{+{word}+}}"),
+        })
+        .collect()
+}
+
+/// Describe the parameters whose values changed, e.g. `host: 10.1.2.3 → 10.1.2.4`.
+pub fn describe_parameter_changes(
+    old: &Option<HashMap<String, String>>,
+    new: &Option<HashMap<String, String>>,
+) -> Vec<String> {
+    let (Some(old), Some(new)) = (old, new) else {
+        return Vec::new();
+    };
+
+    new.iter()
+        .sorted()
+        .filter_map(|(name, value)| match old.get(name) {
+            Some(old_value) if old_value != value => Some(format!("{name}: {old_value} → {value}")),
+            _ => None,
+        })
+        .collect()
+}
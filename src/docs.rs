@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use crate::command_definitions::CommandDefinition;
+use crate::error::{Error, Result};
+use crate::explain::render_with_defaults;
+use crate::interpolation::{build_default_lookup, PreparedTemplate};
+
+/// Heading for the commands without any tags.
+const UNTAGGED_HEADING: &str = "Other commands";
+
+/// A command ready to be written in the docs.
+struct DocsCommand {
+    title: String,
+    ids: Vec<String>,
+    /// How to run the command with `rc`, giving the parameters without defaults. `None` if it
+    /// has no ID to run it with.
+    example: Option<String>,
+    /// Parameters in the order they're used, with their defaults.
+    parameters: Vec<(String, Option<String>)>,
+    environment: Vec<(String, String)>,
+    /// The working directory and command with the defaults filled in.
+    working_directory: Option<String>,
+    command: String,
+}
+
+fn get_docs_command(command_definition: &CommandDefinition) -> Result<DocsCommand> {
+    let template = PreparedTemplate::parse(
+        &command_definition.command,
+        command_definition.working_directory.as_ref(),
+    )?;
+    let defaults = build_default_lookup(&command_definition.parameters).unwrap_or_default();
+
+    let parameters: Vec<(String, Option<String>)> = template
+        .order_tokens(template.tokens())
+        .into_iter()
+        .map(|name| {
+            let default = defaults.get(&name).cloned();
+            (name, default)
+        })
+        .collect();
+
+    let example = command_definition.id.as_ref().map(|id| {
+        let required_parameters = parameters
+            .iter()
+            .filter(|(_, default)| default.is_none())
+            .map(|(name, _)| format!(" --param {name}=<{name}>"))
+            .join("");
+        format!("rc {id}{required_parameters}")
+    });
+
+    let (working_directory, command) = render_with_defaults(&template, defaults)?;
+
+    Ok(DocsCommand {
+        title: command_definition
+            .name
+            .as_ref()
+            .or(command_definition.id.as_ref())
+            .cloned()
+            .unwrap_or_else(|| command_definition.command.join(" ")),
+        ids: command_definition.ids().cloned().collect(),
+        example,
+        parameters,
+        environment: command_definition
+            .environment
+            .iter()
+            .flatten()
+            .sorted()
+            .map(|(key, value)| (key.clone(), value.to_string()))
+            .collect(),
+        working_directory,
+        command,
+    })
+}
+
+/// Write the command's section: its name and IDs, how to run it, its parameters and environment,
+/// and what it runs.
+fn write_command(docs_command: &DocsCommand, output: &mut String) -> std::fmt::Result {
+    writeln!(output, "### {}\n", docs_command.title)?;
+
+    if !docs_command.ids.is_empty() {
+        let ids = docs_command
+            .ids
+            .iter()
+            .map(|id| format!("`{id}`"))
+            .join(", ");
+        writeln!(output, "ID: {ids}\n")?;
+    }
+
+    if let Some(example) = &docs_command.example {
+        writeln!(output, "```shell\n{example}\n```\n")?;
+    }
+
+    if !docs_command.parameters.is_empty() {
+        writeln!(output, "Parameters:\n")?;
+        for (name, default) in &docs_command.parameters {
+            match default {
+                Some(default) => writeln!(output, "- `{name}` (default: `{default}`)")?,
+                None => writeln!(output, "- `{name}` (required)")?,
+            }
+        }
+        writeln!(output)?;
+    }
+
+    if !docs_command.environment.is_empty() {
+        writeln!(output, "Environment:\n")?;
+        for (key, value) in &docs_command.environment {
+            writeln!(output, "- `{key}={value}`")?;
+        }
+        writeln!(output)?;
+    }
+
+    writeln!(output, "Runs, with the defaults:\n\n```shell")?;
+    if let Some(working_directory) = &docs_command.working_directory {
+        writeln!(output, "cd {working_directory}")?;
+    }
+    writeln!(output, "{}\n```\n", docs_command.command)
+}
+
+/// Render the commands as Markdown, with a section for each tag (a command with several tags is
+/// in each of their sections), then the commands without tags.
+pub fn render_docs(command_definitions: &[CommandDefinition]) -> Result<String> {
+    let mut commands_by_tag: BTreeMap<&str, Vec<DocsCommand>> = BTreeMap::new();
+    let mut untagged_commands = Vec::new();
+
+    for command_definition in command_definitions {
+        match command_definition.tags.as_deref() {
+            Some(tags) if !tags.is_empty() => {
+                for tag in tags.iter().unique() {
+                    commands_by_tag
+                        .entry(tag)
+                        .or_default()
+                        .push(get_docs_command(command_definition)?);
+                }
+            }
+            _ => untagged_commands.push(get_docs_command(command_definition)?),
+        }
+    }
+
+    let sections = commands_by_tag
+        .into_iter()
+        .chain((!untagged_commands.is_empty()).then_some((UNTAGGED_HEADING, untagged_commands)));
+
+    let mut output = String::from("# Commands\n\n");
+    for (heading, docs_commands) in sections {
+        writeln!(output, "## {heading}\n")
+            .and_then(|()| {
+                docs_commands
+                    .iter()
+                    .try_for_each(|docs_command| write_command(docs_command, &mut output))
+            })
+            .map_err(|e| Error::Misc(format!("Could not write docs: {e}")))?;
+    }
+
+    Ok(output.trim_end().to_string() + "\n")
+}
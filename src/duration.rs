@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// Units that can be used in a duration, e.g. `1h30m`.
+const DURATION_UNITS: [(char, u64); 4] = [('d', 24 * 60 * 60), ('h', 60 * 60), ('m', 60), ('s', 1)];
+
+/// Parse a duration like `90s`, `10m` or `1h30m`.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let invalid_duration = || Error::InvalidDuration(value.to_string());
+
+    let mut seconds = 0;
+    let mut amount = String::new();
+
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            amount.push(c);
+            continue;
+        }
+
+        let (_, unit_seconds) = DURATION_UNITS
+            .iter()
+            .find(|(unit, _)| *unit == c)
+            .ok_or_else(invalid_duration)?;
+        let unit_amount: u64 = amount.parse().map_err(|_| invalid_duration())?;
+
+        seconds += unit_amount * unit_seconds;
+        amount.clear();
+    }
+
+    // Every amount must have a unit
+    if !amount.is_empty() || seconds == 0 {
+        return Err(invalid_duration());
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// The duration in whole milliseconds, as stored in the history.
+pub fn as_millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Format a duration for display, e.g. `2.5s`, `4m 5s` or `1h 2m`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    match seconds {
+        0..=59 => format!("{:.1}s", duration.as_secs_f64()),
+        60..=3599 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
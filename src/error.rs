@@ -4,20 +4,31 @@ use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Exit code when the arguments are invalid, e.g. an unknown command, the same as `clap` uses.
+pub const USAGE_EXIT_CODE: u8 = 2;
+
+/// Exit code when the command definitions (or other config files) are invalid.
+pub const CONFIG_EXIT_CODE: u8 = 3;
+
+/// Exit code when something needed to run the command isn't available, e.g. a binary it requires.
+pub const UNAVAILABLE_EXIT_CODE: u8 = 4;
+
+/// Exit code when running the command was cancelled, e.g. by not confirming it.
+pub const CANCELLED_EXIT_CODE: u8 = 5;
+
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("The sub process exiting with non-success code.")]
-    SubProcessExit,
-
     #[error("Error with sub process process: {}", _0)]
     SubProcess(#[from] std::io::Error),
 
-    #[error("Error {} {} file at `{}`: {}", .action, .file_description, .path, .original)]
+    #[error("Error {} {} file at `{}`: {}{}", .action, .file_description, .path, .original, .details)]
     Yaml {
         action: String,
         file_description: String,
         path: String,
         original: serde_yaml::Error,
+        /// Where the error is in the file, and suggestions to fix it.
+        details: String,
     },
 
     #[error("For a color, only one of `rgb`, `ansi` or `name` should be defined.")]
@@ -42,9 +53,204 @@ pub enum Error {
     #[error("Error placeholder template string: {}", .0)]
     Render(#[from] RenderError),
 
+    #[error("Error writing JSON output: {}", .0)]
+    Json(#[from] serde_json::Error),
+
+    #[error("Command index out of range: {}.", .0)]
+    IndexOutOfRange(usize),
+
+    #[error("Cancelled.")]
+    Cancelled,
+
     #[error("Rerun flag specified with an index is invalid.")]
     RerunWithIndex,
 
+    #[error("No command with ID `{id}` was found.{}", describe_suggestions(.suggestions))]
+    CommandNotFound {
+        id: String,
+        suggestions: Vec<String>,
+    },
+
+    #[error("Command ID or alias `{}` is used by more than one command.", .0)]
+    DuplicateCommandId(String),
+
+    #[error("Command ID or alias `{}` is invalid, IDs can't be numbers as they would clash with indexes.", .0)]
+    NumericCommandId(String),
+
+    #[error("Command `{}` extends `{}`, but there's no command with that ID.", .command, .base)]
+    UnknownBaseCommand { command: String, base: String },
+
+    #[error("Command `{}` uses parameter set `{}`, which isn't in `parameter_sets`.", .command, .parameter_set)]
+    UnknownParameterSet {
+        command: String,
+        parameter_set: String,
+    },
+
+    #[error("The command definitions have keys that aren't fields, which the `strict` setting doesn't allow:\n{}", .0)]
+    UnknownFields(String),
+
+    #[error("Invalid `container`: {}.", .0)]
+    InvalidContainer(String),
+
+    #[error("Invalid `kubectl`: {}.", .0)]
+    InvalidKubectl(String),
+
+    #[error("Command `{}` is chained to `{}`, but there's no command with that ID.", .command, .chained)]
+    UnknownChainedCommand { command: String, chained: String },
+
+    #[error("Chained commands can't lead back to a command already in the chain: `{}`.", .0)]
+    CommandChainCycle(String),
+
+    #[error("Commands can't extend each other in a cycle: `{}`.", .0)]
+    CommandExtendsCycle(String),
+
+    #[error("Command `{}` is marked as dangerous, so must have an `id` to type when confirming.", .0)]
+    DangerousCommandWithoutId(String),
+
+    #[error("Could not copy to the clipboard, no clipboard command (pbcopy, wl-copy, xclip, xsel or clip) is available.")]
+    NoClipboard,
+
+    #[error("Key `{}` can't be bound, it must be `ctrl-` or `alt-` and a letter, e.g. `ctrl-g`.", .0)]
+    InvalidKeyBinding(String),
+
+    #[error(
+        "Nothing to prune, give `--older-than` or `--keep`, or set `history` in the settings."
+    )]
+    NoHistoryLimits,
+
+    #[error("Could not read the clipboard, no clipboard command (pbpaste, wl-paste, xclip, xsel or powershell) is available.")]
+    NoClipboardPaste,
+
+    #[error("Could not find the last command run in the shell, {}.", .0)]
+    NoShellHistory(String),
+
+    #[error("Could not sync source `{}`: {}", .source_name, .message)]
+    SourceSync {
+        source_name: String,
+        message: String,
+    },
+
+    #[error("Profile `{}` is not defined in the settings.", .0)]
+    UnknownProfile(String),
+
+    #[error("There is no bookmark `{}`, see `rc bookmark list`.", .0)]
+    UnknownBookmark(String),
+
+    #[error("Bookmark `{}` already exists, use `--force` to replace it.", .0)]
+    BookmarkExists(String),
+
+    #[error("There is no job `{}`, see `rc jobs`.", .0)]
+    UnknownJob(String),
+
+    #[error("No command has been run yet, so there's nothing to bookmark.")]
+    NoLastCommand,
+
+    #[error("`{}` isn't in the history, so it has no previous parameters to use.", .0)]
+    NoPreviousRun(String),
+
+    #[error("There's no backup of `{}` to restore, as `rc` hasn't changed it yet.", .0)]
+    NoConfigBackup(String),
+
+    #[error("Working directory `{}` does not exist.", .0)]
+    WorkingDirectoryNotFound(String),
+
+    #[error("Could not open a new terminal, no supported terminal emulator was found.")]
+    NoTerminal,
+
+    #[error("Could not open a new terminal, `{}` was not found.", .0)]
+    TerminalNotFound(String),
+
+    #[error("Unknown terminal `{}`, give its `command` and `arguments` in the `terminal` setting instead.", .0)]
+    UnknownTerminal(String),
+
+    #[error("Could not run the command in tmux: {}", .0)]
+    Tmux(String),
+
+    #[error("Invalid --each value `{}`, expected `name=value1,value2`.", .0)]
+    InvalidEach(String),
+
+    #[error("Command `{}` doesn't use `{{args...}}`, so can't be given arguments after `--`.", .0)]
+    ArgumentsNotForwarded(String),
+
+    #[error("Invalid --param value `{}`, expected `name=value`.", .0)]
+    InvalidParam(String),
+
+    #[error("Invalid schedule: {}.", .0)]
+    InvalidSchedule(String),
+
+    #[error("Command `{}` has no `id`, which it needs to be scheduled, so the schedule still runs it if the commands are reordered.", .0)]
+    ScheduleWithoutId(String),
+
+    #[error("There is no schedule `{}`, see `rc schedule list`.", .0)]
+    UnknownSchedule(String),
+
+    #[error("Schedule `{}` already exists, use `--force` to replace it.", .0)]
+    ScheduleExists(String),
+
+    #[error("Could not install the schedule: {}", .0)]
+    Schedule(String),
+
+    #[error("Could not read {}.", .0)]
+    Secret(String),
+
+    #[error("Could not watch for changes: {}", .0)]
+    Watch(String),
+
+    #[error("No logs were found for `{}`, set `log_output` on the command to save its output.", .0)]
+    NoLogs(String),
+
+    #[error("A command index or ID must be given when not running interactively.")]
+    CommandRequired,
+
+    #[error("No value was given for `{}`, and parameters can't be prompted for when not running interactively.", .0)]
+    MissingParameters(String),
+
+    #[error("Command `{}` is dangerous, so can't be run when not running interactively, as it must be confirmed.", .0)]
+    DangerousNonInteractive(String),
+
+    #[error("Invalid duration `{}`, expected a number followed by `s`, `m`, `h` or `d`, e.g. `10m` or `1h30m`.", .0)]
+    InvalidDuration(String),
+
+    #[error("Invalid conditional section: {}.", .0)]
+    InvalidConditional(String),
+
+    #[error("Unknown filter `{}`, expected one of: upper, lower, trim, quote or slugify.", .0)]
+    UnknownFilter(String),
+
+    #[error("Command `{}` is not available on this platform, it can only be run on: {}.", .command, .platforms)]
+    UnavailableOnPlatform { command: String, platforms: String },
+
+    #[error("Command `{}` needs version {} of rc, but this is version {}. Upgrade rc to use it.", .command, .requirement, crate::version::RC_VERSION)]
+    UpgradeNeeded {
+        command: String,
+        requirement: String,
+    },
+
+    #[error("Invalid `requires_version` `{}`, expected versions with `>=`, `>`, `<=`, `<` or `=`, e.g. `>=0.6`.", .0)]
+    InvalidVersionRequirement(String),
+
+    #[error(
+        "Could not find `{}` on the PATH, which the command requires.{}",
+        .missing,
+        .install_hint.as_ref().map(|hint| format!("\n{hint}")).unwrap_or_default()
+    )]
+    MissingRequirements {
+        missing: String,
+        install_hint: Option<String>,
+    },
+
+    #[error("Could not edit the command definitions: {}.", .0)]
+    ConfigEdit(String),
+
+    #[error("`{}` is for version {} of the command definitions, but this version of rc only supports up to version {}. Update rc to use it.", .path, .version, crate::migration::CONFIG_VERSION)]
+    UnsupportedConfigVersion { path: String, version: u64 },
+
+    #[error("`{}` uses an old layout of the command definitions: {}. Run `rc migrate` to update it.", .path, .message)]
+    OutdatedConfig { path: String, message: String },
+
+    #[error("`{}` must be a list of commands, or have them under `commands` with the `version` of the layout, e.g. `version: {}`.", .0, crate::migration::CONFIG_VERSION)]
+    InvalidConfigLayout(String),
+
     #[error("Misc error: {}", .0)]
     Misc(String),
 
@@ -53,6 +259,81 @@ pub enum Error {
 }
 
 impl Error {
+    /// Exit code for `rc` to exit with, so scripts can tell what kind of error happened.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::IndexOutOfRange(_)
+            | Self::RerunWithIndex
+            | Self::CommandNotFound { .. }
+            | Self::UnknownProfile(_)
+            | Self::UnknownBookmark(_)
+            | Self::BookmarkExists(_)
+            | Self::UnknownJob(_)
+            | Self::InvalidEach(_)
+            | Self::InvalidParam(_)
+            | Self::ArgumentsNotForwarded(_)
+            | Self::InvalidSchedule(_)
+            | Self::ScheduleWithoutId(_)
+            | Self::UnknownSchedule(_)
+            | Self::ScheduleExists(_)
+            | Self::CommandRequired
+            | Self::MissingParameters(_)
+            | Self::DangerousNonInteractive(_)
+            | Self::InvalidKeyBinding(_)
+            | Self::NoHistoryLimits => USAGE_EXIT_CODE,
+            Self::Yaml { .. }
+            | Self::MultipleColorTypes
+            | Self::UnknownColorName(_)
+            | Self::EmptyCommandDefinition { .. }
+            | Self::Parse(_)
+            | Self::DuplicateCommandId(_)
+            | Self::NumericCommandId(_)
+            | Self::DangerousCommandWithoutId(_)
+            | Self::UnknownBaseCommand { .. }
+            | Self::CommandExtendsCycle(_)
+            | Self::UnknownParameterSet { .. }
+            | Self::UnknownFields(_)
+            | Self::InvalidContainer(_)
+            | Self::InvalidKubectl(_)
+            | Self::UnknownChainedCommand { .. }
+            | Self::CommandChainCycle(_)
+            | Self::InvalidDuration(_)
+            | Self::InvalidConditional(_)
+            | Self::InvalidVersionRequirement(_)
+            | Self::UnknownFilter(_)
+            | Self::UnknownTerminal(_)
+            | Self::ConfigEdit(_)
+            | Self::UnsupportedConfigVersion { .. }
+            | Self::OutdatedConfig { .. }
+            | Self::InvalidConfigLayout(_) => CONFIG_EXIT_CODE,
+            Self::NoClipboard
+            | Self::NoClipboardPaste
+            | Self::NoShellHistory(_)
+            | Self::NoLastCommand
+            | Self::NoPreviousRun(_)
+            | Self::NoConfigBackup(_)
+            | Self::WorkingDirectoryNotFound(_)
+            | Self::NoTerminal
+            | Self::TerminalNotFound(_)
+            | Self::Tmux(_)
+            | Self::Schedule(_)
+            | Self::Secret(_)
+            | Self::UnavailableOnPlatform { .. }
+            | Self::UpgradeNeeded { .. }
+            | Self::MissingRequirements { .. } => UNAVAILABLE_EXIT_CODE,
+            Self::Cancelled => CANCELLED_EXIT_CODE,
+            Self::SubProcess(_)
+            | Self::Io { .. }
+            | Self::Render(_)
+            | Self::Json(_)
+            | Self::SourceSync { .. }
+            | Self::Watch(_)
+            | Self::NoLogs(_)
+            | Self::Misc(_)
+            | Self::Stdio(_) => 1,
+        }
+    }
+
     pub fn empty_command_definition(path: String) -> Self {
         Self::EmptyCommandDefinition { path }
     }
@@ -68,6 +349,7 @@ impl Error {
             file_description,
             path,
             original,
+            details: String::new(),
         }
     }
 
@@ -79,3 +361,12 @@ impl Error {
         }
     }
 }
+
+/// The IDs suggested when no command has the ID given, e.g. ` Did you mean: deploy, deploy-docs?`
+fn describe_suggestions(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(" Did you mean: {}?", suggestions.join(", "))
+    }
+}
@@ -1,29 +1,485 @@
-use std::collections::HashMap;
-use std::process::{Command, Stdio};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs::File;
+use std::io::{stdin, stdout, ErrorKind, IsTerminal, Read, Write};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
+use itertools::Itertools;
 use log::info;
+use serde::Serialize;
 
+use crate::command_definitions::CommandExecutionTemplate;
+use crate::container::ContainerDefinition;
+use crate::duration;
 use crate::error::{Error, Result};
+use crate::interpolation::PreparedTemplate;
+use crate::interrupt::{self, RunningChild};
+use crate::kubectl::KubectlDefinition;
+use crate::secrets::ConfigValue;
 
-pub fn execute_command(
-    mut command: Command,
-    environment: Option<HashMap<String, String>>,
-) -> Result<()> {
-    let mut command = command
-        .stdin(Stdio::inherit())
+/// Exit code to use if the sub process did not exit with a code and was not killed by a signal.
+const UNKNOWN_EXIT_CODE: i32 = 1;
+
+/// Exit codes are offset by this value when the sub process was terminated by a signal,
+/// following the shell convention (e.g. 130 for SIGINT).
+#[cfg(unix)]
+const SIGNAL_EXIT_CODE_OFFSET: i32 = 128;
+
+/// A command with its parameters filled in, ready to run. However the command is run (in this
+/// terminal, with `--each`, in a new terminal or in tmux), it is prepared the same way.
+#[derive(Debug, Clone)]
+pub struct PreparedCommand {
+    pub command: String,
+    /// The working directory as given, e.g. without `~` expanded.
+    pub working_directory: Option<String>,
+    pub environment: Option<HashMap<String, ConfigValue>>,
+    pub container: Option<ContainerDefinition>,
+    /// The pod to run the command in, with its parameters filled in.
+    pub kubectl: Option<KubectlDefinition>,
+    secret_parameters: Option<SecretParameters>,
+}
+
+/// What's needed to fill in the command again with its secret parameters' values, which is only
+/// done when it runs.
+#[derive(Debug, Clone)]
+struct SecretParameters {
+    command: Vec<String>,
+    template_context: HashMap<String, String>,
+    /// The secret's name for each parameter whose value is the secret.
+    names: BTreeMap<String, String>,
+}
+
+impl SecretParameters {
+    /// The parameters that are filled in with the secret they default to. A parameter given some
+    /// other value isn't a secret.
+    fn new(
+        execution_context: &CommandExecutionTemplate,
+        template_context: &Option<HashMap<String, String>>,
+    ) -> Option<Self> {
+        let template_context = template_context.as_ref()?;
+        let names = execution_context
+            .secret_parameters
+            .iter()
+            .flatten()
+            .filter(|(parameter, name)| {
+                template_context.get(*parameter)
+                    == Some(&ConfigValue::Secret((*name).clone()).to_string())
+            })
+            .map(|(parameter, name)| (parameter.clone(), name.clone()))
+            .collect::<BTreeMap<String, String>>();
+
+        (!names.is_empty()).then(|| SecretParameters {
+            command: execution_context.command.clone(),
+            template_context: template_context.clone(),
+            names,
+        })
+    }
+
+    /// The command with the secrets read and filled in.
+    fn render_command(&self) -> Result<String> {
+        let mut template_context = self.template_context.clone();
+        for (parameter, name) in &self.names {
+            template_context.insert(
+                parameter.clone(),
+                ConfigValue::Secret(name.clone()).reveal()?,
+            );
+        }
+
+        PreparedTemplate::parse(&self.command, None)?.render_command(&Some(template_context))
+    }
+}
+
+/// Whether stdin and stdout are the terminal, so a command run in a container or pod can be given
+/// one. Asking for a terminal without one fails.
+pub fn has_terminal() -> bool {
+    stdin().is_terminal() && stdout().is_terminal()
+}
+
+/// Fill in the parameters of the command (parsed as `template`) and its working directory.
+pub fn prepare_execution(
+    execution_context: &CommandExecutionTemplate,
+    template: &PreparedTemplate,
+    template_context: &Option<HashMap<String, String>>,
+) -> Result<PreparedCommand> {
+    if let Some(container) = &execution_context.container {
+        container.check()?;
+    }
+
+    if let Some(kubectl) = &execution_context.kubectl {
+        kubectl.check()?;
+        if execution_context.container.is_some() {
+            return Err(Error::InvalidKubectl(
+                "it can't be used with `container`".to_string(),
+            ));
+        }
+    }
+
+    Ok(PreparedCommand {
+        command: template.render_command(template_context)?,
+        working_directory: template.render_working_directory(template_context)?,
+        environment: execution_context.environment.clone(),
+        container: execution_context.container.clone(),
+        kubectl: execution_context
+            .kubectl
+            .as_ref()
+            .map(|kubectl| kubectl.render(template_context))
+            .transpose()?,
+        secret_parameters: SecretParameters::new(execution_context, template_context),
+    })
+}
+
+impl PreparedCommand {
+    pub fn expanded_working_directory(&self) -> Option<String> {
+        self.working_directory
+            .as_deref()
+            .map(|working_directory| shellexpand::tilde(working_directory).to_string())
+    }
+
+    /// Where the command runs, which is the current directory if it has no working directory.
+    pub fn resolved_working_directory(&self) -> String {
+        self.expanded_working_directory().unwrap_or_else(|| {
+            env::current_dir()
+                .map(|current_directory| current_directory.display().to_string())
+                .unwrap_or_else(|_| ".".to_string())
+        })
+    }
+
+    /// The command as the shell runs it, which is in its container or pod if it has one.
+    pub fn runnable_command(&self) -> String {
+        self.wrap_command(&self.command)
+    }
+
+    fn wrap_command(&self, command: &str) -> String {
+        match (&self.container, &self.kubectl) {
+            (Some(container), _) => container.wrap_command(command, self.environment.as_ref()),
+            (None, Some(kubectl)) => kubectl.wrap_command(command),
+            (None, None) => command.to_string(),
+        }
+    }
+
+    /// Whether any of the command's parameters or environment variables are secrets.
+    pub fn has_secrets(&self) -> bool {
+        self.secret_parameters.is_some()
+            || self
+                .environment
+                .iter()
+                .flatten()
+                .any(|(_, value)| value.is_secret())
+    }
+
+    /// Check the command can be edited before it runs. One with secret parameters can't, as
+    /// the secrets are filled in to the command from the config when it runs.
+    pub fn check_editable(&self) -> Result<()> {
+        match &self.secret_parameters {
+            Some(secret_parameters) => Err(Error::Secret(format!(
+                "{} for an edited command, as secret parameters can't be edited",
+                secret_parameters
+                    .names
+                    .values()
+                    .map(|name| format!("`{name}`"))
+                    .join(", ")
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Build the process that runs the command with the shell, in its working directory and
+    /// environment. An `interactive` shell reads the user's shell config (e.g. `~/.bashrc`), so
+    /// their aliases and functions can be used.
+    ///
+    /// Secrets are only read here, so they're never shown, logged or saved with the command.
+    pub fn shell_command(&self, shell: &str, interactive: bool) -> Result<Command> {
+        let mut command = Command::new(shell);
+
+        if let Some(working_directory) = self.expanded_working_directory() {
+            command.current_dir(working_directory);
+        }
+
+        if interactive {
+            command.arg("-i");
+        }
+        match &self.secret_parameters {
+            Some(secret_parameters) => command.args([
+                "-c",
+                &self.wrap_command(&secret_parameters.render_command()?),
+            ]),
+            None => command.args(["-c", &self.runnable_command()]),
+        };
+
+        if let Some(environment) = &self.environment {
+            info!("Executing with environment variables: {:?}", environment);
+            for (key, value) in environment {
+                command.env(key, value.reveal()?);
+            }
+        }
+
+        Ok(command)
+    }
+}
+
+/// Execute the command, passing its stdout and stderr through. Its stdin is usually
+/// `Stdio::inherit()`, so it can be read interactively or piped to `rc`.
+pub fn execute_command(mut command: Command, stdin: Stdio) -> Result<ExitStatus> {
+    let command = command
+        .stdin(stdin)
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit());
 
-    if let Some(environment) = environment {
-        info!("Executing with environment variables: {:?}", environment);
-        command = command.envs(environment);
-    };
+    interrupt::set_process_group(command);
+    let mut child = command.spawn()?;
+    let _running_child = RunningChild::new(&child);
+
+    Ok(child.wait()?)
+}
+
+/// Execute the command, capturing its stdout and stderr instead of passing them through.
+pub fn execute_command_captured(mut command: Command, stdin: Stdio) -> Result<Output> {
+    let command = command
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    interrupt::set_process_group(command);
+    let child = command.spawn()?;
+    let _running_child = RunningChild::new(&child);
+
+    Ok(child.wait_with_output()?)
+}
+
+/// Copy everything read from `reader` to `output` and the log file, as it is read.
+fn tee(mut reader: impl Read, mut output: impl Write, log_file: &Mutex<File>) {
+    let mut buffer = [0; 8192];
+
+    loop {
+        let length = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(length) => length,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+
+        let _ = output.write_all(&buffer[..length]);
+        let _ = output.flush();
+
+        if let Ok(mut log_file) = log_file.lock() {
+            let _ = log_file.write_all(&buffer[..length]);
+        }
+    }
+}
+
+/// Execute the command, passing its stdout and stderr through while also writing them to the
+/// log file.
+pub fn execute_command_logged(
+    mut command: Command,
+    stdin: Stdio,
+    log_file: File,
+) -> Result<ExitStatus> {
+    let command = command
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    interrupt::set_process_group(command);
+    let mut child = command.spawn()?;
+    let _running_child = RunningChild::new(&child);
+    let log_file = Arc::new(Mutex::new(log_file));
+
+    let stdout_tee = child.stdout.take().map(|stdout| {
+        let log_file = Arc::clone(&log_file);
+        thread::spawn(move || tee(stdout, std::io::stdout(), &log_file))
+    });
+    let stderr_tee = child.stderr.take().map(|stderr| {
+        let log_file = Arc::clone(&log_file);
+        thread::spawn(move || tee(stderr, std::io::stderr(), &log_file))
+    });
+
+    let exit_status = child.wait()?;
+
+    for tee in stdout_tee.into_iter().chain(stderr_tee) {
+        let _ = tee.join();
+    }
+
+    Ok(exit_status)
+}
+
+/// Machine-readable result of executing a command, for `--output json`.
+#[derive(Serialize, Debug)]
+pub struct ExecutionResult {
+    pub command: String,
+    pub working_directory: Option<String>,
+    pub environment: Option<BTreeMap<String, String>>,
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl ExecutionResult {
+    pub fn new(prepared: &PreparedCommand, output: &Output, duration: Duration) -> Self {
+        Self {
+            command: prepared.command.clone(),
+            working_directory: prepared.working_directory.clone(),
+            // Sorted so output is stable
+            environment: prepared.environment.as_ref().map(|environment| {
+                environment
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.to_string()))
+                    .collect()
+            }),
+            exit_code: get_exit_code(&output.status),
+            duration_ms: duration::as_millis(duration),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        }
+    }
+}
+
+/// Machine-readable description of what a dry run would have run, for `--output json`.
+#[derive(Serialize, Debug)]
+pub struct DryRunResult {
+    pub command: String,
+    pub working_directory: String,
+    pub shell: String,
+    pub environment: Option<BTreeMap<String, String>>,
+    pub parameters: Option<BTreeMap<String, String>>,
+}
+
+impl DryRunResult {
+    pub fn new(
+        prepared: &PreparedCommand,
+        template_context: &Option<HashMap<String, String>>,
+        shell: &str,
+    ) -> Self {
+        // Sorted so output is stable, e.g. to compare in CI
+        let sorted = |values: &HashMap<String, String>| values.clone().into_iter().collect();
+
+        Self {
+            command: prepared.command.clone(),
+            working_directory: prepared.resolved_working_directory(),
+            shell: shell.to_string(),
+            environment: prepared.environment.as_ref().map(|environment| {
+                environment
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.to_string()))
+                    .collect()
+            }),
+            parameters: template_context.as_ref().map(sorted),
+        }
+    }
+}
+
+/// Get the raw exit code of the sub process, using the shell convention of 128 + signal number
+/// if it was terminated by a signal.
+pub fn get_exit_code(status: &ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return SIGNAL_EXIT_CODE_OFFSET + signal;
+        }
+    }
+
+    UNKNOWN_EXIT_CODE
+}
+
+/// Map the exit code of the sub process to the exit code `rc` should return.
+///
+/// If `success_codes` is defined, any code in that list is treated as success (and mapped to `0`),
+/// otherwise the sub process's exit code is passed through as-is.
+pub fn map_exit_code(exit_code: i32, success_codes: Option<&Vec<i32>>) -> u8 {
+    if let Some(success_codes) = success_codes {
+        if success_codes.contains(&exit_code) {
+            return 0;
+        }
+    }
+
+    // Exit codes outside the range of a u8 can't be returned from the process
+    u8::try_from(exit_code).unwrap_or(UNKNOWN_EXIT_CODE as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_definitions::{CommandDefinition, ParameterDefinition};
+
+    fn execution_context() -> CommandExecutionTemplate {
+        CommandExecutionTemplate::from_command_definition(&CommandDefinition {
+            command: vec![
+                "psql".to_string(),
+                "{user}".to_string(),
+                "{password}".to_string(),
+            ],
+            parameters: Some(vec![
+                ParameterDefinition::new("user".to_string(), Some(ConfigValue::from("app"))),
+                ParameterDefinition::new(
+                    "password".to_string(),
+                    Some(ConfigValue::Secret("db_password".to_string())),
+                ),
+            ]),
+            ..CommandDefinition::default()
+        })
+    }
+
+    fn template_context(password: &str) -> Option<HashMap<String, String>> {
+        Some(HashMap::from([
+            ("user".to_string(), "app".to_string()),
+            ("password".to_string(), password.to_string()),
+        ]))
+    }
+
+    #[test]
+    fn secret_parameters_come_from_the_definition() {
+        assert_eq!(
+            execution_context().secret_parameters,
+            Some(BTreeMap::from([(
+                "password".to_string(),
+                "db_password".to_string()
+            )]))
+        );
+    }
+
+    #[test]
+    fn parameter_left_as_its_secret_default_is_a_secret() {
+        let secret_parameters = SecretParameters::new(
+            &execution_context(),
+            &template_context("!secret db_password"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            secret_parameters.names,
+            BTreeMap::from([("password".to_string(), "db_password".to_string())])
+        );
+    }
+
+    #[test]
+    fn parameter_given_another_value_is_not_a_secret() {
+        assert!(
+            SecretParameters::new(&execution_context(), &template_context("hunter2")).is_none()
+        );
+        assert!(
+            SecretParameters::new(&execution_context(), &template_context("!secret other"))
+                .is_none()
+        );
+    }
 
-    let subprocess_exit_success = command.spawn()?.wait()?.success();
+    #[test]
+    fn command_without_secrets_has_none() {
+        let execution_context =
+            CommandExecutionTemplate::from_command_definition(&CommandDefinition {
+                command: vec!["echo".to_string(), "!secret db_password".to_string()],
+                ..CommandDefinition::default()
+            });
 
-    if subprocess_exit_success {
-        Ok(())
-    } else {
-        Err(Error::SubProcessExit)
+        assert_eq!(execution_context.secret_parameters, None);
+        assert!(SecretParameters::new(&execution_context, &template_context("x")).is_none());
     }
 }
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use itertools::Itertools;
+
+use crate::command_definitions::{CommandDefinition, CommandExecutionTemplate, ConfirmPolicy};
+use crate::error::Result;
+use crate::interpolation::{
+    build_default_lookup, render_computed_default, resolve_computed_defaults, ArgumentTemplate,
+    PreparedTemplate, FORWARDED_ARGUMENTS_KEY,
+};
+use crate::settings::ProfileDefinition;
+
+/// Add where each parameter is used in the template to `usages`, by parameter name.
+fn add_usages(
+    template: &ArgumentTemplate,
+    place: &str,
+    usages: &mut BTreeMap<String, Vec<String>>,
+) {
+    let condition_keys = template.condition_keys();
+
+    for key in template.keys().into_iter().unique() {
+        let usage = if condition_keys.contains(&key) {
+            format!("{place} (as a condition)")
+        } else {
+            place.to_string()
+        };
+        usages.entry(key.to_string()).or_default().push(usage);
+    }
+}
+
+/// The working directory and command with the parameters' defaults filled in, including those
+/// computed from other parameters. Parameters without a default are left as their template token.
+pub fn render_with_defaults(
+    template: &PreparedTemplate,
+    defaults: HashMap<String, String>,
+) -> Result<(Option<String>, String)> {
+    let mut context: HashMap<String, String> = template
+        .tokens()
+        .iter()
+        .map(|name| {
+            let value = defaults
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| format!("{{{name}}}"));
+            (name.clone(), value)
+        })
+        .collect();
+    context.insert(FORWARDED_ARGUMENTS_KEY.to_string(), String::new());
+
+    let mut context = Some(context);
+    resolve_computed_defaults(&mut context, &Some(defaults));
+
+    Ok((
+        template.render_working_directory(&context)?,
+        template.render_command(&context)?,
+    ))
+}
+
+/// Print a breakdown of the command: each parameter with where it's used and its default, where
+/// and how it runs, what runs after it, and what it would run with the defaults.
+pub fn print_explanation(
+    command_definition: &CommandDefinition,
+    profile: Option<(&str, &ProfileDefinition)>,
+) -> Result<()> {
+    let mut execution_context =
+        CommandExecutionTemplate::from_command_definition(command_definition);
+    if let Some((_, profile)) = profile {
+        profile.apply_environment(&mut execution_context.environment);
+    }
+
+    match (&command_definition.id, &command_definition.name) {
+        (Some(id), Some(name)) => println!("{id}: {name}"),
+        (Some(title), None) | (None, Some(title)) => println!("{title}"),
+        (None, None) => println!("{}", command_definition.command.join(" ")),
+    }
+    println!();
+
+    let template = PreparedTemplate::parse(
+        &execution_context.command,
+        execution_context.working_directory.as_ref(),
+    )?;
+
+    println!("Runs: {}", execution_context.command.join(" "));
+    if let Some(working_directory) = &execution_context.working_directory {
+        println!("In directory: {working_directory}");
+    }
+
+    let mut usages: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (i, argument) in template.command.iter().enumerate() {
+        add_usages(argument, &format!("argument {}", i + 1), &mut usages);
+    }
+    if let Some(working_directory) = &template.working_directory {
+        add_usages(working_directory, "working directory", &mut usages);
+    }
+    if let Some(kubectl) = &execution_context.kubectl {
+        for token in kubectl.tokens()? {
+            usages
+                .entry(token)
+                .or_default()
+                .push("kubectl pod".to_string());
+        }
+    }
+    let forwards_arguments = usages.remove(FORWARDED_ARGUMENTS_KEY).is_some();
+
+    let defaults = build_default_lookup(&command_definition.parameters).unwrap_or_default();
+    // Only the parameters' names matter to tell if a default is computed from them
+    let placeholders: HashMap<String, String> = usages
+        .keys()
+        .map(|name| (name.clone(), String::new()))
+        .collect();
+    let names: HashSet<String> = usages.keys().cloned().collect();
+
+    if !usages.is_empty() {
+        println!();
+        println!("Parameters:");
+    }
+
+    for name in template.order_tokens(&names) {
+        println!("  {name}");
+        println!("    Used in: {}", usages[&name].join(", "));
+
+        match defaults.get(&name) {
+            Some(default) if render_computed_default(default, &placeholders).is_some() => {
+                println!("    Default: {default} (computed from the other parameters)");
+            }
+            Some(default) => println!("    Default: {default}"),
+            None => println!("    No default, so it must be given"),
+        }
+    }
+
+    if forwards_arguments {
+        println!();
+        println!(
+            "Arguments given after `--` are passed on in place of `{{{FORWARDED_ARGUMENTS_KEY}}}`."
+        );
+    }
+
+    if let Some(environment) = &execution_context.environment {
+        println!();
+        println!("Environment:");
+        for (key, value) in environment.iter().sorted() {
+            println!("  {key}={value}");
+        }
+    }
+
+    println!();
+    if let Some(container) = &execution_context.container {
+        println!("Runs in {container}.");
+    } else if let Some(kubectl) = &execution_context.kubectl {
+        println!("Runs in {kubectl}.");
+    }
+
+    if execution_context.dangerous.unwrap_or(false) {
+        println!("Dangerous, so it's confirmed by typing its ID, and never run non-interactively.");
+    } else {
+        match execution_context.confirm.unwrap_or_default() {
+            ConfirmPolicy::Always => println!("Always confirmed before it runs."),
+            ConfirmPolicy::Never => println!("Runs without confirmation."),
+            ConfirmPolicy::Default => {
+                println!("Confirmed before it runs, unless `--force` is given.")
+            }
+        }
+    }
+
+    if let Some(requires) = &execution_context.requires {
+        println!("Requires: {}", requires.join(", "));
+    }
+    if let Some(success_codes) = &execution_context.success_codes {
+        println!(
+            "Succeeds with exit codes: {}",
+            success_codes.iter().join(", ")
+        );
+    }
+    if let Some(and_then) = &execution_context.and_then {
+        println!("If it succeeds, then runs `{and_then}`.");
+    }
+    if let Some(or_else) = &execution_context.or_else {
+        println!("If it fails, then runs `{or_else}`.");
+    }
+
+    let (working_directory, command) = render_with_defaults(&template, defaults)?;
+    println!();
+    println!("With the defaults:");
+    if let Some(working_directory) = working_directory {
+        println!("  cd {working_directory}");
+    }
+    println!("  {command}");
+
+    Ok(())
+}
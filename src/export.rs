@@ -0,0 +1,417 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use itertools::Itertools;
+
+use crate::command_definitions::CommandDefinition;
+use crate::error::{Error, Result};
+use crate::interpolation::{shell_quote, ArgumentTemplate, PreparedTemplate};
+
+/// Marks where a parameter is used in a rendered command, before being replaced with the
+/// format's own syntax for it.
+const PARAMETER_MARKER: char = '\u{1}';
+
+/// Formats that commands can be exported to.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExportFormat {
+    /// Shell aliases, with shell functions for commands that have parameters.
+    Aliases,
+    /// A justfile, with a recipe for each command.
+    Just,
+    /// A Makefile, with a target for each command.
+    Makefile,
+}
+
+/// A parameter of an exported command, with its default if it has one.
+struct ExportParameter {
+    name: String,
+    default: Option<String>,
+}
+
+/// A command ready to be written in an export format.
+struct ExportCommand {
+    export_name: String,
+    description: Option<String>,
+    parameters: Vec<ExportParameter>,
+    /// The command, with `PARAMETER_MARKER`s around the index of each parameter used.
+    command: String,
+    /// `cd` and `export` commands to run first, to set up the working directory and environment.
+    /// The working directory can also contain parameter markers.
+    setup: Vec<String>,
+}
+
+impl ExportCommand {
+    /// Get the setup commands and the command, escaped for the format and with the parameter
+    /// markers replaced using `reference`.
+    fn resolve(
+        &self,
+        escape: impl Fn(&str) -> String,
+        reference: impl Fn(usize) -> String,
+    ) -> (Vec<String>, String) {
+        let setup = self
+            .setup
+            .iter()
+            .map(|setup| replace_parameter_markers(&escape(setup), &reference))
+            .collect();
+
+        (
+            setup,
+            replace_parameter_markers(&escape(&self.command), &reference),
+        )
+    }
+}
+
+/// Quote a working directory for the shell, keeping a leading `~/` unquoted so it is expanded.
+fn quote_directory(directory: &str) -> String {
+    match directory.strip_prefix("~/") {
+        Some(rest) if !rest.is_empty() => format!("~/{}", shell_quote(rest)),
+        Some(_) => "~".to_string(),
+        None => shell_quote(directory),
+    }
+}
+
+/// Convert the name to only contain lowercase letters, numbers and `separator`.
+fn slugify(name: &str, separator: char) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .join(&separator.to_string())
+}
+
+fn get_export_command(
+    command_definition: &CommandDefinition,
+    index: usize,
+    separator: char,
+    used_names: &mut HashSet<String>,
+) -> Result<ExportCommand> {
+    let name = command_definition
+        .id
+        .as_ref()
+        .or(command_definition.name.as_ref())
+        .map(|name| slugify(name, separator))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("command{separator}{index}"));
+
+    let has_secrets = command_definition
+        .parameters
+        .iter()
+        .flatten()
+        .any(|parameter| parameter.secret.is_some())
+        || command_definition
+            .environment
+            .iter()
+            .flatten()
+            .any(|(_, value)| value.is_secret());
+    if has_secrets {
+        return Err(Error::Secret(format!(
+            "secrets for the exported `{name}`, as they could be seen or saved in the export"
+        )));
+    }
+
+    // Names must be unique, as well as not starting with a number
+    let mut export_name = if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("command{separator}{name}")
+    } else {
+        name
+    };
+    if !used_names.insert(export_name.clone()) {
+        export_name = format!("{export_name}{separator}{index}");
+        used_names.insert(export_name.clone());
+    }
+
+    let template = PreparedTemplate::parse(
+        &command_definition.command,
+        command_definition.working_directory.as_ref(),
+    )?;
+    let mut tokens = template.tokens().clone();
+
+    // Conditional sections can't be exported, so they are fixed using their parameter's default
+    let condition_keys: HashSet<&str> = template
+        .command
+        .iter()
+        .chain(&template.working_directory)
+        .flat_map(ArgumentTemplate::condition_keys)
+        .collect();
+    tokens.retain(|token| !condition_keys.contains(token.as_str()));
+
+    let defaults: HashMap<&str, &str> = command_definition
+        .parameters
+        .iter()
+        .flatten()
+        .filter_map(|parameter| Some((parameter.name.as_str(), parameter.default.as_deref()?)))
+        .collect();
+
+    // Parameters in the order they are defined, then any without a definition. Those with
+    // defaults go last, as they are optional.
+    let parameter_names: Vec<&String> = command_definition
+        .parameters
+        .iter()
+        .flatten()
+        .map(|parameter| &parameter.name)
+        .filter(|name| tokens.contains(*name))
+        .unique()
+        .chain(tokens.iter().sorted().filter(|token| {
+            !command_definition
+                .parameters
+                .iter()
+                .flatten()
+                .any(|parameter| &parameter.name == *token)
+        }))
+        .sorted_by_key(|name| defaults.contains_key(name.as_str()))
+        .collect();
+
+    let mut marked_values: HashMap<String, String> = parameter_names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            (
+                (*name).clone(),
+                format!("{PARAMETER_MARKER}{i}{PARAMETER_MARKER}"),
+            )
+        })
+        .collect();
+    for condition_key in condition_keys {
+        marked_values.insert(
+            condition_key.to_string(),
+            defaults.get(condition_key).unwrap_or(&"false").to_string(),
+        );
+    }
+
+    // Filters can't be exported, so parameters are passed to the command as given
+    let render = |templates: &[ArgumentTemplate]| -> Result<Vec<String>> {
+        templates
+            .iter()
+            .map(|template| template.render_unfiltered(&marked_values))
+            .collect()
+    };
+    let command = render(&template.command)?.join(" ");
+
+    let mut setup = Vec::new();
+    if let Some(working_directory) = render(template.working_directory.as_slice())?.pop() {
+        if working_directory.contains(PARAMETER_MARKER) {
+            // Double quoted, so the parameters are still expanded
+            setup.push(format!("cd \"{}\"", working_directory.replace('"', "\\\"")));
+        } else {
+            setup.push(format!("cd {}", quote_directory(&working_directory)));
+        }
+    }
+    for (key, value) in command_definition.environment.iter().flatten().sorted() {
+        setup.push(format!("export {key}={}", shell_quote(&value.to_string())));
+    }
+
+    Ok(ExportCommand {
+        export_name,
+        description: command_definition.name.clone(),
+        parameters: parameter_names
+            .into_iter()
+            .map(|name| ExportParameter {
+                name: name.clone(),
+                default: defaults
+                    .get(name.as_str())
+                    .map(|default| default.to_string()),
+            })
+            .collect(),
+        command,
+        setup,
+    })
+}
+
+/// Replace the parameter markers in the command using `reference`, which is given the index of
+/// the parameter.
+fn replace_parameter_markers(command: &str, reference: impl Fn(usize) -> String) -> String {
+    command
+        .split(PARAMETER_MARKER)
+        .enumerate()
+        .map(|(i, part)| {
+            // Every other part is between markers, so is the index of a parameter
+            if i % 2 == 1 {
+                part.parse().map_or_else(|_| part.to_string(), &reference)
+            } else {
+                part.to_string()
+            }
+        })
+        .collect()
+}
+
+fn with_setup(setup: &[String], command: String) -> String {
+    setup.iter().cloned().chain([command]).join(" && ")
+}
+
+fn write_aliases(export_commands: &[ExportCommand], output: &mut String) -> std::fmt::Result {
+    for export_command in export_commands {
+        if let Some(description) = &export_command.description {
+            writeln!(output, "# {description}")?;
+        }
+
+        let (setup, command) = export_command.resolve(str::to_string, |i| {
+            let parameter = &export_command.parameters[i];
+            match &parameter.default {
+                Some(default) => format!("${{{}:-{}}}", i + 1, shell_quote(default)),
+                None => format!("${{{}}}", i + 1),
+            }
+        });
+
+        // A sub shell is used so the working directory and environment aren't changed
+        let command = if setup.is_empty() {
+            command
+        } else {
+            format!("({})", with_setup(&setup, command))
+        };
+
+        if export_command.parameters.is_empty() {
+            writeln!(
+                output,
+                "alias {}={}",
+                export_command.export_name,
+                shell_quote(&command)
+            )?;
+        } else {
+            let usage = export_command
+                .parameters
+                .iter()
+                .map(|parameter| match parameter.default {
+                    Some(_) => format!("[{}]", parameter.name),
+                    None => format!("<{}>", parameter.name),
+                })
+                .join(" ");
+
+            writeln!(output, "# Usage: {} {usage}", export_command.export_name)?;
+            writeln!(output, "{}() {{", export_command.export_name)?;
+            for line in command.lines() {
+                writeln!(output, "    {line}")?;
+            }
+            writeln!(output, "}}")?;
+        }
+
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+/// Quote a string for a justfile, using a raw string if possible.
+fn just_string(value: &str) -> String {
+    if value.contains('\'') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        format!("'{value}'")
+    }
+}
+
+fn write_justfile(export_commands: &[ExportCommand], output: &mut String) -> std::fmt::Result {
+    for export_command in export_commands {
+        if let Some(description) = &export_command.description {
+            writeln!(output, "# {description}")?;
+        }
+
+        let parameters = export_command
+            .parameters
+            .iter()
+            .map(|parameter| match &parameter.default {
+                Some(default) => format!(" {}={}", parameter.name, just_string(default)),
+                None => format!(" {}", parameter.name),
+            })
+            .join("");
+
+        let (setup, command) = export_command.resolve(
+            |text| text.replace("{{", "{{{{"),
+            |i| format!("{{{{{}}}}}", export_command.parameters[i].name),
+        );
+
+        writeln!(output, "{}{parameters}:", export_command.export_name)?;
+        if command.contains('\n') {
+            // Each line of a recipe runs in its own shell, so multi line commands use a script
+            writeln!(output, "    #!/usr/bin/env bash\n    set -e")?;
+            for line in setup.iter().map(String::as_str).chain(command.lines()) {
+                writeln!(output, "    {line}")?;
+            }
+        } else {
+            writeln!(output, "    {}", with_setup(&setup, command))?;
+        }
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+fn write_makefile(export_commands: &[ExportCommand], output: &mut String) -> std::fmt::Result {
+    // Run each target's lines in the same shell, so multi line commands work
+    writeln!(output, ".ONESHELL:")?;
+
+    if !export_commands.is_empty() {
+        writeln!(
+            output,
+            ".PHONY: {}\n",
+            export_commands
+                .iter()
+                .map(|export_command| &export_command.export_name)
+                .join(" ")
+        )?;
+    }
+
+    for export_command in export_commands {
+        if let Some(description) = &export_command.description {
+            writeln!(output, "# {description}")?;
+        }
+
+        for parameter in &export_command.parameters {
+            if let Some(default) = &parameter.default {
+                writeln!(
+                    output,
+                    "{}: {} ?= {}",
+                    export_command.export_name,
+                    parameter.name,
+                    default.replace('$', "$$")
+                )?;
+            }
+        }
+
+        // `$` must be escaped for make, apart from where parameters are used
+        let (setup, command) = export_command.resolve(
+            |text| text.replace('$', "$$"),
+            |i| format!("$({})", export_command.parameters[i].name),
+        );
+
+        writeln!(output, "{}:", export_command.export_name)?;
+        for line in with_setup(&setup, command).lines() {
+            writeln!(output, "\t{line}")?;
+        }
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+/// Render the command definitions in the export format.
+pub fn export_commands(
+    command_definitions: &[CommandDefinition],
+    format: ExportFormat,
+    config_path: &str,
+) -> Result<String> {
+    // Shell functions can't contain `-`, whereas recipes and targets usually do
+    let separator = match format {
+        ExportFormat::Aliases => '_',
+        ExportFormat::Just | ExportFormat::Makefile => '-',
+    };
+
+    let mut used_names = HashSet::new();
+    let export_commands = command_definitions
+        .iter()
+        .enumerate()
+        .map(|(i, command_definition)| {
+            get_export_command(command_definition, i, separator, &mut used_names)
+        })
+        .collect::<Result<Vec<ExportCommand>>>()?;
+
+    let mut output = format!("# Exported by rust-cuts from {config_path}\n\n");
+
+    match format {
+        ExportFormat::Aliases => write_aliases(&export_commands, &mut output),
+        ExportFormat::Just => write_justfile(&export_commands, &mut output),
+        ExportFormat::Makefile => write_makefile(&export_commands, &mut output),
+    }
+    .map_err(|e| Error::Misc(format!("Could not write export: {e}")))?;
+
+    Ok(output)
+}
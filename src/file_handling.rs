@@ -1,8 +1,354 @@
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::path::Path;
+use std::process;
 
-use crate::command_definitions::{CommandDefinition, CommandExecutionTemplate};
+use itertools::Itertools;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde_yaml::Value;
+
+use crate::command_definitions::{
+    self, validate_command_ids, CommandDefinition, CommandExecutionTemplate, ParameterDefinition,
+};
 use crate::error::{Error, Result};
+use crate::migration::{self, ParameterSets};
+use crate::settings::Settings;
+use crate::validation;
+use crate::version;
+
+/// Lines shown before the line with a YAML error, so it can be found in the file.
+const SNIPPET_CONTEXT_LINES: usize = 2;
+
+/// A key that isn't a field of the command (or parameter) it is in, so is ignored.
+pub struct UnknownField {
+    pub command_index: usize,
+    /// The parameter the key is in, if it isn't in the command itself.
+    pub parameter: Option<String>,
+    pub key: String,
+    /// The field that was likely meant, if the key looks like a typo of one.
+    pub suggestion: Option<&'static str>,
+}
+
+impl UnknownField {
+    /// Describe the key and what it may have been meant to be, e.g. for `rc validate` warnings.
+    pub fn describe(&self) -> String {
+        let location = match &self.parameter {
+            Some(parameter) => format!("parameter `{parameter}`"),
+            None => "the command".to_string(),
+        };
+
+        match self.suggestion {
+            Some(suggestion) => format!(
+                "`{}` isn't a field of {location}, did you mean `{suggestion}`?",
+                self.key
+            ),
+            None => format!("`{}` isn't a field of {location}, so is ignored.", self.key),
+        }
+    }
+}
+
+/// Records the field names `serde` expects when deserializing a struct, then stops.
+struct FieldNamesDeserializer<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNamesDeserializer<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs have field names"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("the field names have been found"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// The fields of a struct, as they are named in YAML. These come from its `Deserialize`
+/// implementation, so are always up to date.
+fn get_field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut field_names: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNamesDeserializer(&mut field_names));
+    field_names
+}
+
+/// The field closest to the key, if it is close enough to likely be a typo of it, e.g.
+/// `parameter` for `parameters`.
+fn suggest_field(key: &str, field_names: &'static [&'static str]) -> Option<&'static str> {
+    let max_distance = (key.chars().count() / 3).max(1);
+
+    field_names
+        .iter()
+        .map(|field_name| (strsim::damerau_levenshtein(key, field_name), *field_name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field_name)| field_name)
+}
+
+fn get_unknown_keys(
+    value: &Value,
+    field_names: &'static [&'static str],
+) -> Vec<(String, Option<&'static str>)> {
+    value
+        .as_mapping()
+        .into_iter()
+        .flat_map(|mapping| mapping.keys())
+        .filter_map(Value::as_str)
+        .filter(|key| !field_names.contains(key))
+        .map(|key| (key.to_string(), suggest_field(key, field_names)))
+        .collect()
+}
+
+/// The commands in the config, in any layout, or the config itself if it's a single command.
+fn get_command_values(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Sequence(commands) => commands.clone(),
+        Value::Mapping(mapping) => match mapping.get("commands") {
+            Some(commands) => commands.as_sequence().cloned().unwrap_or_default(),
+            None => vec![value.clone()],
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Describe a command in the YAML by its name, falling back to its ID then its index.
+fn describe_command_value(command: &Value, index: usize) -> String {
+    ["name", "id"]
+        .iter()
+        .find_map(|key| command.get(key).and_then(Value::as_str))
+        .map_or_else(|| format!("at index {index}"), str::to_string)
+}
+
+/// Warnings to show when the config is loaded: commands that need a newer version of `rc`, so
+/// are hidden, and keys that aren't fields, so are ignored.
+pub fn get_config_warnings(config_path: &str) -> Result<Vec<String>> {
+    let config = fs::read_to_string(config_path)
+        .map_err(|e| Error::io_error("config".to_string(), config_path.to_string(), e))?;
+    let Ok(value) = serde_yaml::from_str::<Value>(&config) else {
+        return Ok(Vec::new());
+    };
+    let commands = get_command_values(&value);
+
+    let mut warnings = Vec::new();
+    for (i, command) in commands.iter().enumerate() {
+        if let Some(requirement) = migration::get_unsupported_requirement(command)? {
+            warnings.push(format!(
+                "Command `{}` needs version {requirement} of rc, but this is version {}, so it's hidden. Upgrade rc to use it.",
+                describe_command_value(command, i),
+                version::RC_VERSION
+            ));
+        }
+    }
+
+    warnings.extend(find_unknown_fields(&config).iter().map(|unknown_field| {
+        format!(
+            "Command `{}`: {}",
+            describe_command_value(
+                &commands[unknown_field.command_index],
+                unknown_field.command_index
+            ),
+            unknown_field.describe()
+        )
+    }));
+
+    Ok(warnings)
+}
+
+/// Find keys in the commands (and their parameters) that aren't fields, so are ignored. These
+/// are usually typos, e.g. `enviroment`, which would otherwise go unnoticed.
+///
+/// The config can be in any layout, or be a single command (e.g. one being edited).
+///
+/// Commands that need a newer version of `rc` are skipped, as their keys are likely fields of
+/// that version.
+pub fn find_unknown_fields(config: &str) -> Vec<UnknownField> {
+    let Ok(value) = serde_yaml::from_str::<Value>(config) else {
+        return Vec::new();
+    };
+
+    let command_fields = get_field_names::<CommandDefinition>();
+    let parameter_fields = get_field_names::<ParameterDefinition>();
+
+    get_command_values(&value)
+        .iter()
+        .enumerate()
+        .filter(|(_, command)| {
+            !matches!(migration::get_unsupported_requirement(command), Ok(Some(_)))
+        })
+        .flat_map(|(command_index, command)| {
+            let command_keys = get_unknown_keys(command, command_fields).into_iter().map(
+                move |(key, suggestion)| UnknownField {
+                    command_index,
+                    parameter: None,
+                    key,
+                    suggestion,
+                },
+            );
+
+            let parameter_keys = command
+                .get("parameters")
+                .and_then(Value::as_sequence)
+                .into_iter()
+                .flatten()
+                .flat_map(move |parameter| {
+                    let name = parameter
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string();
+
+                    get_unknown_keys(parameter, parameter_fields)
+                        .into_iter()
+                        .map(move |(key, suggestion)| UnknownField {
+                            command_index,
+                            parameter: Some(name.clone()),
+                            key,
+                            suggestion,
+                        })
+                });
+
+            command_keys.chain(parameter_keys).collect_vec()
+        })
+        .collect()
+}
+
+/// The line (starting at 1) of the unknown key, looking from the line its command starts on.
+pub fn find_unknown_field_line(
+    config: &str,
+    command_lines: Option<&[usize]>,
+    unknown_field: &UnknownField,
+) -> Option<usize> {
+    let command_line = command_lines
+        .and_then(|command_lines| command_lines.get(unknown_field.command_index))
+        .copied()
+        .unwrap_or(1);
+    let command_source = config
+        .lines()
+        .skip(command_line - 1)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    find_key_line(&command_source, &unknown_field.key).map(|line| line + command_line - 1)
+}
+
+/// Error if the config has keys that aren't fields, for the `strict` setting, with the line of
+/// each.
+pub fn check_unknown_fields(config_path: &str) -> Result<()> {
+    let config = fs::read_to_string(config_path)
+        .map_err(|e| Error::io_error("config".to_string(), config_path.to_string(), e))?;
+    let Ok(value) = serde_yaml::from_str::<Value>(&config) else {
+        return Ok(());
+    };
+    let commands = get_command_values(&value);
+
+    let unknown_fields = find_unknown_fields(&config);
+    if unknown_fields.is_empty() {
+        return Ok(());
+    }
+
+    let command_lines = validation::get_command_lines(&config, commands.len());
+    let fields = unknown_fields
+        .iter()
+        .map(|unknown_field| {
+            let line = find_unknown_field_line(&config, command_lines.as_deref(), unknown_field)
+                .map(|line| format!("{config_path}:{line}: "))
+                .unwrap_or_default();
+            format!(
+                "{line}Command `{}`: {}",
+                describe_command_value(
+                    &commands[unknown_field.command_index],
+                    unknown_field.command_index
+                ),
+                unknown_field.describe()
+            )
+        })
+        .join("\n");
+
+    Err(Error::UnknownFields(fields))
+}
+
+/// The line (starting at 1) a key is first used on, to point to it in hints.
+fn find_key_line(source: &str, key: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| {
+            line.trim_start_matches([' ', '-'])
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+        })
+        .map(|i| i + 1)
+}
+
+/// The lines around a YAML error, with a caret under where it is, and suggestions for keys that
+/// look like typos as these are a common cause of errors (e.g. a required field that is
+/// misspelled).
+fn describe_yaml_error(source: &str, error: &serde_yaml::Error) -> String {
+    let mut details = String::new();
+
+    if let Some(location) = error.location() {
+        let lines: Vec<&str> = source.lines().collect();
+        let error_line = location.line();
+        let first_line = error_line.saturating_sub(SNIPPET_CONTEXT_LINES).max(1);
+        let number_width = error_line.to_string().len();
+
+        details.push_str(&format!("\n{:number_width$} |", ""));
+        for line_number in first_line..=error_line.min(lines.len()) {
+            details.push_str(&format!(
+                "\n{line_number:>number_width$} | {}",
+                lines[line_number - 1]
+            ));
+        }
+        details.push_str(&format!(
+            "\n{:number_width$} | {:column$}^",
+            "",
+            "",
+            column = location.column().saturating_sub(1)
+        ));
+    }
+
+    for unknown_field in find_unknown_fields(source) {
+        if let Some(suggestion) = unknown_field.suggestion {
+            let line = find_key_line(source, &unknown_field.key)
+                .map(|line| format!(" (line {line})"))
+                .unwrap_or_default();
+            details.push_str(&format!(
+                "\nDid you mean `{suggestion}` instead of `{}`{line}?",
+                unknown_field.key
+            ));
+        }
+    }
+
+    details
+}
+
+/// A YAML error, showing where it is in the source.
+pub fn yaml_error_in_source(
+    action: &str,
+    file_description: &str,
+    path: &str,
+    source: &str,
+    original: serde_yaml::Error,
+) -> Error {
+    Error::Yaml {
+        action: action.to_string(),
+        file_description: file_description.to_string(),
+        path: path.to_string(),
+        details: describe_yaml_error(source, &original),
+        original,
+    }
+}
 
 fn get_reader(file_description: &str, path: &str) -> Result<File> {
     match File::open(path) {
@@ -47,48 +393,141 @@ pub fn get_last_command(last_command_path: &String) -> Result<Option<CommandExec
     }
 }
 
-pub fn write_last_command(path: &str, last_command: &CommandExecutionTemplate) -> Result<()> {
-    let f = File::create(path);
+/// An exclusive lock on a file, released when this is dropped. It's held while the file is read,
+/// changed and written back, so another `rc` doing the same at once doesn't lose the change.
+pub struct FileLock {
+    _lock_file: File,
+}
 
-    let Ok(f) = f else {
-        return Err(Error::io_error(
-            "last command".to_string(),
-            path.to_string(),
-            f.unwrap_err(),
-        ));
-    };
+#[cfg(unix)]
+fn lock_exclusively(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
 
-    serde_yaml::to_writer(f, &last_command).map_err(|e| {
-        Error::yaml_error(
-            path.to_string(),
-            "writing".to_string(),
-            "last command".to_string(),
-            e,
-        )
+    loop {
+        // SAFETY: the file descriptor is open for as long as `file` is
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(());
+        }
+
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Files are only locked on Unix, elsewhere the last write wins.
+#[cfg(not(unix))]
+fn lock_exclusively(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Lock the file, waiting for any other `rc` holding the lock to release it. The lock is on a
+/// file beside it (`{path}.lock`), as the file itself is replaced when written atomically.
+pub fn lock_file(file_description: &str, path: &str) -> Result<FileLock> {
+    let lock_path = format!("{path}.lock");
+    let io_error = |e| Error::io_error(file_description.to_string(), lock_path.clone(), e);
+
+    if let Some(directory) = Path::new(path).parent() {
+        fs::create_dir_all(directory).map_err(io_error)?;
+    }
+
+    let lock_file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(io_error)?;
+    lock_exclusively(&lock_file).map_err(io_error)?;
+
+    Ok(FileLock {
+        _lock_file: lock_file,
     })
 }
 
-pub fn get_command_definitions(config_path: &String) -> Result<Vec<CommandDefinition>> {
-    let config_reader = &get_reader("config", config_path)?;
+/// Replace the file's contents by writing them to a temporary file beside it, then renaming that
+/// over it. Another `rc` reading the file at the same time sees either the old or new contents,
+/// never part of them, and if two write at once, one's contents are kept whole.
+pub fn write_atomically(file_description: &str, path: &str, contents: &str) -> Result<()> {
+    let io_error = |e| Error::io_error(file_description.to_string(), path.to_string(), e);
+    // Unique to this process, so writers don't share a temporary file
+    let temporary_path = format!("{path}.{}.tmp", process::id());
 
-    let parsing_result: serde_yaml::Result<Vec<CommandDefinition>>;
+    let result = File::create(&temporary_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temporary_path, path));
 
-    {
-        parsing_result = serde_yaml::from_reader(config_reader);
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary_path);
     }
 
-    let parsed_command_defs = parsing_result.map_err(|e| {
+    result.map_err(io_error)
+}
+
+pub fn write_last_command(path: &str, last_command: &CommandExecutionTemplate) -> Result<()> {
+    let last_command = serde_yaml::to_string(last_command).map_err(|e| {
         Error::yaml_error(
-            config_path.clone(),
-            "reading".to_string(),
-            "config".to_string(),
+            "writing".to_string(),
+            "last command".to_string(),
+            path.to_string(),
             e,
         )
     })?;
 
+    write_atomically("last command", path, &last_command)
+}
+
+/// Read the settings file, the defaults are used if it does not exist.
+pub fn get_settings(settings_path: &str) -> Result<Settings> {
+    if !Path::exists(Path::new(settings_path)) {
+        return Ok(Settings::default());
+    }
+
+    let settings = fs::read_to_string(settings_path)
+        .map_err(|e| Error::io_error("settings".to_string(), settings_path.to_string(), e))?;
+
+    serde_yaml::from_str(&settings)
+        .map_err(|e| yaml_error_in_source("reading", "settings", settings_path, &settings, e))
+}
+
+/// The commands as they are written in the config, before `extends`, `variants` and
+/// `parameter_sets` are resolved, and the parameter sets they use.
+pub fn get_config_command_definitions(
+    config_path: &str,
+) -> Result<(Vec<CommandDefinition>, ParameterSets)> {
+    let config = fs::read_to_string(config_path)
+        .map_err(|e| Error::io_error("config".to_string(), config_path.to_string(), e))?;
+
+    migration::parse_config(config_path, &config)
+}
+
+/// The commands to choose from, with the parameters of their sets added, `extends` resolved and
+/// each variant as a command of its own.
+pub fn resolve_command_definitions(
+    config_path: &str,
+    command_definitions: Vec<CommandDefinition>,
+    parameter_sets: &ParameterSets,
+) -> Result<Vec<CommandDefinition>> {
+    let parsed_command_defs = command_definitions::resolve_extends(migration::add_parameter_sets(
+        command_definitions,
+        parameter_sets,
+    )?)?;
+    let parsed_command_defs = command_definitions::expand_variants(parsed_command_defs);
+
     if parsed_command_defs.is_empty() {
-        Err(Error::empty_command_definition(config_path.to_string()))
-    } else {
-        Ok(parsed_command_defs)
+        return Err(Error::empty_command_definition(config_path.to_string()));
     }
+
+    validate_command_ids(&parsed_command_defs)?;
+
+    Ok(parsed_command_defs)
+}
+
+pub fn get_command_definitions(config_path: &str) -> Result<Vec<CommandDefinition>> {
+    let (command_definitions, parameter_sets) = get_config_command_definitions(config_path)?;
+
+    resolve_command_definitions(config_path, command_definitions, &parameter_sets)
 }
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::command_definitions::CommandExecutionTemplate;
+use crate::error::{Error, Result};
+use crate::file_handling;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How much a run counts towards a command's frecency score, depending on how many days ago it
+/// was. Runs older than the last bucket get `OLDEST_RUN_WEIGHT`.
+const RECENCY_WEIGHTS: [(u64, u64); 4] = [(4, 100), (14, 70), (31, 50), (90, 30)];
+const OLDEST_RUN_WEIGHT: u64 = 10;
+
+/// How many of the values used before for each parameter are offered when filling it in.
+pub const RECENT_VALUE_COUNT: usize = 5;
+
+/// A single execution of a command, stored as one JSON object per line in the history file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    /// Identifies the command across runs, see `CommandExecutionTemplate::history_key`.
+    pub command_key: String,
+    pub resolved_command: String,
+    pub template_context: Option<HashMap<String, String>>,
+    /// Seconds since the Unix epoch that the command was run.
+    pub timestamp: u64,
+    pub exit_code: Option<i32>,
+    /// Where the command's output was saved, if it has `log_output`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_path: Option<String>,
+    /// How long the command took to run, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        execution_context: &CommandExecutionTemplate,
+        resolved_command: &str,
+        exit_code: Option<i32>,
+    ) -> Self {
+        Self {
+            command_key: execution_context.history_key(),
+            resolved_command: resolved_command.to_string(),
+            template_context: execution_context.template_context.clone(),
+            timestamp: now(),
+            exit_code,
+            log_path: None,
+            duration_ms: None,
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn append_entry(history_path: &str, entry: &HistoryEntry) -> Result<()> {
+    // So it isn't lost if the history is being pruned
+    let _lock = file_handling::lock_file("history", history_path)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path)
+        .map_err(|e| Error::io_error("history".to_string(), history_path.to_string(), e))?;
+
+    // Written in one go, so lines from several `rc`s running at once aren't mixed together
+    let line = serde_json::to_string(entry)? + "\n";
+
+    file.write_all(line.as_bytes())
+        .map_err(|e| Error::io_error("history".to_string(), history_path.to_string(), e))
+}
+
+/// Read all the entries in the history file, oldest first. Lines that can't be parsed are skipped.
+pub fn read_entries(history_path: &str) -> Result<Vec<HistoryEntry>> {
+    if !Path::new(history_path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(history_path)
+        .map_err(|e| Error::io_error("history".to_string(), history_path.to_string(), e))?;
+
+    let mut entries = Vec::new();
+
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line =
+            line.map_err(|e| Error::io_error("history".to_string(), history_path.to_string(), e))?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!(
+                "Skipping invalid history entry on line {}: {e}",
+                line_number + 1
+            ),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Replace the history with the entries. They're written to a temporary file first, so the
+/// history isn't lost if writing fails part way through.
+fn write_entries(history_path: &str, entries: &[&HistoryEntry]) -> Result<()> {
+    let mut history = String::new();
+    for entry in entries {
+        history.push_str(&serde_json::to_string(entry)?);
+        history.push('\n');
+    }
+
+    file_handling::write_atomically("history", history_path, &history)
+}
+
+/// The entries that are newer than `max_age`, and only the most recent `max_entries` of them.
+fn get_kept_entries(
+    entries: &[HistoryEntry],
+    max_age: Option<Duration>,
+    max_entries: Option<usize>,
+) -> Vec<&HistoryEntry> {
+    let oldest_timestamp = max_age.map_or(0, |max_age| now().saturating_sub(max_age.as_secs()));
+    let mut kept_entries: Vec<&HistoryEntry> = entries
+        .iter()
+        .filter(|entry| entry.timestamp >= oldest_timestamp)
+        .collect();
+
+    // Entries are oldest first
+    if let Some(max_entries) = max_entries {
+        kept_entries.drain(..kept_entries.len().saturating_sub(max_entries));
+    }
+
+    kept_entries
+}
+
+/// Remove the entries older than `max_age`, and all but the most recent `max_entries`. Returns how
+/// many entries were removed, and how many are left.
+///
+/// The history is only rewritten if there are entries to remove, with it locked so entries another
+/// `rc` adds while it's being rewritten aren't lost.
+pub fn prune_entries(
+    history_path: &str,
+    max_age: Option<Duration>,
+    max_entries: Option<usize>,
+) -> Result<(usize, usize)> {
+    let entries = read_entries(history_path)?;
+    let kept_count = get_kept_entries(&entries, max_age, max_entries).len();
+    if kept_count == entries.len() {
+        return Ok((0, kept_count));
+    }
+
+    let _lock = file_handling::lock_file("history", history_path)?;
+    // Read again, as entries may have been added since
+    let entries = read_entries(history_path)?;
+    let kept_entries = get_kept_entries(&entries, max_age, max_entries);
+
+    let removed_count = entries.len() - kept_entries.len();
+    if removed_count > 0 {
+        write_entries(history_path, &kept_entries)?;
+    }
+
+    Ok((removed_count, kept_entries.len()))
+}
+
+/// Describe how long ago the timestamp was, e.g. `5 minutes ago`.
+pub fn format_age(timestamp: u64) -> String {
+    let age = now().saturating_sub(timestamp);
+
+    let (amount, unit) = match age {
+        0..=59 => (age, "second"),
+        60..=3599 => (age / 60, "minute"),
+        3600..=86399 => (age / 3600, "hour"),
+        _ => (age / SECONDS_PER_DAY, "day"),
+    };
+
+    let plural = if amount == 1 { "" } else { "s" };
+
+    format!("{amount} {unit}{plural} ago")
+}
+
+/// The most recent run of the command.
+pub fn get_last_entry<'a>(
+    entries: &'a [HistoryEntry],
+    command_key: &str,
+) -> Option<&'a HistoryEntry> {
+    entries
+        .iter()
+        .rev()
+        .find(|entry| entry.command_key == command_key)
+}
+
+/// Score each command by how often and how recently it was run.
+pub fn get_frecency_scores(entries: &[HistoryEntry]) -> HashMap<String, u64> {
+    let now = now();
+    let mut scores: HashMap<String, u64> = HashMap::new();
+
+    for entry in entries {
+        let age_in_days = now.saturating_sub(entry.timestamp) / SECONDS_PER_DAY;
+
+        let weight = RECENCY_WEIGHTS
+            .iter()
+            .find(|(max_age_in_days, _)| age_in_days <= *max_age_in_days)
+            .map_or(OLDEST_RUN_WEIGHT, |(_, weight)| *weight);
+
+        *scores.entry(entry.command_key.clone()).or_default() += weight;
+    }
+
+    scores
+}
+
+/// The values each of the command's parameters were given the last few times it was run, most
+/// recent first and without repeats.
+pub fn get_recent_values(
+    entries: &[HistoryEntry],
+    command_key: &str,
+) -> HashMap<String, Vec<String>> {
+    let mut recent_values: HashMap<String, Vec<String>> = HashMap::new();
+
+    for context in entries
+        .iter()
+        .rev()
+        .filter(|entry| entry.command_key == command_key)
+        .filter_map(|entry| entry.template_context.as_ref())
+    {
+        for (name, value) in context {
+            let values = recent_values.entry(name.clone()).or_default();
+            if values.len() < RECENT_VALUE_COUNT && !values.contains(value) {
+                values.push(value.clone());
+            }
+        }
+    }
+
+    recent_values
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    fn entry(command_key: &str, timestamp: u64) -> HistoryEntry {
+        HistoryEntry {
+            command_key: command_key.to_string(),
+            resolved_command: command_key.to_string(),
+            template_context: None,
+            timestamp,
+            exit_code: Some(0),
+            log_path: None,
+            duration_ms: None,
+        }
+    }
+
+    fn keys(entries: &[&HistoryEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| entry.command_key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn kept_entries_are_within_the_limits() {
+        let now = now();
+        let entries = vec![
+            entry("old", now - 10 * SECONDS_PER_DAY),
+            entry("a", now - 2),
+            entry("b", now - 1),
+            entry("c", now),
+        ];
+        let max_age = Some(Duration::from_secs(SECONDS_PER_DAY));
+
+        assert_eq!(
+            keys(&get_kept_entries(&entries, max_age, None)),
+            ["a", "b", "c"]
+        );
+        assert_eq!(keys(&get_kept_entries(&entries, None, Some(2))), ["b", "c"]);
+        assert_eq!(
+            keys(&get_kept_entries(&entries, max_age, Some(5))),
+            ["a", "b", "c"]
+        );
+        assert_eq!(get_kept_entries(&entries, None, None).len(), 4);
+    }
+
+    #[test]
+    fn prune_only_rewrites_when_over_the_limits() {
+        let path = env::temp_dir().join(format!("rc-test-history-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let now = now();
+        for (command_key, timestamp) in [("a", now - 2), ("b", now - 1), ("c", now)] {
+            append_entry(path, &entry(command_key, timestamp)).unwrap();
+        }
+
+        let modified = fs::metadata(path).unwrap().modified().unwrap();
+        assert_eq!(prune_entries(path, None, Some(3)).unwrap(), (0, 3));
+        assert_eq!(fs::metadata(path).unwrap().modified().unwrap(), modified);
+
+        assert_eq!(prune_entries(path, None, Some(2)).unwrap(), (1, 2));
+        let entries = read_entries(path).unwrap();
+        assert_eq!(keys(&entries.iter().collect::<Vec<_>>()), ["b", "c"]);
+
+        // Added after pruning, with the lock released
+        append_entry(path, &entry("d", now)).unwrap();
+        assert_eq!(read_entries(path).unwrap().len(), 3);
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(format!("{path}.lock")).unwrap();
+    }
+}
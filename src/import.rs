@@ -0,0 +1,564 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::{env, fs};
+
+use clap::ValueEnum;
+use crossterm::cursor::MoveTo;
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{enable_raw_mode, Clear, ClearType};
+use crossterm::{event, queue, terminal};
+use leon::Template;
+use log::{debug, warn};
+use unicode_width::UnicodeWidthStr;
+
+use crate::command_definitions::CommandDefinition;
+use crate::command_selection::{truncate_to_width, RawModeGuard};
+use crate::config_editing;
+use crate::error::{Error, Result};
+use crate::theme::{self, Theme};
+
+/// Files read when importing from the shell, if they exist.
+const SHELL_CONFIG_PATHS: [&str; 3] = ["~/.bashrc", "~/.bash_aliases", "~/.zshrc"];
+/// Files read when importing from the shell history, if they exist.
+const SHELL_HISTORY_PATHS: [&str; 2] = ["~/.bash_history", "~/.zsh_history"];
+/// How many of the most frequently run commands are offered when importing from history.
+const HISTORY_IMPORT_COUNT: usize = 30;
+/// History files for finding the last command run in a shell, by the shell's name. For bash and
+/// zsh, `$HISTFILE` is used instead if it's set.
+const LAST_COMMAND_HISTORY_PATHS: [(&str, &str); 3] = [
+    ("bash", "~/.bash_history"),
+    ("zsh", "~/.zsh_history"),
+    ("fish", "~/.local/share/fish/fish_history"),
+];
+
+/// Where commands are imported from.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ImportSource {
+    /// Aliases and functions in shell config files.
+    Shell,
+    /// The most frequently run commands in the shell history.
+    History,
+}
+
+/// A command found to import, with whether it is selected in the review list.
+struct ImportCandidate {
+    command_definition: CommandDefinition,
+    is_selected: bool,
+}
+
+/// Escape braces so the shell command isn't treated as a template.
+fn escape_braces(command: &str) -> String {
+    command.replace('{', "\\{").replace('}', "\\}")
+}
+
+/// Convert a function body to a template, with its positional arguments (`$1` or `${1}`) as
+/// parameters (`{arg1}`), and other braces escaped. Single quoted text (e.g. an `awk` program) is
+/// left as it is.
+fn function_body_to_template(body: &str) -> String {
+    let mut template = String::new();
+    let mut chars = body.chars().peekable();
+    let mut is_single_quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                is_single_quoted = !is_single_quoted;
+                template.push(c);
+            }
+            '$' if !is_single_quoted => match chars.peek() {
+                Some(digit @ '1'..='9') => {
+                    template.push_str(&format!("{{arg{digit}}}"));
+                    chars.next();
+                }
+                Some('{') => {
+                    chars.next();
+                    let mut variable = String::new();
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            break;
+                        }
+                        variable.push(c);
+                    }
+
+                    if variable.parse::<u8>().is_ok_and(|n| (1..=9).contains(&n)) {
+                        template.push_str(&format!("{{arg{variable}}}"));
+                    } else {
+                        template.push_str(&format!("$\\{{{variable}\\}}"));
+                    }
+                }
+                _ => template.push(c),
+            },
+            '{' => template.push_str("\\{"),
+            '}' => template.push_str("\\}"),
+            _ => template.push(c),
+        }
+    }
+
+    template
+}
+
+/// Remove matching quotes from around an alias value.
+fn unquote(value: &str) -> &str {
+    for quote in ['\'', '"'] {
+        if let Some(unquoted) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return unquoted;
+        }
+    }
+
+    value
+}
+
+/// Get the name of the function defined on the line, for `name() {` or `function name {`.
+fn get_function_name(line: &str) -> Option<&str> {
+    let name = if let Some(rest) = line.strip_prefix("function ") {
+        rest.split(|c: char| c == '(' || c == '{' || c.is_whitespace())
+            .next()?
+    } else {
+        line.split_once("()")?.0.trim()
+    };
+
+    let is_valid_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == ':');
+
+    is_valid_name.then_some(name)
+}
+
+/// Find the aliases and functions defined in a shell config file, as `(name, template)` pairs.
+///
+/// Functions are imported as their body, see `function_body_to_template`.
+fn parse_shell_config(content: &str) -> Vec<(String, String)> {
+    let mut definitions = Vec::new();
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+
+        if let Some(alias) = line.strip_prefix("alias ") {
+            // Options such as `alias -g` in zsh
+            let alias = alias.trim_start_matches("-g ").trim();
+
+            if let Some((name, value)) = alias.split_once('=') {
+                definitions.push((
+                    name.trim().to_string(),
+                    escape_braces(unquote(value.trim())),
+                ));
+            }
+        } else if let Some(name) = get_function_name(line) {
+            let Some((_, body_start)) = line.split_once('{') else {
+                continue;
+            };
+
+            let mut body_lines = Vec::new();
+
+            // One line functions, e.g. `mkcd() { mkdir -p "$1" && cd "$1"; }`
+            if let Some(body) = body_start.trim().strip_suffix('}') {
+                body_lines.push(body.trim().trim_end_matches(';').to_string());
+            } else {
+                if !body_start.trim().is_empty() {
+                    body_lines.push(body_start.trim().to_string());
+                }
+
+                for body_line in lines.by_ref() {
+                    if body_line.trim() == "}" {
+                        break;
+                    }
+
+                    body_lines.push(body_line.trim().to_string());
+                }
+            }
+
+            let body = body_lines
+                .into_iter()
+                .filter(|body_line| !body_line.is_empty())
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            if !body.is_empty() {
+                definitions.push((name.to_string(), function_body_to_template(&body)));
+            }
+        }
+    }
+
+    definitions
+}
+
+/// Find the most frequently run commands in a shell history file, in bash or zsh format.
+fn parse_shell_history(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| {
+            // zsh extended history lines look like `: 1700000000:0;git status`
+            match line
+                .strip_prefix(": ")
+                .and_then(|line| line.split_once(';'))
+            {
+                Some((_, command)) => command.trim(),
+                None => line.trim(),
+            }
+        })
+        .filter(|command| !command.is_empty() && !command.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Find the commands in a fish history file, which each start with `- cmd: `.
+fn parse_fish_history(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.strip_prefix("- cmd: "))
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Running `rc` itself isn't useful to import.
+fn is_rc_command(command: &str) -> bool {
+    command == "rc" || command.starts_with("rc ")
+}
+
+fn read_files(paths: &[String]) -> Vec<String> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let expanded_path = shellexpand::tilde(path).to_string();
+
+            if !Path::new(&expanded_path).exists() {
+                debug!("Skipping `{path}` for import as it doesn't exist");
+                return None;
+            }
+
+            // History files aren't always valid UTF-8
+            match fs::read(&expanded_path) {
+                Ok(content) => Some(String::from_utf8_lossy(&content).to_string()),
+                Err(e) => {
+                    warn!("Could not read `{path}` for import: {e}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Find commands to import from the source, skipping any that are already defined.
+fn get_import_candidates(
+    source: ImportSource,
+    paths: &[String],
+    existing_definitions: &[CommandDefinition],
+) -> Vec<ImportCandidate> {
+    let default_paths: Vec<String> = match source {
+        ImportSource::Shell => SHELL_CONFIG_PATHS.iter().map(|p| p.to_string()).collect(),
+        ImportSource::History => SHELL_HISTORY_PATHS.iter().map(|p| p.to_string()).collect(),
+    };
+    let paths = if paths.is_empty() {
+        &default_paths
+    } else {
+        paths
+    };
+
+    let named_commands: Vec<(Option<String>, String)> = match source {
+        ImportSource::Shell => read_files(paths)
+            .iter()
+            .flat_map(|content| parse_shell_config(content))
+            .map(|(name, command)| (Some(name), command))
+            .collect(),
+        ImportSource::History => {
+            let mut run_counts: HashMap<String, usize> = HashMap::new();
+
+            for content in read_files(paths) {
+                for command in parse_shell_history(&content) {
+                    *run_counts.entry(command).or_default() += 1;
+                }
+            }
+
+            let mut commands: Vec<(String, usize)> = run_counts
+                .into_iter()
+                .filter(|(command, _)| !is_rc_command(command))
+                .collect();
+            commands.sort_by(|(c1, n1), (c2, n2)| n2.cmp(n1).then_with(|| c1.cmp(c2)));
+
+            commands
+                .into_iter()
+                .take(HISTORY_IMPORT_COUNT)
+                .map(|(command, _)| (None, escape_braces(&command)))
+                .collect()
+        }
+    };
+
+    let mut existing_commands: HashSet<String> = existing_definitions
+        .iter()
+        .map(|command_definition| command_definition.command.join(" "))
+        .collect();
+    let mut existing_ids: HashSet<String> = existing_definitions
+        .iter()
+        .flat_map(CommandDefinition::ids)
+        .cloned()
+        .collect();
+
+    let mut candidates = Vec::new();
+
+    for (name, command) in named_commands {
+        if let Err(e) = Template::parse(&command) {
+            warn!("Skipping `{command}` as it can't be used as a template: {e}");
+            continue;
+        }
+
+        if !existing_commands.insert(command.clone()) {
+            debug!("Skipping `{command}` as it is already defined");
+            continue;
+        }
+
+        // Alias names make good IDs, as long as they don't clash with existing ones
+        let id = name
+            .clone()
+            .filter(|name| name.parse::<usize>().is_err() && existing_ids.insert(name.clone()));
+
+        candidates.push(ImportCandidate {
+            command_definition: CommandDefinition {
+                id,
+                name,
+                command: vec![command],
+                ..CommandDefinition::default()
+            },
+            is_selected: true,
+        });
+    }
+
+    candidates
+}
+
+fn print_review_list(
+    candidates: &[ImportCandidate],
+    selected_index: usize,
+    offset: usize,
+    theme: &Theme,
+) -> Result<()> {
+    let mut stdout = stdout();
+    let (width, height) = terminal::size()?;
+    let width = width as usize;
+
+    let selected_count = candidates
+        .iter()
+        .filter(|candidate| candidate.is_selected)
+        .count();
+    let header = format!(
+        "  Import {selected_count}/{}   |   <space>: Toggle   |   a: Toggle all   |   <enter>: Import   |   <esc>: Cancel",
+        candidates.len()
+    );
+    let header = truncate_to_width(&header, width);
+    let header_padding = " ".repeat(width.saturating_sub(header.width()));
+
+    queue!(stdout, Clear(ClearType::All), MoveTo(0, 0),)?;
+    theme::queue_highlight(
+        &mut stdout,
+        theme.header_background,
+        theme.header_foreground,
+    )?;
+    queue!(stdout, Print(header), Print(header_padding))?;
+    theme::queue_end_highlight(&mut stdout)?;
+
+    for (row, (i, candidate)) in candidates
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height.saturating_sub(1) as usize)
+        .enumerate()
+    {
+        let checkbox = if candidate.is_selected { "[x]" } else { "[ ]" };
+        let command_definition = &candidate.command_definition;
+        // Multi-line function bodies are shown on one line
+        let command = command_definition.command.join(" ").replace('\n', "; ");
+
+        let text = match &command_definition.name {
+            Some(name) => format!("{checkbox} {name}: {command}"),
+            None => format!("{checkbox} {command}"),
+        };
+        let text = truncate_to_width(&text, width);
+
+        queue!(stdout, MoveTo(0, row as u16 + 1))?;
+
+        if i == selected_index {
+            theme::queue_highlight(
+                &mut stdout,
+                theme.selected_background,
+                theme.selected_foreground,
+            )?;
+            queue!(
+                stdout,
+                Print(&text),
+                Print(" ".repeat(width.saturating_sub(text.width()))),
+            )?;
+            theme::queue_end_highlight(&mut stdout)?;
+        } else {
+            queue!(stdout, Print(text))?;
+        }
+    }
+
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Show the commands found so the user can choose which to import.
+///
+/// Returns `None` if the import was cancelled.
+fn review_candidates(
+    mut candidates: Vec<ImportCandidate>,
+    theme: &Theme,
+) -> Result<Option<Vec<CommandDefinition>>> {
+    enable_raw_mode()?;
+    let _raw_mode_guard = RawModeGuard;
+
+    let mut selected_index = 0;
+    let mut offset = 0;
+
+    let result = loop {
+        let (_, height) = terminal::size()?;
+        let list_height = (height.saturating_sub(1) as usize).max(1);
+
+        if selected_index < offset {
+            offset = selected_index;
+        } else if selected_index >= offset + list_height {
+            offset = selected_index + 1 - list_height;
+        }
+
+        print_review_list(&candidates, selected_index, offset, theme)?;
+
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+
+        match key_event.code {
+            KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                break None;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => break None,
+            KeyCode::Up => selected_index = selected_index.saturating_sub(1),
+            KeyCode::Down => selected_index = (selected_index + 1).min(candidates.len() - 1),
+            KeyCode::Char(' ') => {
+                candidates[selected_index].is_selected = !candidates[selected_index].is_selected;
+            }
+            KeyCode::Char('a') => {
+                let select_all = candidates.iter().any(|candidate| !candidate.is_selected);
+
+                for candidate in &mut candidates {
+                    candidate.is_selected = select_all;
+                }
+            }
+            KeyCode::Enter => {
+                break Some(
+                    candidates
+                        .into_iter()
+                        .filter(|candidate| candidate.is_selected)
+                        .map(|candidate| candidate.command_definition)
+                        .collect(),
+                );
+            }
+            _ => {}
+        }
+    };
+
+    let mut stdout = stdout();
+    queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+    stdout.flush()?;
+
+    Ok(result)
+}
+
+/// Let the user choose which of the commands to add, all chosen to start with. Returns `None` if
+/// it was cancelled.
+pub fn choose_commands(
+    command_definitions: Vec<CommandDefinition>,
+    theme: &Theme,
+) -> Result<Option<Vec<CommandDefinition>>> {
+    let candidates = command_definitions
+        .into_iter()
+        .map(|command_definition| ImportCandidate {
+            command_definition,
+            is_selected: true,
+        })
+        .collect();
+
+    review_candidates(candidates, theme)
+}
+
+/// Find commands to import, let the user review them, then add the chosen ones to the config.
+/// The last command run in the shell (e.g. `/bin/zsh`), other than `rc` itself. Shells may only
+/// write their history when they exit, depending on their settings.
+pub fn get_last_shell_command(shell: &str) -> Result<String> {
+    let shell_name = Path::new(shell).file_name().map_or_else(
+        || shell.to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+
+    let Some((_, default_path)) = LAST_COMMAND_HISTORY_PATHS
+        .iter()
+        .find(|(name, _)| *name == shell_name)
+    else {
+        return Err(Error::NoShellHistory(format!(
+            "as `{shell_name}` isn't supported (only bash, zsh and fish are)"
+        )));
+    };
+
+    let path = match env::var("HISTFILE") {
+        Ok(path) if shell_name != "fish" => path,
+        _ => default_path.to_string(),
+    };
+
+    let content = read_files(std::slice::from_ref(&path))
+        .pop()
+        .ok_or_else(|| Error::NoShellHistory(format!("as `{path}` could not be read")))?;
+
+    let commands = if shell_name == "fish" {
+        parse_fish_history(&content)
+    } else {
+        parse_shell_history(&content)
+    };
+
+    commands
+        .into_iter()
+        .rev()
+        .find(|command| !is_rc_command(command))
+        .ok_or_else(|| Error::NoShellHistory(format!("as there are no commands in `{path}`")))
+}
+
+pub fn import_commands(
+    config_path: &str,
+    existing_definitions: &[CommandDefinition],
+    source: ImportSource,
+    paths: &[String],
+    theme: &Theme,
+) -> Result<()> {
+    let candidates = get_import_candidates(source, paths, existing_definitions);
+
+    if candidates.is_empty() {
+        println!("No new commands were found to import.");
+        return Ok(());
+    }
+
+    let Some(command_definitions) = review_candidates(candidates, theme)? else {
+        println!("Import cancelled.");
+        return Ok(());
+    };
+
+    if command_definitions.is_empty() {
+        println!("No commands were selected to import.");
+        return Ok(());
+    }
+
+    config_editing::append_commands(config_path, &command_definitions)?;
+
+    println!(
+        "Imported {} command(s) into `{config_path}`.",
+        command_definitions.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::command_definitions::{CommandDefinition, ParameterDefinition};
+use crate::command_selection;
+use crate::config_editing;
+use crate::error::{Error, Result};
+use crate::file_handling;
+use crate::import::{self, ImportSource};
+use crate::migration;
+use crate::secrets::ConfigValue;
+use crate::theme::Theme;
+
+/// Sets of example commands, built in to `rc`, that new command definitions can start with.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InitTemplate {
+    /// kubectl, docker and terraform commands.
+    Devops,
+    /// npm, HTTP and git commands.
+    Web,
+    /// cargo and git commands.
+    Rust,
+}
+
+impl InitTemplate {
+    fn name(self) -> &'static str {
+        match self {
+            InitTemplate::Devops => "devops",
+            InitTemplate::Web => "web",
+            InitTemplate::Rust => "rust",
+        }
+    }
+
+    fn command_definitions(self) -> Result<Vec<CommandDefinition>> {
+        let config = match self {
+            InitTemplate::Devops => include_str!("../templates/devops.yml"),
+            InitTemplate::Web => include_str!("../templates/web.yml"),
+            InitTemplate::Rust => include_str!("../templates/rust.yml"),
+        };
+
+        migration::parse_command_definitions(&format!("{} template", self.name()), config)
+    }
+}
+
+/// Ask which template to start with, if any.
+fn prompt_for_template() -> Result<Option<InitTemplate>> {
+    let names = InitTemplate::value_variants()
+        .iter()
+        .map(|template| template.name())
+        .collect::<Vec<&str>>()
+        .join(", ");
+
+    loop {
+        let input = command_selection::prompt_text(
+            &format!("Start with some example commands? ({names}, or leave empty for none): "),
+            "",
+        )?;
+        if input.is_empty() {
+            return Ok(None);
+        }
+
+        match InitTemplate::from_str(&input, true) {
+            Ok(template) => return Ok(Some(template)),
+            Err(_) => println!("There's no `{input}` template."),
+        }
+    }
+}
+
+/// Add the template's commands to the config, letting the user choose which when interactive.
+fn add_template_commands(
+    config_path: &str,
+    template: InitTemplate,
+    is_interactive: bool,
+    theme: &Theme,
+) -> Result<()> {
+    let command_definitions = template.command_definitions()?;
+    let command_definitions = if is_interactive {
+        import::choose_commands(command_definitions, theme)?.unwrap_or_default()
+    } else {
+        command_definitions
+    };
+
+    if command_definitions.is_empty() {
+        println!("No example commands were added.");
+        return Ok(());
+    }
+
+    config_editing::append_commands(config_path, &command_definitions)?;
+    println!(
+        "Added {} example command(s) from the `{}` template.",
+        command_definitions.len(),
+        template.name()
+    );
+
+    Ok(())
+}
+
+/// A command to start a new config with when nothing was imported, showing a parameter with a
+/// default.
+fn get_example_command() -> CommandDefinition {
+    CommandDefinition {
+        id: Some("hello".to_string()),
+        name: Some("Say hello".to_string()),
+        command: vec!["echo".to_string(), "Hello, {name}!".to_string()],
+        parameters: Some(vec![ParameterDefinition::new(
+            "name".to_string(),
+            Some(ConfigValue::from("world")),
+        )]),
+        ..CommandDefinition::default()
+    }
+}
+
+fn has_commands(config_path: &str) -> bool {
+    fs::read_to_string(config_path).is_ok_and(|config| !config.trim().is_empty())
+}
+
+/// Create the command definitions at `config_path`, from the template's example commands and,
+/// when interactive, offering to import the shell's aliases and functions into them. Without a
+/// template, one is offered when interactive. A single example command is added if nothing else
+/// was, so there's something to run.
+pub fn initialize_config(
+    config_path: &str,
+    template: Option<InitTemplate>,
+    is_interactive: bool,
+    theme: &Theme,
+) -> Result<()> {
+    if has_commands(config_path) {
+        println!("`{config_path}` already exists, so it was left as it is.");
+        return Ok(());
+    }
+
+    if let Some(directory) = Path::new(config_path).parent() {
+        fs::create_dir_all(directory)
+            .map_err(|e| Error::io_error("config".to_string(), config_path.to_string(), e))?;
+    }
+
+    let template = match template {
+        Some(template) => Some(template),
+        None if is_interactive => prompt_for_template()?,
+        None => None,
+    };
+    if let Some(template) = template {
+        add_template_commands(config_path, template, is_interactive, theme)?;
+    }
+
+    if is_interactive && command_selection::confirm_import_shell_commands()? {
+        // Aliases with the same IDs as the template's commands aren't given IDs
+        let existing_command_defs = if has_commands(config_path) {
+            file_handling::get_command_definitions(config_path)?
+        } else {
+            Vec::new()
+        };
+        import::import_commands(
+            config_path,
+            &existing_command_defs,
+            ImportSource::Shell,
+            &[],
+            theme,
+        )?;
+    }
+
+    if !has_commands(config_path) {
+        config_editing::append_commands(config_path, &[get_example_command()])?;
+        println!("Created `{config_path}` with an example command, `hello`.");
+    }
+
+    println!("Add more commands with `rc new`, or by editing `{config_path}`.");
+
+    Ok(())
+}
@@ -1,11 +1,78 @@
 use itertools::Itertools;
-use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use leon::Template;
+use leon::{Template, Values};
 
 use crate::command_definitions::ParameterDefinition;
 use crate::command_selection;
-use crate::error::Result;
+use crate::error::{Error, Result};
+
+/// A parameter of the command being run: its definition from the config, if it has one, and the
+/// value it was given before, e.g. when rerunning the last command. They're kept apart so a
+/// value from an earlier run isn't mistaken for the config's default.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedParameter {
+    pub definition: Option<ParameterDefinition>,
+    pub value: Option<String>,
+}
+
+/// The command's parameters by name.
+pub type ResolvedParameters = BTreeMap<String, ResolvedParameter>;
+
+impl ResolvedParameter {
+    /// The default from the config.
+    pub fn default(&self) -> Option<&String> {
+        self.definition
+            .as_ref()
+            .and_then(|definition| definition.default.as_ref())
+    }
+
+    /// The value to start from, which is the value given before, otherwise the default.
+    pub fn initial_value(&self) -> Option<&String> {
+        self.value.as_ref().or_else(|| self.default())
+    }
+}
+
+/// Combine the parameter definitions with the values given before, e.g. the last command's.
+pub fn resolve_parameters(
+    definitions: Option<&[ParameterDefinition]>,
+    values: &Option<HashMap<String, String>>,
+) -> ResolvedParameters {
+    let mut parameters = ResolvedParameters::new();
+
+    for definition in definitions.into_iter().flatten() {
+        parameters
+            .entry(definition.name.clone())
+            .or_default()
+            .definition = Some(definition.clone());
+    }
+    for (name, value) in values.iter().flatten() {
+        parameters.entry(name.clone()).or_default().value = Some(value.clone());
+    }
+
+    parameters
+}
+
+/// The values to start from, by parameter name, or `None` if there are none.
+pub fn get_initial_values(parameters: &ResolvedParameters) -> Option<HashMap<String, String>> {
+    let initial_values: HashMap<String, String> = parameters
+        .iter()
+        .filter_map(|(name, parameter)| Some((name.clone(), parameter.initial_value()?.clone())))
+        .collect();
+
+    (!initial_values.is_empty()).then_some(initial_values)
+}
+
+/// The defaults from the config, by parameter name, without any values given before.
+pub fn get_config_defaults(parameters: &ResolvedParameters) -> Option<HashMap<String, String>> {
+    Some(
+        parameters
+            .iter()
+            .filter_map(|(name, parameter)| Some((name.clone(), parameter.default()?.clone())))
+            .collect(),
+    )
+}
 
 pub fn build_default_lookup(
     definitions: &Option<Vec<ParameterDefinition>>,
@@ -24,54 +91,551 @@ pub fn build_default_lookup(
     }
 }
 
+/// Parse `--param` arguments like `env=prod` into the parameters' values.
+pub fn parse_parameter_values(param_arguments: &[String]) -> Result<HashMap<String, String>> {
+    param_arguments
+        .iter()
+        .map(|param_argument| {
+            param_argument
+                .split_once('=')
+                .filter(|(name, _)| !name.trim().is_empty())
+                .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+                .ok_or_else(|| Error::InvalidParam(param_argument.clone()))
+        })
+        .collect()
+}
+
+/// The parameters a default is computed from, e.g. `env` for `{env}-cluster`, or `None` if it's
+/// a plain value. Only the parameters in `values` count, so a default with other braces (e.g.
+/// `awk '{print $1}'`) is left as it is.
+fn get_default_sources(default: &str, values: &HashMap<String, String>) -> Option<Vec<String>> {
+    let template = ArgumentTemplate::parse(default).ok()?;
+    let sources: Vec<String> = template
+        .keys()
+        .into_iter()
+        .unique()
+        .map(str::to_string)
+        .collect();
+
+    (!sources.is_empty() && sources.iter().all(|source| values.contains_key(source)))
+        .then_some(sources)
+}
+
+/// Fill in a default computed from other parameters with their values, or `None` if it's a plain
+/// value, or uses a parameter not in `values`.
+pub fn render_computed_default(default: &str, values: &HashMap<String, String>) -> Option<String> {
+    get_default_sources(default, values)?;
+    ArgumentTemplate::parse(default).ok()?.render(values).ok()
+}
+
+/// Fill in the parameters still set to a computed default with the values of the parameters it
+/// uses. Those given a value of their own are left as they are, and defaults that use each other
+/// are left unfilled.
+pub fn resolve_computed_defaults(
+    context: &mut Option<HashMap<String, String>>,
+    defaults: &Option<HashMap<String, String>>,
+) {
+    let (Some(values), Some(defaults)) = (context.as_mut(), defaults) else {
+        return;
+    };
+
+    let mut pending: HashMap<&String, Vec<String>> = defaults
+        .iter()
+        .filter(|(name, default)| values.get(*name) == Some(*default))
+        .filter_map(|(name, default)| Some((name, get_default_sources(default, values)?)))
+        .collect();
+
+    // A default can use another computed default, so that's filled in first
+    while let Some(name) = pending
+        .iter()
+        .find(|(_, sources)| sources.iter().all(|source| !pending.contains_key(&source)))
+        .map(|(name, _)| *name)
+    {
+        pending.remove(name);
+
+        if let Some(value) = render_computed_default(&defaults[name], values) {
+            values.insert(name.clone(), value);
+        }
+    }
+}
+
+/// Prompt for each parameter in turn, in the order given. A default computed from other
+/// parameters is filled in with the values given before it, or `known_values`. One using a
+/// parameter prompted for after it is left for `resolve_computed_defaults`.
 pub fn get_template_context(
-    tokens: &HashSet<String>,
+    tokens: &[String],
     defaults: &Option<HashMap<String, String>>,
+    known_values: &HashMap<String, String>,
+    recent_values: &HashMap<String, Vec<String>>,
 ) -> Result<Option<HashMap<String, String>>> {
     if tokens.is_empty() {
         return Ok(None);
     }
 
     let mut context: HashMap<String, String> = HashMap::new();
-    for key in tokens.iter().sorted() {
-        let default_value = match defaults {
-            Some(defaults) => defaults.get(key),
-            None => None,
-        };
+    for key in tokens {
+        let default_value = defaults
+            .as_ref()
+            .and_then(|defaults| defaults.get(key))
+            .map(|default| {
+                let mut values = known_values.clone();
+                values.extend(context.clone());
+
+                render_computed_default(default, &values).unwrap_or_else(|| default.clone())
+            });
 
-        let value = command_selection::prompt_value(key, default_value)?;
+        let value = command_selection::prompt_value(
+            key,
+            default_value.as_ref(),
+            recent_values.get(key).map_or(&[], Vec::as_slice),
+        )?;
 
         context.insert(key.to_string(), value);
     }
+
     Ok(Some(context))
 }
 
-/// Find all tokens in all arguments of templates of command.
-pub fn get_tokens(templates: &[Template]) -> HashSet<String> {
+/// Placeholder for the arguments given to `rc` after `--`, e.g. `rc cargo-run -- --release`.
+/// It isn't a parameter, so isn't prompted for, and is empty if no arguments are given.
+pub const FORWARDED_ARGUMENTS_KEY: &str = "args...";
+
+/// The forwarded arguments as they're put in the command, each quoted so it's passed on as
+/// given.
+pub fn quote_forwarded_arguments(arguments: &[String]) -> String {
+    arguments
+        .iter()
+        .map(|argument| shell_quote(argument))
+        .join(" ")
+}
+
+/// Separates a parameter's name from the filters applied to its value, e.g. `{branch|slugify}`.
+const FILTER_SEPARATOR: char = '|';
+
+/// Transforms a parameter's value, e.g. to change its case.
+pub type Filter = fn(&str) -> String;
+
+/// Filters that can be applied to a parameter's value, by name.
+pub const FILTERS: [(&str, Filter); 5] = [
+    ("upper", str::to_uppercase),
+    ("lower", str::to_lowercase),
+    ("trim", |value| value.trim().to_string()),
+    ("quote", shell_quote),
+    ("slugify", slugify),
+];
+
+/// Get a filter from [`FILTERS`] by its name.
+pub fn get_filter(name: &str) -> Option<Filter> {
+    FILTERS
+        .iter()
+        .find(|(filter_name, _)| *filter_name == name)
+        .map(|(_, filter)| *filter)
+}
+
+/// Lowercase the value, replacing anything that isn't a letter or number with `-`, so it can be
+/// used in file names, branch names, etc.
+fn slugify(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .join("-")
+}
+
+/// Split a template key like `name|upper|quote` into the parameter's name and its filters.
+fn split_filters(key: &str) -> (&str, impl Iterator<Item = &str>) {
+    let mut parts = key.split(FILTER_SEPARATOR).map(str::trim);
+    let name = parts.next().unwrap_or_default();
+    (name, parts)
+}
+
+/// Check every filter used in the template exists.
+fn check_filters(template: &Template) -> Result<()> {
+    for key in template.keys() {
+        let (_, mut filters) = split_filters(key);
+
+        if let Some(filter) = filters.find(|filter| get_filter(filter).is_none()) {
+            return Err(Error::UnknownFilter(filter.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Values for rendering a template, with any filters in its keys applied.
+struct FilteredValues<'a> {
+    context: &'a HashMap<String, String>,
+    apply_filters: bool,
+}
+
+impl Values for FilteredValues<'_> {
+    fn get_value(&self, key: &str) -> Option<Cow<'_, str>> {
+        let (name, filters) = split_filters(key);
+        let value = match self.context.get(name) {
+            Some(value) => value,
+            None if name == FORWARDED_ARGUMENTS_KEY => return Some(Cow::Borrowed("")),
+            None => return None,
+        };
+
+        if !self.apply_filters {
+            return Some(Cow::Borrowed(value));
+        }
+
+        // Filters are checked when parsing, so unknown ones can't be found here
+        Some(Cow::Owned(
+            filters
+                .filter_map(get_filter)
+                .fold(value.clone(), |value, filter| filter(&value)),
+        ))
+    }
+}
+
+const IF_START: &str = "{#if ";
+const IF_END: &str = "{/if}";
+
+/// Values of a parameter that make a `{#if name}` section be included.
+const TRUE_VALUES: [&str; 5] = ["true", "yes", "y", "1", "on"];
+
+/// Doubled braces are a literal brace, e.g. `awk '{{print $1}}'`, as well as `leon`'s `\{`.
+const LITERAL_OPEN: &str = "{{";
+const LITERAL_CLOSE: &str = "}}";
+
+/// Part of an argument: either a `leon` template, a literal brace, or a section that is only
+/// included if a parameter is true.
+enum Segment<'a> {
+    Template(Template<'a>),
+    Literal(&'a str),
+    Conditional {
+        name: &'a str,
+        negated: bool,
+        segments: Vec<Segment<'a>>,
+    },
+}
+
+/// A template for one argument of a command, which can contain conditional sections like
+/// `{#if prod}--require-approval{/if}` (or `{#if !prod}`) around normal `leon` templates.
+pub struct ArgumentTemplate<'a> {
+    segments: Vec<Segment<'a>>,
+}
+
+/// Find the next `{#if `, `{/if}`, `{{` or `}}` at or after `position`, ignoring escaped braces
+/// and the braces around template keys.
+fn find_marker(argument: &str, position: usize) -> Option<usize> {
+    let mut is_in_key = false;
+    let mut is_escaped = false;
+
+    for (index, c) in argument[position..].char_indices() {
+        let index = position + index;
+        let rest = &argument[index..];
+
+        if is_escaped {
+            is_escaped = false;
+        } else if c == '\\' {
+            is_escaped = true;
+        } else if is_in_key {
+            is_in_key = c != '}';
+        } else if rest.starts_with(IF_START)
+            || rest.starts_with(IF_END)
+            || rest.starts_with(LITERAL_OPEN)
+            || rest.starts_with(LITERAL_CLOSE)
+        {
+            return Some(index);
+        } else if c == '{' {
+            is_in_key = true;
+        }
+    }
+
+    None
+}
+
+fn parse_template(text: &str) -> Result<Segment<'_>> {
+    let template = Template::parse(text)?;
+    check_filters(&template)?;
+    Ok(Segment::Template(template))
+}
+
+/// Parse segments from `position` until the end of the argument, or the `{/if}` that closes the
+/// section being parsed if `inside_conditional`.
+fn parse_segments<'a>(
+    argument: &'a str,
+    position: &mut usize,
+    inside_conditional: bool,
+) -> Result<Vec<Segment<'a>>> {
+    let mut segments = Vec::new();
+
+    loop {
+        let Some(marker) = find_marker(argument, *position) else {
+            if inside_conditional {
+                return Err(Error::InvalidConditional(format!(
+                    "`{argument}` has a `{IF_START}...}}` without a closing `{IF_END}`"
+                )));
+            }
+
+            segments.push(parse_template(&argument[*position..])?);
+            *position = argument.len();
+            return Ok(segments);
+        };
+
+        if marker > *position {
+            segments.push(parse_template(&argument[*position..marker])?);
+        }
+
+        if argument[marker..].starts_with(LITERAL_OPEN)
+            || argument[marker..].starts_with(LITERAL_CLOSE)
+        {
+            segments.push(Segment::Literal(&argument[marker..=marker]));
+            *position = marker + 2;
+            continue;
+        }
+
+        if argument[marker..].starts_with(IF_END) {
+            if !inside_conditional {
+                return Err(Error::InvalidConditional(format!(
+                    "`{argument}` has a `{IF_END}` without an opening `{IF_START}...}}`"
+                )));
+            }
+
+            *position = marker + IF_END.len();
+            return Ok(segments);
+        }
+
+        let name_start = marker + IF_START.len();
+        let name_end = argument[name_start..]
+            .find('}')
+            .map(|index| name_start + index)
+            .ok_or_else(|| {
+                Error::InvalidConditional(format!("`{argument}` has an unclosed `{IF_START}`"))
+            })?;
+        let name = argument[name_start..name_end].trim();
+        let (name, negated) = match name.strip_prefix('!') {
+            Some(name) => (name.trim(), true),
+            None => (name, false),
+        };
+
+        *position = name_end + 1;
+        segments.push(Segment::Conditional {
+            name,
+            negated,
+            segments: parse_segments(argument, position, true)?,
+        });
+    }
+}
+
+fn is_true(value: &str) -> bool {
+    TRUE_VALUES
+        .iter()
+        .any(|true_value| value.trim().eq_ignore_ascii_case(true_value))
+}
+
+fn render_segments(
+    segments: &[Segment],
+    values: &FilteredValues,
+    rendered: &mut String,
+) -> Result<()> {
+    for segment in segments {
+        match segment {
+            Segment::Template(template) => rendered.push_str(&template.render(values)?),
+            Segment::Literal(text) => rendered.push_str(text),
+            Segment::Conditional {
+                name,
+                negated,
+                segments,
+            } => {
+                let value = values
+                    .context
+                    .get(*name)
+                    .is_some_and(|value| is_true(value));
+
+                if value != *negated {
+                    render_segments(segments, values, rendered)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn add_keys<'a>(segments: &'a [Segment], keys: &mut Vec<&'a str>, include_templates: bool) {
+    for segment in segments {
+        match segment {
+            Segment::Template(template) => {
+                if include_templates {
+                    keys.extend(template.keys().map(|key| split_filters(key).0));
+                }
+            }
+            Segment::Literal(_) => {}
+            Segment::Conditional { name, segments, .. } => {
+                keys.push(name);
+                add_keys(segments, keys, include_templates);
+            }
+        }
+    }
+}
+
+impl<'a> ArgumentTemplate<'a> {
+    pub fn parse(argument: &'a str) -> Result<Self> {
+        let mut position = 0;
+
+        Ok(Self {
+            segments: parse_segments(argument, &mut position, false)?,
+        })
+    }
+
+    /// Names of the parameters used, including in conditions.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys = Vec::new();
+        add_keys(&self.segments, &mut keys, true);
+        keys
+    }
+
+    /// Names of the parameters used in conditions.
+    pub fn condition_keys(&self) -> Vec<&str> {
+        let mut keys = Vec::new();
+        add_keys(&self.segments, &mut keys, false);
+        keys
+    }
+
+    pub fn render(&self, context: &HashMap<String, String>) -> Result<String> {
+        self.render_values(context, true)
+    }
+
+    /// Render without applying any filters, e.g. when the values are placeholders.
+    pub fn render_unfiltered(&self, context: &HashMap<String, String>) -> Result<String> {
+        self.render_values(context, false)
+    }
+
+    fn render_values(
+        &self,
+        context: &HashMap<String, String>,
+        apply_filters: bool,
+    ) -> Result<String> {
+        let values = FilteredValues {
+            context,
+            apply_filters,
+        };
+
+        let mut rendered = String::new();
+        render_segments(&self.segments, &values, &mut rendered)?;
+        Ok(rendered)
+    }
+}
+
+/// Find all tokens in all arguments of templates of command. The forwarded arguments aren't a
+/// token, as they aren't a parameter.
+pub fn get_tokens(templates: &[ArgumentTemplate]) -> HashSet<String> {
     let mut tokens = HashSet::new();
 
     for template in templates {
         for key in template.keys() {
-            let _ = tokens.insert((*key).to_string());
+            if key != FORWARDED_ARGUMENTS_KEY {
+                let _ = tokens.insert(key.to_string());
+            }
         }
     }
 
     tokens
 }
 
-pub fn get_templates(command: &[String]) -> Result<Vec<Template>> {
-    let mut templates: Vec<Template> = Vec::new();
+pub fn get_templates(command: &[String]) -> Result<Vec<ArgumentTemplate>> {
+    let mut templates: Vec<ArgumentTemplate> = Vec::new();
 
     for argument in command {
-        templates.push(Template::parse(argument.as_ref())?);
+        templates.push(ArgumentTemplate::parse(argument.as_ref())?);
     }
 
     Ok(templates)
 }
 
+/// A command's templates and its working directory's, parsed once so the same templates can be
+/// used to find the parameters to prompt for, and to fill them in as many times as needed (e.g.
+/// for each `--each` value, or after changing the parameters).
+pub struct PreparedTemplate<'a> {
+    pub command: Vec<ArgumentTemplate<'a>>,
+    /// The working directory can use the same parameters as the command.
+    pub working_directory: Option<ArgumentTemplate<'a>>,
+    tokens: HashSet<String>,
+}
+
+impl<'a> PreparedTemplate<'a> {
+    pub fn parse(command: &'a [String], working_directory: Option<&'a String>) -> Result<Self> {
+        let command = get_templates(command)?;
+        let working_directory = working_directory
+            .map(|working_directory| ArgumentTemplate::parse(working_directory))
+            .transpose()?;
+
+        let mut tokens = get_tokens(&command);
+        tokens.extend(get_tokens(working_directory.as_slice()));
+
+        Ok(PreparedTemplate {
+            command,
+            working_directory,
+            tokens,
+        })
+    }
+
+    /// The parameters used by the command and its working directory.
+    pub fn tokens(&self) -> &HashSet<String> {
+        &self.tokens
+    }
+
+    /// The parameters in the order they're first used, so they're prompted for in that order.
+    /// Any not used by the command or working directory (e.g. only by a chained command) are
+    /// last, by name.
+    pub fn order_tokens(&self, tokens: &HashSet<String>) -> Vec<String> {
+        let order: Vec<&str> = self
+            .command
+            .iter()
+            .chain(&self.working_directory)
+            .flat_map(ArgumentTemplate::keys)
+            .unique()
+            .collect();
+
+        tokens
+            .iter()
+            .sorted_by_key(|token| {
+                let position = order.iter().position(|key| key == token);
+                (position.unwrap_or(usize::MAX), *token)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Whether the command uses `{args...}`, so can be given arguments after `--`.
+    pub fn forwards_arguments(&self) -> bool {
+        self.command
+            .iter()
+            .any(|template| template.keys().contains(&FORWARDED_ARGUMENTS_KEY))
+    }
+
+    pub fn render_command(&self, context: &Option<HashMap<String, String>>) -> Result<String> {
+        Ok(interpolate_command(context, &self.command)?.join(" "))
+    }
+
+    pub fn render_working_directory(
+        &self,
+        context: &Option<HashMap<String, String>>,
+    ) -> Result<Option<String>> {
+        Ok(interpolate_command(context, self.working_directory.as_slice())?.pop())
+    }
+}
+
+/// Quote the value for the shell, so it is used as-is.
+pub fn shell_quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./:=@%+,".contains(c))
+    {
+        return value.to_string();
+    }
+
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 pub fn interpolate_command(
     context: &Option<HashMap<String, String>>,
-    templates: &[Template],
+    templates: &[ArgumentTemplate],
 ) -> Result<Vec<String>> {
     let mut interpolated_arguments: Vec<String> = Vec::new();
 
@@ -80,8 +644,163 @@ pub fn interpolate_command(
     let context = context.as_ref().unwrap_or(&empty_hashmap);
 
     for template in templates {
-        interpolated_arguments.push(template.render(&context)?);
+        interpolated_arguments.push(template.render(context)?);
     }
 
     Ok(interpolated_arguments)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(argument: &str, context: &[(&str, &str)]) -> Result<String> {
+        let context = context
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        ArgumentTemplate::parse(argument)?.render(&context)
+    }
+
+    fn tokens(command: &[&str]) -> Vec<String> {
+        let command = command
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect_vec();
+        PreparedTemplate::parse(&command, None)
+            .unwrap()
+            .tokens()
+            .iter()
+            .sorted()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn conditional_is_included_when_true() {
+        let argument = "deploy{#if prod} --require-approval{/if}";
+
+        assert_eq!(
+            render(argument, &[("prod", "yes")]).unwrap(),
+            "deploy --require-approval"
+        );
+        assert_eq!(render(argument, &[("prod", "no")]).unwrap(), "deploy");
+        assert_eq!(render(argument, &[]).unwrap(), "deploy");
+    }
+
+    #[test]
+    fn negated_conditional_is_included_when_false() {
+        let argument = "{#if !prod}--dry-run{/if}";
+
+        assert_eq!(render(argument, &[("prod", "true")]).unwrap(), "");
+        assert_eq!(render(argument, &[("prod", "false")]).unwrap(), "--dry-run");
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let argument = "{#if a}a{#if b}b{/if}{/if}";
+
+        assert_eq!(render(argument, &[("a", "1"), ("b", "1")]).unwrap(), "ab");
+        assert_eq!(render(argument, &[("a", "1"), ("b", "0")]).unwrap(), "a");
+        assert_eq!(render(argument, &[("a", "0"), ("b", "1")]).unwrap(), "");
+    }
+
+    #[test]
+    fn conditions_are_parameters() {
+        assert_eq!(
+            tokens(&["echo", "{#if verbose}-v {level}{/if}"]),
+            vec!["level", "verbose"]
+        );
+    }
+
+    #[test]
+    fn unterminated_conditionals_are_errors() {
+        for argument in ["{#if a}x", "{#if a}{#if b}x{/if}", "{#if a", "x{/if}"] {
+            assert!(
+                matches!(
+                    ArgumentTemplate::parse(argument),
+                    Err(Error::InvalidConditional(_))
+                ),
+                "`{argument}` should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_filter_is_an_error() {
+        assert!(matches!(
+            ArgumentTemplate::parse("{name|shout}"),
+            Err(Error::UnknownFilter(filter)) if filter == "shout"
+        ));
+    }
+
+    #[test]
+    fn quote_filter() {
+        assert_eq!(
+            render("{path|quote}", &[("path", "my file's")]).unwrap(),
+            "'my file'\\''s'"
+        );
+        assert_eq!(
+            render("{path|quote}", &[("path", "src/main.rs")]).unwrap(),
+            "src/main.rs"
+        );
+        assert_eq!(render("{path|quote}", &[("path", "")]).unwrap(), "''");
+    }
+
+    #[test]
+    fn slugify_filter() {
+        assert_eq!(
+            render("{branch|slugify}", &[("branch", "Feature/Add  Login!")]).unwrap(),
+            "feature-add-login"
+        );
+    }
+
+    #[test]
+    fn filters_are_applied_in_order() {
+        assert_eq!(
+            render("{name|trim|upper}", &[("name", "  rc ")]).unwrap(),
+            "RC"
+        );
+    }
+
+    #[test]
+    fn unfiltered_render_ignores_filters() {
+        let context = HashMap::from([("name".to_string(), "a b".to_string())]);
+
+        assert_eq!(
+            ArgumentTemplate::parse("{name|quote}")
+                .unwrap()
+                .render_unfiltered(&context)
+                .unwrap(),
+            "a b"
+        );
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let command = vec!["awk".to_string(), "'{{print $1}}'".to_string()];
+        let template = PreparedTemplate::parse(&command, None).unwrap();
+
+        assert_eq!(template.render_command(&None).unwrap(), "awk '{print $1}'");
+    }
+
+    #[test]
+    fn doubled_braces_can_surround_parameters() {
+        assert_eq!(
+            render("{{\"name\": \"{name}\"}}", &[("name", "rc")]).unwrap(),
+            "{\"name\": \"rc\"}"
+        );
+    }
+
+    #[test]
+    fn unmatched_doubled_close_brace_is_literal() {
+        assert_eq!(render("done}}", &[]).unwrap(), "done}");
+        assert_eq!(render("{name}}}", &[("name", "x")]).unwrap(), "x}");
+    }
+
+    #[test]
+    fn doubled_braces_are_not_parameters() {
+        assert_eq!(tokens(&["awk", "'{{print $1}}'"]), Vec::<String>::new());
+        assert_eq!(tokens(&["echo", "{{literal}}", "{name}"]), vec!["name"]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::io::stdout;
+use std::process::{self, Child, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crossterm::cursor;
+use crossterm::execute;
+use crossterm::terminal::disable_raw_mode;
+use log::debug;
+
+use crate::error::{Error, Result};
+
+/// Exit code when interrupted with ctrl-c, following the shell convention of 128 + SIGINT.
+pub const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// IDs of the commands that are currently running.
+static RUNNING_CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Start the command in its own process group if there is no terminal (e.g. in CI), so it and
+/// anything it starts can be interrupted together.
+///
+/// With a terminal, ctrl-c already goes to every process in the foreground process group, and
+/// moving the command out of it would stop it from reading from the terminal.
+pub fn set_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        if std::fs::File::open(crate::command_selection::TTY_PATH).is_err() {
+            command.process_group(0);
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// A running command, which is passed on any ctrl-c until it is dropped.
+pub struct RunningChild(u32);
+
+impl RunningChild {
+    pub fn new(child: &Child) -> Self {
+        if let Ok(mut running_children) = RUNNING_CHILDREN.lock() {
+            running_children.push(child.id());
+        }
+
+        Self(child.id())
+    }
+}
+
+impl Drop for RunningChild {
+    fn drop(&mut self) {
+        if let Ok(mut running_children) = RUNNING_CHILDREN.lock() {
+            running_children.retain(|id| *id != self.0);
+        }
+    }
+}
+
+/// Interrupt the command, and anything it started in its process group.
+#[cfg(unix)]
+fn interrupt_child(id: u32) {
+    let Ok(pid) = libc::pid_t::try_from(id) else {
+        return;
+    };
+
+    // SAFETY: `kill` only sends a signal, the results are ignored as the processes may have
+    // already exited (or not be in their own process group).
+    unsafe {
+        libc::kill(-pid, libc::SIGINT);
+        libc::kill(pid, libc::SIGINT);
+    }
+}
+
+/// On Windows, ctrl-c is already sent to every process attached to the console.
+#[cfg(not(unix))]
+fn interrupt_child(_id: u32) {}
+
+/// Exit after ctrl-c was pressed while nothing was running, leaving the terminal usable. This is
+/// also used when ctrl-c is read as a key in raw mode, where it doesn't interrupt.
+pub fn exit_interrupted() -> ! {
+    let _ = disable_raw_mode();
+    let _ = execute!(stdout(), cursor::Show);
+    println!();
+    process::exit(i32::from(INTERRUPTED_EXIT_CODE));
+}
+
+fn handle_interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+
+    let running_children = RUNNING_CHILDREN
+        .lock()
+        .map(|running_children| running_children.clone())
+        .unwrap_or_default();
+
+    if running_children.is_empty() {
+        // Nothing is running (e.g. while prompting), so exit straight away
+        exit_interrupted();
+    }
+
+    // Otherwise let the commands finish being interrupted, so their runs are recorded
+    for id in running_children {
+        debug!("Interrupting process {id}");
+        interrupt_child(id);
+    }
+}
+
+/// Handle ctrl-c by passing it on to the running commands, instead of exiting immediately.
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(handle_interrupt)
+        .map_err(|e| Error::Misc(format!("Could not handle ctrl-c: {e}")))
+}
+
+/// Whether ctrl-c was pressed while a command was running.
+pub fn was_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, stdout};
+use std::path::Path;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::execution::PreparedCommand;
+use crate::file_handling;
+use crate::history;
+use crate::logs;
+
+/// How often `rc attach` checks for new output.
+const ATTACH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A command started with `--background`, which keeps running after `rc` exits.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Job {
+    /// See `CommandExecutionTemplate::history_key`.
+    pub command_key: String,
+    pub command: String,
+    pub pid: u32,
+    /// Where the job's output is written.
+    pub log_path: String,
+    /// Seconds since the Unix epoch that the job was started.
+    pub started_at: u64,
+}
+
+/// Jobs by their number, which is used to attach to or kill them.
+pub type Jobs = BTreeMap<u32, Job>;
+
+/// Whether the process is still running.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+
+    // SAFETY: signal 0 only checks the process exists, nothing is sent to it
+    let result = unsafe { libc::kill(pid, 0) };
+    // It may be running as another user
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+}
+
+/// Stop the job, and anything it started in its process group.
+#[cfg(unix)]
+fn stop_process(pid: u32) {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return;
+    };
+
+    // SAFETY: `kill` only sends a signal, the result is ignored as the job may have just exited
+    unsafe {
+        libc::kill(-pid, libc::SIGTERM);
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_process(pid: u32) {
+    let _ = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .output();
+}
+
+impl Job {
+    pub fn is_running(&self) -> bool {
+        is_running(self.pid)
+    }
+}
+
+/// Read the jobs file, there are none if it does not exist.
+pub fn read_jobs(jobs_path: &str) -> Result<Jobs> {
+    if !Path::new(jobs_path).exists() {
+        return Ok(Jobs::new());
+    }
+
+    let jobs = fs::read_to_string(jobs_path)
+        .map_err(|e| Error::io_error("jobs".to_string(), jobs_path.to_string(), e))?;
+
+    serde_yaml::from_str(&jobs).map_err(|e| {
+        Error::yaml_error(
+            "reading".to_string(),
+            "jobs".to_string(),
+            jobs_path.to_string(),
+            e,
+        )
+    })
+}
+
+fn write_jobs(jobs_path: &str, jobs: &Jobs) -> Result<()> {
+    let jobs = serde_yaml::to_string(jobs).map_err(|e| {
+        Error::yaml_error(
+            "writing".to_string(),
+            "jobs".to_string(),
+            jobs_path.to_string(),
+            e,
+        )
+    })?;
+
+    file_handling::write_atomically("jobs", jobs_path, &jobs)
+}
+
+/// Start the command detached from the terminal, with its output written to `log_path` (or a
+/// log for the job in `job_logs_path`), and record it as a job. Jobs that have finished are
+/// removed, so their numbers can be reused. Returns the job's number.
+pub fn start_job(
+    jobs_path: &str,
+    job_logs_path: &str,
+    shell: &str,
+    prepared: &PreparedCommand,
+    command_key: &str,
+    log_path: Option<String>,
+) -> Result<(u32, Job)> {
+    // Held until the job is recorded, so another job started at once doesn't get the same number
+    let _lock = file_handling::lock_file("jobs", jobs_path)?;
+    let mut jobs = read_jobs(jobs_path)?;
+    jobs.retain(|_, job| job.is_running());
+
+    let number = jobs.keys().max().map_or(1, |number| number + 1);
+    let log_path = log_path.unwrap_or_else(|| {
+        Path::new(job_logs_path)
+            .join(format!("{number}.log"))
+            .to_string_lossy()
+            .to_string()
+    });
+    let log_file = logs::create_log_file(&log_path)?;
+    let log_file_for_stderr = log_file
+        .try_clone()
+        .map_err(|e| Error::io_error("log".to_string(), log_path.clone(), e))?;
+
+    // Not an interactive shell, as there's no terminal for it
+    let mut command = prepared.shell_command(shell, false)?;
+    command
+        .stdin(Stdio::null())
+        .stdout(log_file)
+        .stderr(log_file_for_stderr);
+
+    // In its own process group, so ctrl-c in the terminal doesn't stop it, and it can be killed
+    // with anything it starts
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        command.process_group(0);
+    }
+
+    let child = command.spawn()?;
+
+    let job = Job {
+        command_key: command_key.to_string(),
+        command: prepared.command.clone(),
+        pid: child.id(),
+        log_path,
+        started_at: history::now(),
+    };
+    jobs.insert(number, job.clone());
+    write_jobs(jobs_path, &jobs)?;
+
+    Ok((number, job))
+}
+
+/// Find a job by its number, or the most recent job of a command by its ID.
+fn find_job(jobs: &Jobs, job: &str) -> Result<u32> {
+    let number = match job.parse::<u32>() {
+        Ok(number) => jobs.contains_key(&number).then_some(number),
+        Err(_) => jobs
+            .iter()
+            .rev()
+            .find(|(_, existing_job)| existing_job.command_key == job)
+            .map(|(number, _)| *number),
+    };
+
+    number.ok_or_else(|| Error::UnknownJob(job.to_string()))
+}
+
+/// Print the job's output so far, then follow it until the job finishes or ctrl-c is pressed,
+/// which leaves the job running.
+pub fn attach_to_job(jobs_path: &str, job: &str) -> Result<()> {
+    let jobs = read_jobs(jobs_path)?;
+    let job = &jobs[&find_job(&jobs, job)?];
+
+    let mut log_file = File::open(&job.log_path)
+        .map_err(|e| Error::io_error("log".to_string(), job.log_path.clone(), e))?;
+
+    loop {
+        // Checked before reading, so the last of the output is printed once it finishes
+        let is_running = job.is_running();
+        io::copy(&mut log_file, &mut stdout())?;
+
+        if !is_running {
+            return Ok(());
+        }
+
+        thread::sleep(ATTACH_POLL_INTERVAL);
+    }
+}
+
+/// Stop the job if it's still running, and remove it from the jobs.
+pub fn kill_job(jobs_path: &str, job: &str) -> Result<(u32, Job)> {
+    let _lock = file_handling::lock_file("jobs", jobs_path)?;
+    let mut jobs = read_jobs(jobs_path)?;
+    let number = find_job(&jobs, job)?;
+    let job = jobs
+        .remove(&number)
+        .ok_or_else(|| Error::UnknownJob(number.to_string()))?;
+
+    if job.is_running() {
+        stop_process(job.pid);
+    }
+
+    write_jobs(jobs_path, &jobs)?;
+    Ok((number, job))
+}
+
+/// Print each job's number, command, whether it's still running, and where its output is.
+pub fn print_jobs(jobs: &Jobs) {
+    if jobs.is_empty() {
+        println!("There are no jobs, start one with `rc <command> --background`.");
+        return;
+    }
+
+    for (number, job) in jobs {
+        let status = if job.is_running() {
+            format!("running, pid {}", job.pid)
+        } else {
+            "finished".to_string()
+        };
+
+        println!(
+            "{number}: {} ({status}), started {}",
+            job.command_key,
+            history::format_age(job.started_at)
+        );
+        println!("    {}", job.command);
+        println!("    Output: {}", job.log_path);
+    }
+}
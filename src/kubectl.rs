@@ -0,0 +1,167 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::execution::has_terminal;
+use crate::interpolation::{get_templates, get_tokens, interpolate_command, shell_quote};
+
+/// A Kubernetes pod to run the command in with `kubectl exec`, either by its name or the first
+/// running pod matching a label selector. Each field can use the command's parameters, e.g.
+/// `namespace: "{namespace}"`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct KubectlDefinition {
+    /// Name of the pod to run the command in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<String>,
+    /// Label selector for the pod, e.g. `app=api`. The first running pod that matches is used,
+    /// which is found when the command is run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    /// The pod's namespace, or the current one if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Container in the pod, or its default container if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// The kubeconfig context, or the current one if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+fn render_field(
+    field: &Option<String>,
+    context: &Option<HashMap<String, String>>,
+) -> Result<Option<String>> {
+    let Some(field) = field else {
+        return Ok(None);
+    };
+
+    Ok(interpolate_command(context, &get_templates(std::slice::from_ref(field))?)?.pop())
+}
+
+impl KubectlDefinition {
+    fn fields(&self) -> Vec<String> {
+        [
+            &self.pod,
+            &self.selector,
+            &self.namespace,
+            &self.container,
+            &self.context,
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect()
+    }
+
+    /// Error unless exactly one of `pod` and `selector` is given.
+    pub fn check(&self) -> Result<()> {
+        match (&self.pod, &self.selector) {
+            (Some(_), Some(_)) => Err(Error::InvalidKubectl(
+                "give either `pod` or `selector`, not both".to_string(),
+            )),
+            (None, None) => Err(Error::InvalidKubectl(
+                "`pod` or `selector` is required".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The parameters used by the fields.
+    pub fn tokens(&self) -> Result<HashSet<String>> {
+        Ok(get_tokens(&get_templates(&self.fields())?))
+    }
+
+    /// Fill in the parameters used by the fields.
+    pub fn render(&self, context: &Option<HashMap<String, String>>) -> Result<KubectlDefinition> {
+        Ok(KubectlDefinition {
+            pod: render_field(&self.pod, context)?,
+            selector: render_field(&self.selector, context)?,
+            namespace: render_field(&self.namespace, context)?,
+            container: render_field(&self.container, context)?,
+            context: render_field(&self.context, context)?,
+        })
+    }
+
+    /// `kubectl` with the context and namespace to use.
+    fn kubectl(&self) -> Vec<String> {
+        let mut arguments = vec!["kubectl".to_string()];
+
+        if let Some(context) = &self.context {
+            arguments.extend(["--context".to_string(), shell_quote(context)]);
+        }
+
+        if let Some(namespace) = &self.namespace {
+            arguments.extend(["-n".to_string(), shell_quote(namespace)]);
+        }
+
+        arguments
+    }
+
+    /// The command that runs `command` with the shell in the pod. With a selector, the pod is
+    /// found by the shell when it runs the command, so it's the one running at the time.
+    pub fn wrap_command(&self, command: &str) -> String {
+        let mut arguments = self.kubectl();
+        arguments.extend(["exec".to_string(), "-i".to_string()]);
+        if has_terminal() {
+            arguments.push("-t".to_string());
+        }
+
+        match (&self.pod, &self.selector) {
+            (Some(pod), _) => arguments.push(shell_quote(pod)),
+            (None, Some(selector)) => {
+                let mut find_pod = self.kubectl();
+                find_pod.extend([
+                    "get".to_string(),
+                    "pods".to_string(),
+                    "-l".to_string(),
+                    shell_quote(selector),
+                    "--field-selector=status.phase=Running".to_string(),
+                    "-o".to_string(),
+                    "name".to_string(),
+                ]);
+                arguments.push(format!("\"$({} | head -n 1)\"", find_pod.join(" ")));
+            }
+            (None, None) => {}
+        }
+
+        if let Some(container) = &self.container {
+            arguments.extend(["-c".to_string(), shell_quote(container)]);
+        }
+
+        arguments.extend([
+            "--".to_string(),
+            "sh".to_string(),
+            "-c".to_string(),
+            shell_quote(command),
+        ]);
+
+        arguments.join(" ")
+    }
+}
+
+impl Display for KubectlDefinition {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.pod, &self.selector) {
+            (Some(pod), _) => write!(formatter, "pod `{pod}`")?,
+            (None, Some(selector)) => write!(formatter, "first running pod matching `{selector}`")?,
+            (None, None) => formatter.write_str("pod")?,
+        }
+
+        if let Some(container) = &self.container {
+            write!(formatter, ", container `{container}`")?;
+        }
+
+        if let Some(namespace) = &self.namespace {
+            write!(formatter, ", in namespace `{namespace}`")?;
+        }
+
+        if let Some(context) = &self.context {
+            write!(formatter, ", with context `{context}`")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use std::io::{stdout, Write};
+
+use crossterm::cursor::{MoveToColumn, MoveUp};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{enable_raw_mode, Clear, ClearType};
+use crossterm::{event, queue, terminal};
+use unicode_width::UnicodeWidthStr;
+
+use crate::command_selection::RawModeGuard;
+use crate::error::{Error, Result};
+use crate::interrupt;
+
+struct LineEditor<'a> {
+    prompt: &'a str,
+    line: Vec<char>,
+    /// Position in `line` that characters are inserted at.
+    cursor: usize,
+    /// Lines that can be recalled with the up and down keys, most recent first.
+    history: &'a [String],
+    /// Which history line is shown, and the line that was being typed before it.
+    history_position: Option<(usize, Vec<char>)>,
+    /// Row the cursor was left on, counting from the row the prompt starts on.
+    cursor_row: u16,
+}
+
+impl LineEditor<'_> {
+    fn width_of(characters: &[char]) -> usize {
+        characters.iter().collect::<String>().width()
+    }
+
+    /// Reprint the prompt and line, replacing what was printed before, and put the cursor back.
+    fn render(&mut self) -> Result<()> {
+        let mut stdout = stdout();
+        // Some terminals report no width (e.g. when recorded with `script`), so nothing is wrapped
+        let width = match terminal::size()?.0 {
+            0 => usize::MAX,
+            width => usize::from(width),
+        };
+
+        if self.cursor_row > 0 {
+            queue!(stdout, MoveUp(self.cursor_row))?;
+        }
+
+        queue!(
+            stdout,
+            MoveToColumn(0),
+            Clear(ClearType::FromCursorDown),
+            Print(self.prompt),
+            Print(self.line.iter().collect::<String>()),
+        )?;
+
+        let end = self.prompt.width() + Self::width_of(&self.line);
+        // The terminal doesn't move to the next row until something is printed after the last
+        // column, so it's done here to know where the cursor is
+        if end > 0 && end.is_multiple_of(width) {
+            queue!(stdout, Print("\r\n"))?;
+        }
+
+        let before_cursor = self.prompt.width() + Self::width_of(&self.line[..self.cursor]);
+        let (end_row, cursor_row) = (end / width, before_cursor / width);
+        if end_row > cursor_row {
+            queue!(stdout, MoveUp((end_row - cursor_row) as u16))?;
+        }
+        queue!(stdout, MoveToColumn((before_cursor % width) as u16))?;
+        stdout.flush()?;
+
+        self.cursor_row = cursor_row as u16;
+
+        Ok(())
+    }
+
+    /// Show the next older (`older` is true) or newer line from the history. Going newer than the
+    /// most recent line puts back what was being typed.
+    fn recall_history(&mut self, older: bool) {
+        self.history_position = match (self.history_position.take(), older) {
+            (None, true) if !self.history.is_empty() => Some((0, self.line.clone())),
+            (None, _) => None,
+            (Some((position, typed)), true) => {
+                Some(((position + 1).min(self.history.len() - 1), typed))
+            }
+            (Some((0, typed)), false) => {
+                self.line = typed;
+                None
+            }
+            (Some((position, typed)), false) => Some((position - 1, typed)),
+        };
+
+        if let Some((position, _)) = &self.history_position {
+            self.line = self.history[*position].chars().collect();
+        }
+        self.cursor = self.line.len();
+    }
+
+    /// Start of the word before the cursor, skipping any whitespace before it.
+    fn previous_word_start(&self) -> usize {
+        let mut position = self.cursor;
+        while position > 0 && self.line[position - 1].is_whitespace() {
+            position -= 1;
+        }
+        while position > 0 && !self.line[position - 1].is_whitespace() {
+            position -= 1;
+        }
+        position
+    }
+
+    /// End of the word after the cursor, skipping any whitespace before it.
+    fn next_word_end(&self) -> usize {
+        let mut position = self.cursor;
+        while position < self.line.len() && self.line[position].is_whitespace() {
+            position += 1;
+        }
+        while position < self.line.len() && !self.line[position].is_whitespace() {
+            position += 1;
+        }
+        position
+    }
+
+    /// Change the line for the key. Returns `true` when the line has been entered.
+    fn handle_key(&mut self, key_event: KeyEvent) -> Result<bool> {
+        let is_control = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let is_alt = key_event.modifiers.contains(KeyModifiers::ALT);
+        // Checked before `<ctrl-b>` and `<ctrl-f>` are changed to the left and right keys
+        let is_word_move = (is_control && matches!(key_event.code, KeyCode::Left | KeyCode::Right))
+            || (is_alt && matches!(key_event.code, KeyCode::Char('b' | 'f')));
+
+        // The emacs style keys do the same as their usual equivalents
+        let code = match key_event.code {
+            KeyCode::Char('a') if is_control => KeyCode::Home,
+            KeyCode::Char('e') if is_control => KeyCode::End,
+            KeyCode::Char('b') if is_control => KeyCode::Left,
+            KeyCode::Char('f') if is_control => KeyCode::Right,
+            KeyCode::Char('p') if is_control => KeyCode::Up,
+            KeyCode::Char('n') if is_control => KeyCode::Down,
+            KeyCode::Char('h') if is_control => KeyCode::Backspace,
+            // A newline, e.g. from enter being pressed before raw mode was enabled
+            KeyCode::Char('j') if is_control => KeyCode::Enter,
+            KeyCode::Char('d') if is_control && !self.line.is_empty() => KeyCode::Delete,
+            code => code,
+        };
+
+        if !matches!(code, KeyCode::Up | KeyCode::Down) {
+            self.history_position = None;
+        }
+
+        match code {
+            KeyCode::Enter => return Ok(true),
+            KeyCode::Char('c') if is_control => interrupt::exit_interrupted(),
+            KeyCode::Char('d') if is_control => return Err(Error::Cancelled),
+            KeyCode::Char('w') if is_control => {
+                let start = self.previous_word_start();
+                self.line.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            KeyCode::Char('u') if is_control => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Char('k') if is_control => self.line.truncate(self.cursor),
+            KeyCode::Char(c) if !is_control && !is_alt => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Left | KeyCode::Char('b') if is_word_move => {
+                self.cursor = self.previous_word_start();
+            }
+            KeyCode::Right | KeyCode::Char('f') if is_word_move => {
+                self.cursor = self.next_word_end();
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.line.len(),
+            KeyCode::Up => self.recall_history(true),
+            KeyCode::Down => self.recall_history(false),
+            _ => {}
+        }
+
+        Ok(false)
+    }
+}
+
+/// Read a line typed after the prompt, starting with `initial`, with the usual line editing keys (e.g. `<ctrl-a>` and
+/// `<ctrl-e>` to move to the start and end, `<ctrl-w>` to delete a word), and the up and down keys
+/// to recall lines from the history. As with other prompts, if stdin is piped to `rc` the line is
+/// read from the terminal instead.
+///
+/// `<ctrl-d>` on an empty line cancels, and `<ctrl-c>` exits as it would without raw mode.
+pub fn read_line(prompt: &str, initial: &str, history: &[String]) -> Result<String> {
+    enable_raw_mode()?;
+    let _raw_mode_guard = RawModeGuard;
+
+    let mut line_editor = LineEditor {
+        prompt,
+        line: initial.chars().collect(),
+        cursor: initial.chars().count(),
+        history,
+        history_position: None,
+        cursor_row: 0,
+    };
+
+    loop {
+        line_editor.render()?;
+
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+
+        // Windows also reports keys being released
+        if key_event.kind != KeyEventKind::Release && line_editor.handle_key(key_event)? {
+            break;
+        }
+    }
+
+    // Leave the cursor after the whole line
+    line_editor.cursor = line_editor.line.len();
+    line_editor.render()?;
+    let mut stdout = stdout();
+    queue!(stdout, Print("\r\n"))?;
+    stdout.flush()?;
+
+    Ok(line_editor.line.into_iter().collect())
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+
+use log::warn;
+
+use crate::command_definitions::CommandExecutionTemplate;
+use crate::error::{Error, Result};
+use crate::history;
+use crate::interpolation::ArgumentTemplate;
+use crate::interrupt::RunningChild;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Used for `{id}` in the log path if the command has no ID.
+const DEFAULT_LOG_ID: &str = "rc";
+const DEFAULT_PAGER: &str = "less";
+
+/// Format the timestamp (in UTC) for use in a file name, e.g. `2024-05-01_13-45-10`.
+fn format_date(timestamp: u64) -> String {
+    let days = timestamp / SECONDS_PER_DAY;
+    let seconds = timestamp % SECONDS_PER_DAY;
+
+    // Convert days since the epoch to a date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let shifted_days = days + 719_468;
+    let era = shifted_days / 146_097;
+    let day_of_era = shifted_days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Fill in the command's `log_output` path. As well as the command's parameters, it can use
+/// `{id}` and `{__date}` (the time the command was run).
+pub fn get_log_path(
+    log_output: &str,
+    execution_context: &CommandExecutionTemplate,
+) -> Result<String> {
+    let mut context: HashMap<String, String> = execution_context
+        .template_context
+        .clone()
+        .unwrap_or_default();
+    context.insert(
+        "id".to_string(),
+        execution_context
+            .id
+            .clone()
+            .unwrap_or_else(|| DEFAULT_LOG_ID.to_string()),
+    );
+    context.insert("__date".to_string(), format_date(history::now()));
+
+    let log_path = ArgumentTemplate::parse(log_output)?.render(&context)?;
+
+    Ok(shellexpand::tilde(&log_path).to_string())
+}
+
+/// Create the log file, and its directory if needed.
+pub fn create_log_file(log_path: &str) -> Result<File> {
+    if let Some(log_directory) = Path::new(log_path).parent() {
+        fs::create_dir_all(log_directory)
+            .map_err(|e| Error::io_error("log".to_string(), log_path.to_string(), e))?;
+    }
+
+    File::create(log_path).map_err(|e| Error::io_error("log".to_string(), log_path.to_string(), e))
+}
+
+/// Open the most recent log of the command in the user's pager (`$PAGER`).
+pub fn open_latest_log(history_path: &str, command_key: &str) -> Result<()> {
+    let log_path = history::read_entries(history_path)?
+        .into_iter()
+        .rev()
+        .filter(|entry| entry.command_key == command_key)
+        .filter_map(|entry| entry.log_path)
+        .find(|log_path| Path::new(log_path).exists())
+        .ok_or_else(|| Error::NoLogs(command_key.to_string()))?;
+
+    let pager = env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+
+    // The pager may include arguments, e.g. `less -R`
+    let mut pager_parts = pager.split_whitespace();
+    let program = pager_parts.next().unwrap_or(DEFAULT_PAGER);
+
+    let mut pager_process = Command::new(program)
+        .args(pager_parts)
+        .arg(&log_path)
+        .spawn()?;
+    let _running_pager = RunningChild::new(&pager_process);
+    let status = pager_process.wait()?;
+
+    if !status.success() {
+        warn!("Pager `{pager}` exited with {status}");
+    }
+
+    Ok(())
+}
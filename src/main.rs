@@ -1,52 +1,115 @@
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::io::{stdout, Write};
-use std::process::{Command, ExitCode};
+use std::fs;
+use std::io::{stdin, stdout, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::time::Instant;
 
-use clap::Parser;
 use crossterm::terminal::{disable_raw_mode, Clear, ClearType};
-use crossterm::{cursor, queue, terminal};
+use crossterm::{cursor, queue};
 use itertools::Itertools;
 use log::{debug, info, warn};
 
-use crate::cli_args::Args;
+use crate::chaining::Chain;
+use crate::cli_args::{
+    Args, BookmarkCommand, HistoryCommand, OutputFormat, ScheduleCommand, SubCommand,
+};
 use command_selection::CommandChoice::{Index, Quit, Rerun};
 
-use crate::command_definitions::{CommandDefinition, CommandExecutionTemplate};
-use crate::command_selection::{CommandChoice, RunChoice};
+use crate::command_definitions::{
+    find_close_command_id, get_command_index, CommandDefinition, CommandExecutionTemplate,
+    ConfirmPolicy,
+};
+use crate::command_selection::{CommandAction, CommandChoice, CommandReloader, RunChoice};
 use crate::error::{Error, Result};
-use crate::interpolation::{get_template_context, get_templates, get_tokens, interpolate_command};
+use crate::execution::PreparedCommand;
+use crate::history::HistoryEntry;
+use crate::interpolation::{
+    get_template_context, parse_parameter_values, quote_forwarded_arguments, PreparedTemplate,
+    ResolvedParameters, FORWARDED_ARGUMENTS_KEY,
+};
+use crate::migration::ParameterSets;
+use crate::parallel::EachRun;
+use crate::settings::{ProfileDefinition, Settings};
+use crate::sources::SourceDefinition;
+use crate::theme::Theme;
 
+mod bookmarks;
+mod chaining;
 mod cli_args;
+mod clipboard;
 mod command_definitions;
 mod command_selection;
+mod config_editing;
+mod container;
+mod diff;
+mod docs;
+mod duration;
 mod error;
 mod execution;
+mod explain;
+mod export;
 mod file_handling;
+mod history;
+mod import;
+mod init;
 mod interpolation;
+mod interrupt;
+mod jobs;
+mod kubectl;
+mod line_editor;
+mod logs;
+mod migration;
+mod new_command;
+mod parallel;
+mod parameter_form;
+mod remembered_values;
+mod requirements;
+mod schedule;
+mod search;
+mod secrets;
+mod settings;
+mod shell_integration;
+mod sources;
+mod stats;
+mod terminal;
+mod theme;
+mod tmux;
+mod validation;
+mod version;
+mod watch;
+mod which;
 
 const DEFAULT_CONFIG_PATH: &str = "~/.rust-cuts/commands.yml";
 const DEFAULT_LAST_COMMAND_PATH: &str = "~/.rust-cuts/last_command.yml";
+const DEFAULT_SETTINGS_PATH: &str = "~/.rust-cuts/settings.yml";
+const DEFAULT_HISTORY_PATH: &str = "~/.rust-cuts/history.jsonl";
+const DEFAULT_BOOKMARKS_PATH: &str = "~/.rust-cuts/bookmarks.yml";
+const DEFAULT_SOURCES_PATH: &str = "~/.rust-cuts/sources";
+const DEFAULT_JOBS_PATH: &str = "~/.rust-cuts/jobs.yml";
+const DEFAULT_JOB_LOGS_PATH: &str = "~/.rust-cuts/jobs";
+const DEFAULT_SCHEDULES_PATH: &str = "~/.rust-cuts/schedules.yml";
+const DEFAULT_REMEMBERED_VALUES_PATH: &str = "~/.rust-cuts/values.yml";
 const LAST_COMMAND_OPTION: char = 'r';
 
 const DEFAULT_SHELL: &str = "/bin/bash";
+const DEFAULT_EDITOR: &str = "vi";
 
-fn get_config_path(config_path_arg: &Option<String>) -> String {
-    let config_path = match config_path_arg {
-        Some(last_command_path) => last_command_path,
-        None => DEFAULT_CONFIG_PATH,
-    };
-
-    shellexpand::tilde(config_path).to_string()
+/// Whether `rc` can prompt, which it can't if its output isn't to a terminal (e.g. in CI or
+/// cron) or `--non-interactive` is given.
+fn is_interactive(args: &Args) -> bool {
+    !args.non_interactive && stdout().is_terminal()
 }
 
-fn get_last_command_path(last_command_path_arg: &Option<String>) -> String {
-    let last_command_path = match last_command_path_arg {
-        Some(last_command_path) => last_command_path,
-        None => DEFAULT_LAST_COMMAND_PATH,
+/// Get the path given as an argument, or the default path if not given, with `~` expanded.
+fn get_path(path_arg: &Option<String>, default_path: &str) -> String {
+    let path = match path_arg {
+        Some(path) => path,
+        None => default_path,
     };
 
-    shellexpand::tilde(last_command_path).to_string()
+    shellexpand::tilde(path).to_string()
 }
 
 /// Parameters should not be prompted for if:
@@ -85,36 +148,656 @@ fn get_should_prompt_for_parameters(
     }
 }
 
+/// Parameters that don't have a default (or previous value), in order.
+fn get_missing_parameters(
+    tokens: &HashSet<String>,
+    defaults: &Option<HashMap<String, String>>,
+) -> Vec<String> {
+    tokens
+        .iter()
+        .filter(|token| {
+            !defaults
+                .as_ref()
+                .is_some_and(|defaults| defaults.contains_key(*token))
+        })
+        .sorted()
+        .cloned()
+        .collect()
+}
+
 fn get_rerun_request_is_valid(args: &Args) -> Result<bool> {
     if !args.rerun_last_command {
         return Ok(false);
     }
 
-    if args.command_index.is_some() {
-        // Can't rerun if an index is specified, doesn't make sense
+    if args.command.is_some()
+        || matches!(
+            args.subcommand,
+            Some(SubCommand::Run { .. } | SubCommand::Bookmark { .. })
+        )
+    {
+        // Can't rerun if an index (or another command) is specified, doesn't make sense
         return Err(Error::RerunWithIndex);
     }
 
     Ok(true)
 }
 
-fn execute() -> Result<()> {
-    let args = cli_args::Args::parse();
+/// Whether the command's confirmation policy, and whether `--force` was given, let it run without
+/// being confirmed. Dangerous commands are checked for separately, as they're always confirmed.
+fn get_should_skip_confirmation(confirm_policy: ConfirmPolicy, force: bool) -> bool {
+    match confirm_policy {
+        ConfirmPolicy::Always => false,
+        ConfirmPolicy::Never => true,
+        ConfirmPolicy::Default => force,
+    }
+}
+
+/// Open the file in the user's editor (`$VISUAL`, then `$EDITOR`), waiting for it to close.
+fn open_in_editor(path: &str) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+
+    // The editor may include arguments, e.g. `code --wait`
+    let mut editor_parts = editor.split_whitespace();
+    let program = editor_parts.next().unwrap_or(DEFAULT_EDITOR);
+
+    let mut editor_process = Command::new(program).args(editor_parts).arg(path).spawn()?;
+    let _running_editor = interrupt::RunningChild::new(&editor_process);
+    let status = editor_process.wait()?;
+
+    if !status.success() {
+        warn!("Editor `{editor}` exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// Edit the text in the editor, in a temporary file with the extension, and return what it was
+/// changed to.
+fn edit_text(text: &str, extension: &str) -> Result<String> {
+    let edit_path = env::temp_dir().join(format!("rc-edit-{}.{extension}", std::process::id()));
+    let edit_path_string = edit_path.to_string_lossy().to_string();
+    let io_error = |e| Error::io_error("command".to_string(), edit_path_string.clone(), e);
+
+    fs::write(&edit_path, text).map_err(io_error)?;
+    open_in_editor(&edit_path_string)?;
+    let edited_text = fs::read_to_string(&edit_path).map_err(io_error);
+    let _ = fs::remove_file(&edit_path);
+
+    edited_text
+}
+
+/// Edit the command's definition in the editor, then write it back in place of the original. The
+/// definition is edited as it is written in the config, so `extends` and `parameter_sets` aren't
+/// resolved.
+fn edit_command(
+    config_path: &str,
+    config_command_definitions: &[CommandDefinition],
+    parameter_sets: &ParameterSets,
+    index: usize,
+) -> Result<()> {
+    let yaml_error = |action: &str, e| {
+        Error::yaml_error(
+            action.to_string(),
+            "command".to_string(),
+            config_path.to_string(),
+            e,
+        )
+    };
+
+    let original_yaml = serde_yaml::to_string(&config_command_definitions[index])
+        .map_err(|e| yaml_error("writing", e))?;
+    let edited_yaml = edit_text(&original_yaml, "yml")?;
+
+    if edited_yaml == original_yaml {
+        println!("No changes were made.");
+        return Ok(());
+    }
+
+    let edited_definition: CommandDefinition = serde_yaml::from_str(&edited_yaml).map_err(|e| {
+        file_handling::yaml_error_in_source("reading", "command", config_path, &edited_yaml, e)
+    })?;
+
+    config_editing::replace_command(
+        config_path,
+        config_command_definitions,
+        parameter_sets,
+        index,
+        &edited_definition,
+    )?;
+    println!("Updated `{}`.", edited_definition.history_key());
+
+    Ok(())
+}
+
+/// Print the previous runs of the command, most recent first.
+fn print_command_history(
+    history_path: &str,
+    execution_context: &CommandExecutionTemplate,
+) -> Result<()> {
+    let command_key = execution_context.history_key();
+
+    let entries: Vec<HistoryEntry> = history::read_entries(history_path)?
+        .into_iter()
+        .filter(|entry| entry.command_key == command_key)
+        .collect();
+
+    if entries.is_empty() {
+        println!("`{execution_context}` has not been run before.");
+        return Ok(());
+    }
+
+    println!("History of `{execution_context}`:");
+    for entry in entries.iter().rev() {
+        let exit_code = entry
+            .exit_code
+            .map_or("?".to_string(), |exit_code| exit_code.to_string());
+
+        println!(
+            "  {:>16}   [{exit_code:>3}]   {}",
+            history::format_age(entry.timestamp),
+            entry.resolved_command
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the working directory exists, offering to create it if `can_prompt`.
+fn check_working_directory(working_directory: &str, can_prompt: bool) -> Result<()> {
+    let expanded_working_directory = shellexpand::tilde(working_directory);
+    let path = Path::new(expanded_working_directory.as_ref());
+
+    if path.is_dir() {
+        return Ok(());
+    }
+
+    if !path.exists()
+        && can_prompt
+        && command_selection::confirm_create_directory(working_directory)?
+    {
+        return fs::create_dir_all(path).map_err(|e| {
+            Error::io_error(
+                "working directory".to_string(),
+                working_directory.to_string(),
+                e,
+            )
+        });
+    }
+
+    Err(Error::WorkingDirectoryNotFound(
+        working_directory.to_string(),
+    ))
+}
+
+/// Add, list or remove schedules. Scheduled commands are run by their ID, with the config and
+/// profile they were scheduled with.
+fn run_schedule_command(
+    command: &ScheduleCommand,
+    command_definitions: &[CommandDefinition],
+    config_path: &str,
+    args: &Args,
+) -> Result<()> {
+    let schedules_path = shellexpand::tilde(DEFAULT_SCHEDULES_PATH).to_string();
+
+    match command {
+        ScheduleCommand::Add {
+            command,
+            cron,
+            param,
+            backend,
+            name,
+            force,
+        } => {
+            let command_definition =
+                &command_definitions[get_command_index(command_definitions, command)?];
+            let id = command_definition
+                .id
+                .clone()
+                .ok_or_else(|| Error::ScheduleWithoutId(command_definition.history_key()))?;
+            // Checked now, rather than when the schedule runs
+            parse_parameter_values(param)?;
+
+            // The schedule doesn't run in this directory, so the paths must be absolute
+            let config_path = fs::canonicalize(config_path).map_or_else(
+                |_| config_path.to_string(),
+                |path| path.to_string_lossy().to_string(),
+            );
+            let mut arguments = vec![
+                env::current_exe()?.to_string_lossy().to_string(),
+                "--non-interactive".to_string(),
+                "--config-path".to_string(),
+                config_path,
+            ];
+            if let Some(profile) = &args.profile {
+                arguments.extend(["--profile".to_string(), profile.clone()]);
+            }
+            for param in param {
+                arguments.extend(["--param".to_string(), param.clone()]);
+            }
+            arguments.push(id.clone());
+
+            let name = name
+                .clone()
+                .unwrap_or_else(|| schedule::get_default_name(&id));
+            let schedule = schedule::Schedule {
+                cron: cron.clone(),
+                backend: backend.unwrap_or_else(schedule::ScheduleBackend::for_platform),
+                arguments,
+            };
+            schedule::add_schedule(&schedules_path, &name, schedule, *force)?;
+            println!("Scheduled `{id}` as `{name}`, remove it with `rc schedule remove {name}`.");
+        }
+        ScheduleCommand::List => {
+            schedule::print_schedules(&schedule::read_schedules(&schedules_path)?);
+        }
+        ScheduleCommand::Remove { name } => {
+            schedule::remove_schedule(&schedules_path, name)?;
+            println!("Removed schedule `{name}`.");
+        }
+    }
+
+    Ok(())
+}
+
+fn execute() -> Result<ExitCode> {
+    let args = cli_args::Args::parse_with_env();
+    interrupt::install_handler()?;
+
+    // Only the command is printed to stdout, so the command list and prompts go to the terminal
+    let captured_stdout = if args.print_only {
+        shell_integration::redirect_stdout_to_terminal()?
+    } else {
+        None
+    };
+
+    theme::apply_color_policy(args.color);
 
     let shell = env::var("SHELL").unwrap_or_else(|_| DEFAULT_SHELL.to_string());
 
-    let config_path = get_config_path(&args.config_path);
+    let settings_path = get_path(&args.settings_path, DEFAULT_SETTINGS_PATH);
+    let settings = file_handling::get_settings(&settings_path)?;
+    let profile = settings.get_profile(args.profile.as_deref())?;
+    if let Some(secrets_definition) = &settings.secrets {
+        secrets::use_secrets_definition(secrets_definition.clone());
+    }
+
+    if args.plain_select || settings.plain_select.unwrap_or(false) {
+        command_selection::use_plain_prompts();
+    } else if is_interactive(&args) {
+        command_selection::check_raw_mode_is_available();
+    }
+
+    // Paths given as arguments take priority over the profile's
+    let config_path = get_path(
+        &args
+            .config_path
+            .clone()
+            .or_else(|| profile.and_then(ProfileDefinition::config_path)),
+        DEFAULT_CONFIG_PATH,
+    );
     debug!("Config path: `{}`", config_path);
+    let theme = match &settings.theme {
+        Some(theme_definition) => theme_definition.resolve()?,
+        None => Theme::default(),
+    };
+
+    let sources_path = shellexpand::tilde(DEFAULT_SOURCES_PATH).to_string();
+    let jobs_path = shellexpand::tilde(DEFAULT_JOBS_PATH).to_string();
+
+    match &args.subcommand {
+        Some(SubCommand::Validate) => {
+            return run_validate(&config_path, settings.strict.unwrap_or(true))
+        }
+        Some(SubCommand::ShellInit { shell, key }) => {
+            print!("{}", shell_integration::get_init_script(*shell, key)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::Migrate) => {
+            match migration::migrate_config(&config_path)? {
+                Some((version, backup_path)) => println!(
+                    "Updated `{config_path}` from version {version} to {} of the command definitions, the original was copied to `{backup_path}`.",
+                    migration::CONFIG_VERSION
+                ),
+                None => println!("`{config_path}` is already up to date."),
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::RestoreConfig) => {
+            let backup_path = config_editing::restore_config(&config_path)?;
+            println!("Restored `{config_path}` from `{backup_path}`, restore again to undo this.");
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::Init { template }) => {
+            init::initialize_config(&config_path, *template, is_interactive(&args), &theme)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::Import { from, path }) => {
+            // Commands can be imported to start a new config
+            let existing_command_defs = if Path::new(&config_path).exists() {
+                file_handling::get_command_definitions(&config_path)?
+            } else {
+                Vec::new()
+            };
+
+            import::import_commands(&config_path, &existing_command_defs, *from, path, &theme)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::New {
+            command,
+            from_clipboard,
+            from_last_shell,
+        }) => {
+            let text = if *from_clipboard {
+                Some(clipboard::paste_from_clipboard()?)
+            } else if *from_last_shell {
+                Some(import::get_last_shell_command(&shell)?)
+            } else {
+                (!command.is_empty()).then(|| command.join(" "))
+            };
+
+            new_command::create_command(&config_path, text.as_deref())?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::Jobs) => {
+            jobs::print_jobs(&jobs::read_jobs(&jobs_path)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::Attach { job }) => {
+            jobs::attach_to_job(&jobs_path, job)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::Kill { job }) => {
+            let (number, job) = jobs::kill_job(&jobs_path, job)?;
+            println!("Stopped job {number}, `{}`.", job.command_key);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::Sync) => {
+            let all_synced = sources::sync_sources(
+                settings.sources.as_deref().unwrap_or_default(),
+                &sources_path,
+            )?;
+
+            return Ok(if all_synced {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            });
+        }
+        Some(
+            SubCommand::Run { .. }
+            | SubCommand::Export { .. }
+            | SubCommand::Logs { .. }
+            | SubCommand::Stats { .. }
+            | SubCommand::History { .. }
+            | SubCommand::Bookmark { .. }
+            | SubCommand::Schedule { .. }
+            | SubCommand::Which { .. }
+            | SubCommand::Explain { .. }
+            | SubCommand::Docs { .. }
+            | SubCommand::Search { .. }
+            | SubCommand::Edit { .. }
+            | SubCommand::Remove { .. },
+        )
+        | None => {}
+    }
+
+    // On the first run, offer to create the command definitions, then go on to choose from them
+    if args.subcommand.is_none() && is_interactive(&args) && !Path::new(&config_path).exists() {
+        if !command_selection::confirm_create_config(&config_path)? {
+            return Err(Error::Cancelled);
+        }
+        init::initialize_config(&config_path, None, true, &theme)?;
+    }
 
     let parsed_command_defs = file_handling::get_command_definitions(&config_path)?;
+    if settings.strict.unwrap_or(false) {
+        file_handling::check_unknown_fields(&config_path)?;
+    }
+    for warning in file_handling::get_config_warnings(&config_path)? {
+        eprintln!("warning: {warning}");
+    }
+
+    if let Some(SubCommand::Export { format }) = args.subcommand {
+        print!(
+            "{}",
+            export::export_commands(&parsed_command_defs, format, &config_path)?
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Only local commands can be edited, so these are handled before adding source commands
+    match &args.subcommand {
+        Some(SubCommand::Edit { command }) => {
+            let command_index = get_command_index(&parsed_command_defs, command)?;
+            let (config_command_defs, parameter_sets) =
+                file_handling::get_config_command_definitions(&config_path)?;
+            let config_index = config_editing::get_config_index(
+                &config_command_defs,
+                &parameter_sets,
+                command_index,
+            )?;
+            edit_command(
+                &config_path,
+                &config_command_defs,
+                &parameter_sets,
+                config_index,
+            )?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(SubCommand::Remove { command, force }) => {
+            let command_index = get_command_index(&parsed_command_defs, command)?;
+            let description = parsed_command_defs[command_index].history_key();
+            let (config_command_defs, parameter_sets) =
+                file_handling::get_config_command_definitions(&config_path)?;
+            let config_index = config_editing::get_config_index(
+                &config_command_defs,
+                &parameter_sets,
+                command_index,
+            )?;
 
-    let last_command_path = get_last_command_path(&args.last_command_path);
+            if !force && !command_selection::confirm_remove_command(&description)? {
+                return Err(Error::Cancelled);
+            }
+
+            config_editing::remove_command(&config_path, config_index)?;
+            println!("Removed `{description}`.");
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+
+    let sources = settings.sources.as_deref().unwrap_or_default();
+    let mut parsed_command_defs =
+        sources::add_source_commands(parsed_command_defs, sources, &sources_path)?;
+
+    if args.verbose {
+        for warning in validation::find_parameter_warnings(&parsed_command_defs) {
+            eprintln!("warning: {warning}");
+        }
+    }
+
+    if let Some(SubCommand::Which { command }) = &args.subcommand {
+        which::print_command_info(
+            &parsed_command_defs,
+            get_command_index(&parsed_command_defs, command)?,
+            &config_path,
+            args.profile.as_deref().zip(profile),
+            settings.sources.as_deref().unwrap_or_default(),
+            &sources_path,
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(SubCommand::Explain { command }) = &args.subcommand {
+        explain::print_explanation(
+            &parsed_command_defs[get_command_index(&parsed_command_defs, command)?],
+            args.profile.as_deref().zip(profile),
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(SubCommand::Search { query, format }) = &args.subcommand {
+        let results = search::search_commands(&parsed_command_defs, query);
+        match format {
+            OutputFormat::Human => search::print_search_results(&results, query),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        }
+
+        return Ok(if results.is_empty() {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    if let Some(SubCommand::Docs { output }) = &args.subcommand {
+        let docs = docs::render_docs(&parsed_command_defs)?;
+        match output {
+            Some(output) => {
+                fs::write(output, docs)
+                    .map_err(|e| Error::io_error("docs".to_string(), output.clone(), e))?;
+                println!("Wrote the documentation to `{output}`.");
+            }
+            None => print!("{docs}"),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(SubCommand::Schedule { command }) = &args.subcommand {
+        run_schedule_command(command, &parsed_command_defs, &config_path, &args)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let history_path = get_path(
+        &args
+            .history_path
+            .clone()
+            .or_else(|| profile.and_then(ProfileDefinition::history_path)),
+        DEFAULT_HISTORY_PATH,
+    );
+
+    let history_settings = settings.history.clone().unwrap_or_default();
+    let max_history_age = history_settings
+        .max_age
+        .as_deref()
+        .map(duration::parse_duration)
+        .transpose()?;
+
+    if let Some(SubCommand::History {
+        command: HistoryCommand::Prune { older_than, keep },
+    }) = &args.subcommand
+    {
+        let (max_age, max_entries) = if older_than.is_some() || keep.is_some() {
+            let max_age = older_than
+                .as_deref()
+                .map(duration::parse_duration)
+                .transpose()?;
+            (max_age, *keep)
+        } else {
+            (max_history_age, history_settings.max_entries)
+        };
+
+        if max_age.is_none() && max_entries.is_none() {
+            return Err(Error::NoHistoryLimits);
+        }
+
+        let (removed_count, kept_count) =
+            history::prune_entries(&history_path, max_age, max_entries)?;
+        println!(
+            "Removed {removed_count} of the {} history entries.",
+            removed_count + kept_count
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if max_history_age.is_some() || history_settings.max_entries.is_some() {
+        history::prune_entries(&history_path, max_history_age, history_settings.max_entries)?;
+    }
+
+    if let Some(SubCommand::Stats { format }) = &args.subcommand {
+        let command_stats = stats::get_command_stats(&history::read_entries(&history_path)?);
+        match format {
+            OutputFormat::Human => stats::print_command_stats(&command_stats),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&command_stats)?),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(SubCommand::Logs { command }) = &args.subcommand {
+        let command_index = get_command_index(&parsed_command_defs, command)?;
+        logs::open_latest_log(
+            &history_path,
+            &parsed_command_defs[command_index].history_key(),
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let last_command_path = get_path(
+        &args
+            .last_command_path
+            .clone()
+            .or_else(|| profile.and_then(ProfileDefinition::last_command_path)),
+        DEFAULT_LAST_COMMAND_PATH,
+    );
 
     let last_command = file_handling::get_last_command(&last_command_path)?;
 
-    let rerun_option = if get_rerun_request_is_valid(&args)? {
+    let bookmarks_path = get_path(
+        &profile.and_then(ProfileDefinition::bookmarks_path),
+        DEFAULT_BOOKMARKS_PATH,
+    );
+
+    let bookmark = match &args.subcommand {
+        Some(SubCommand::Bookmark { command }) => match command {
+            BookmarkCommand::Add { name, force } => {
+                let last_command = last_command.as_ref().ok_or(Error::NoLastCommand)?;
+                bookmarks::add_bookmark(&bookmarks_path, name, last_command, *force)?;
+                println!(
+                    "Saved the last command as `{name}`, run it with `rc bookmark run {name}`."
+                );
+                return Ok(ExitCode::SUCCESS);
+            }
+            BookmarkCommand::Run { name } => Some(bookmarks::get_bookmark(&bookmarks_path, name)?),
+            BookmarkCommand::List => {
+                bookmarks::print_bookmarks(&bookmarks::read_bookmarks(&bookmarks_path)?);
+                return Ok(ExitCode::SUCCESS);
+            }
+            BookmarkCommand::Remove { name } => {
+                bookmarks::remove_bookmark(&bookmarks_path, name)?;
+                println!("Removed bookmark `{name}`.");
+                return Ok(ExitCode::SUCCESS);
+            }
+        },
+        _ => None,
+    };
+
+    let is_rerun_requested = get_rerun_request_is_valid(&args)?;
+
+    // A bookmark, or a command's most recent run, is run the same way as rerunning the last
+    // command
+    let rerun_option = if let Some(bookmark) = bookmark {
+        Some((Rerun(Box::new(bookmark)), CommandAction::Run))
+    } else if let Some(command) = args.command.as_ref().filter(|_| args.last) {
+        let command_definition =
+            &parsed_command_defs[get_command_index(&parsed_command_defs, command)?];
+        command_definition.check_available()?;
+
+        let mut execution_context =
+            CommandExecutionTemplate::from_command_definition(command_definition);
+        let entries = history::read_entries(&history_path)?;
+        let last_entry = history::get_last_entry(&entries, &execution_context.history_key())
+            .ok_or_else(|| Error::NoPreviousRun(command.clone()))?;
+        execution_context
+            .template_context
+            .clone_from(&last_entry.template_context);
+
+        Some((Rerun(Box::new(execution_context)), CommandAction::Run))
+    } else if is_rerun_requested {
         if let Some(last_command) = &last_command {
-            Some(Rerun(last_command.clone()))
+            Some((Rerun(Box::new(last_command.clone())), CommandAction::Run))
         } else {
             warn!("Rerun last command was specified, but there is no previous command!");
             None
@@ -123,95 +806,471 @@ fn execute() -> Result<()> {
         None
     };
 
-    let selected_option = match rerun_option {
-        None => get_selected_option(&args, &parsed_command_defs, last_command.as_ref())?,
-        Some(rerun_option) => rerun_option,
+    // An ad-hoc command is run the same way as the others, so it's added to the end of the list
+    let ad_hoc_index = if let Some(SubCommand::Run { command }) = &args.subcommand {
+        parsed_command_defs.push(CommandDefinition {
+            command: command.clone(),
+            ..CommandDefinition::default()
+        });
+        Some(parsed_command_defs.len() - 1)
+    } else {
+        None
+    };
+    let command_given = args.command.is_some() || ad_hoc_index.is_some();
+
+    let is_interactive = is_interactive(&args);
+    if !is_interactive && rerun_option.is_none() && !command_given {
+        return Err(Error::CommandRequired);
+    }
+
+    // Parameters are filled in with a form if the command was selected from the command list
+    let use_parameter_form =
+        rerun_option.is_none() && !command_given && !command_selection::is_using_plain_prompts();
+
+    let (selected_option, action) = match (rerun_option, ad_hoc_index) {
+        (Some(rerun_option), _) => rerun_option,
+        (None, Some(ad_hoc_index)) => (Index(ad_hoc_index), CommandAction::Run),
+        (None, None) => get_selected_option(
+            &args,
+            &mut parsed_command_defs,
+            // The command list is only shown if no command was given
+            args.command
+                .is_none()
+                .then(|| get_command_reloader(&config_path, sources, &sources_path))
+                .flatten(),
+            last_command.as_ref(),
+            &settings,
+            theme,
+            &history_path,
+        )?,
     };
 
     let mut execution_context: CommandExecutionTemplate;
-    let defaults: Option<HashMap<String, String>>;
+    let mut parameters: ResolvedParameters;
+    // Where the values entered for the parameters are remembered, if they are
+    let mut remembered_values_path = None;
+
+    let is_rerun = matches!(selected_option, Rerun(_));
 
     match selected_option {
         Index(selected_index) => {
             let selected_command = &parsed_command_defs[selected_index];
-            defaults = interpolation::build_default_lookup(&selected_command.parameters);
             execution_context = CommandExecutionTemplate::from_command_definition(selected_command);
+
+            let remember_values = selected_command
+                .remember_values
+                .or(settings.remember_values)
+                .unwrap_or(false);
+            let remembered_values = if remember_values && is_interactive {
+                let values_path = shellexpand::tilde(DEFAULT_REMEMBERED_VALUES_PATH).to_string();
+                let remembered_values = remembered_values::get_remembered_values(
+                    &values_path,
+                    &execution_context.history_key(),
+                )?;
+                remembered_values_path = Some(values_path);
+                remembered_values
+            } else {
+                None
+            };
+            parameters = interpolation::resolve_parameters(
+                selected_command.parameters.as_deref(),
+                &remembered_values,
+            );
         }
         Rerun(last_command) => {
-            execution_context = last_command.clone();
-            defaults = last_command.template_context.clone();
+            // The command's definition (if it still has one) for its defaults, separate from the
+            // values it was last run with
+            let definition = last_command.id.as_ref().and_then(|id| {
+                parsed_command_defs
+                    .iter()
+                    .find(|command_definition| command_definition.id.as_ref() == Some(id))
+            });
+            parameters = interpolation::resolve_parameters(
+                definition.and_then(|definition| definition.parameters.as_deref()),
+                &last_command.template_context,
+            );
+            execution_context = *last_command;
         }
         Quit => {
-            let mut stdout = stdout();
-            queue!(stdout, Clear(ClearType::All),)?;
-            return Ok(());
+            // The plain list is left, as it's not drawn over
+            if !command_selection::is_using_plain_prompts() {
+                let mut stdout = stdout();
+                queue!(stdout, Clear(ClearType::All),)?;
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+
+    if let Some(profile) = profile {
+        profile.apply_environment(&mut execution_context.environment);
+    }
+
+    match action {
+        CommandAction::Edit => {
+            open_in_editor(&config_path)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        CommandAction::History => {
+            print_command_history(&history_path, &execution_context)?;
+            return Ok(ExitCode::SUCCESS);
         }
+        CommandAction::Run
+        | CommandAction::DryRun
+        | CommandAction::NewTerminal
+        | CommandAction::Copy => {}
     }
 
-    let templates = get_templates(&execution_context.command)?;
+    // Commands chained with `and_then` and `or_else` share the command's parameters
+    let chain = Chain::build(&execution_context, &parsed_command_defs, profile)?;
+    chain.add_chained_parameters(&mut parameters);
 
-    let tokens = get_tokens(&templates);
+    // What the parameters start as when prompted for, and the defaults from the config, which
+    // computed defaults are filled in from
+    let defaults = interpolation::get_initial_values(&parameters);
+    let config_defaults = interpolation::get_config_defaults(&parameters);
 
-    let mut args_as_string: String;
+    let dry_run = args.dry_run || action == CommandAction::DryRun;
+    let new_terminal = args.new_terminal || action == CommandAction::NewTerminal;
+
+    // Checked before prompting for parameters, so they aren't filled in for nothing
+    if !dry_run && action != CommandAction::Copy {
+        requirements::check_requirements(&execution_context)?;
+        for chained in chain.chained_commands() {
+            requirements::check_requirements(&chained.execution_context)?;
+        }
+    }
+
+    // Parsed once, however many times the parameters are changed
+    let template = PreparedTemplate::parse(
+        &execution_context.command,
+        execution_context.working_directory.as_ref(),
+    )?;
+    let mut tokens = chaining::get_command_tokens(&execution_context)?;
+    tokens.extend(chain.chained_tokens()?);
+
+    // Parameters given with `--each` aren't prompted for
+    let each_contexts = parallel::parse_each(&args.each)?;
+    for (name, _) in each_contexts.first().into_iter().flatten() {
+        tokens.remove(name);
+    }
+
+    // Nor are those given with `--param`
+    let parameter_values = parse_parameter_values(&args.param)?;
+    for name in parameter_values.keys() {
+        tokens.remove(name);
+    }
+
+    // Prompted for in the order they're used, so a default computed from other parameters can use
+    // the values given before it
+    let prompt_order = template.order_tokens(&tokens);
+
+    if !args.arguments.is_empty() && !template.forwards_arguments() {
+        return Err(Error::ArgumentsNotForwarded(
+            execution_context.history_key(),
+        ));
+    }
+
+    let mut runs: Vec<EachRun>;
+    // The runs from before the parameters were changed, to show what changed
+    let mut previous_runs: Option<Vec<EachRun>> = None;
 
     let mut should_prompt_for_parameters =
-        get_should_prompt_for_parameters(&tokens, &defaults, last_command.is_some());
+        get_should_prompt_for_parameters(&tokens, &defaults, is_rerun);
+
+    // Offered as well as the defaults when filling in the parameters
+    let recent_values = if is_interactive && !tokens.is_empty() {
+        history::get_recent_values(
+            &history::read_entries(&history_path)?,
+            &execution_context.history_key(),
+        )
+    } else {
+        HashMap::new()
+    };
 
     let mut template_context = None;
 
     loop {
         if tokens.is_empty() {
             template_context = None;
+        } else if !is_interactive {
+            // Parameters can't be prompted for, so they must all have defaults
+            let missing_parameters = get_missing_parameters(&tokens, &defaults);
+            if !missing_parameters.is_empty() {
+                return Err(Error::MissingParameters(missing_parameters.join("`, `")));
+            }
+
+            template_context.clone_from(&defaults);
         } else if should_prompt_for_parameters {
             // On first loop, the defaults should be the normal defaults
             // Once template_context is set, that should be used as the default
-            template_context = get_template_context(
-                &tokens,
-                if template_context.is_none() {
-                    &defaults
-                } else {
-                    &template_context
-                },
-            )?;
+            let prompt_defaults = if template_context.is_none() {
+                &defaults
+            } else {
+                &template_context
+            };
+
+            if use_parameter_form {
+                let Some(form_values) = parameter_form::prompt_for_parameter_values(
+                    &execution_context.to_string(),
+                    &prompt_order,
+                    prompt_defaults,
+                    &parameter_values,
+                    &recent_values,
+                    &theme,
+                )?
+                else {
+                    // Form was cancelled
+                    return Err(Error::Cancelled);
+                };
+
+                template_context = Some(form_values);
+            } else {
+                template_context = get_template_context(
+                    &prompt_order,
+                    prompt_defaults,
+                    &parameter_values,
+                    &recent_values,
+                )?;
+            }
         } else {
             template_context.clone_from(&defaults);
         };
 
-        args_as_string = interpolate_command(&template_context, &templates)?.join(" ");
+        if !parameter_values.is_empty() {
+            template_context
+                .get_or_insert_with(HashMap::new)
+                .extend(parameter_values.clone());
+        }
 
-        print_command_and_environment(&execution_context, &args_as_string);
-        if args.dry_run {
-            println!("Dry run is specified, exiting without executing.");
-            return Ok(());
+        // Recorded with the parameters, so a rerun passes on the same arguments
+        if !args.arguments.is_empty() {
+            template_context.get_or_insert_with(HashMap::new).insert(
+                FORWARDED_ARGUMENTS_KEY.to_string(),
+                quote_forwarded_arguments(&args.arguments),
+            );
         }
-        if args.force {
-            // Force run - break loop
+
+        runs = parallel::resolve_runs(
+            &execution_context,
+            &template,
+            &template_context,
+            &config_defaults,
+            &each_contexts,
+        )?;
+
+        // Defaults computed from other parameters, e.g. `{env}-cluster`, that weren't filled in
+        // when prompting. Each run fills them in with its own `--each` values.
+        interpolation::resolve_computed_defaults(&mut template_context, &config_defaults);
+
+        execution_context
+            .template_context
+            .clone_from(&template_context);
+
+        if args.edit_command {
+            // `--each` can't be used with this, so there is only one run
+            runs[0].prepared.check_editable()?;
+            let edited_command = edit_text(&format!("{}\n", runs[0].prepared.command), "sh")?;
+            let edited_command = edited_command.trim();
+            if edited_command.is_empty() {
+                return Err(Error::Cancelled);
+            }
+            edited_command.clone_into(&mut runs[0].prepared.command);
+        }
+
+        if args.print_only {
+            let commands = runs
+                .iter()
+                .map(|run| shell_integration::get_standalone_command(&run.prepared))
+                .collect::<Result<Vec<String>>>()?
+                .join("\n");
+            shell_integration::print_output(captured_stdout, &commands)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        if action == CommandAction::Copy {
+            let args_as_string = runs
+                .iter()
+                .map(|run| run.prepared.command.as_str())
+                .join("\n");
+            clipboard::copy_to_clipboard(&args_as_string)?;
+            println!("Copied to clipboard:\n{args_as_string}");
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        if args.output == OutputFormat::Human {
+            print_command_and_environment(
+                &execution_context,
+                &runs,
+                previous_runs.as_deref(),
+                &shell,
+            );
+            chain.print_chained_commands(&template_context)?;
+        }
+        if dry_run {
+            match args.output {
+                OutputFormat::Human => {
+                    println!("Dry run is specified, exiting without executing.");
+                }
+                OutputFormat::Json => print_dry_run(&runs, &shell)?,
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let can_prompt =
+            is_interactive && args.output == OutputFormat::Human && stdin().is_terminal();
+        for working_directory in runs
+            .iter()
+            .filter_map(|run| run.prepared.working_directory.as_deref())
+            .unique()
+        {
+            check_working_directory(working_directory, can_prompt)?;
+        }
+        let dangerous_id = execution_context.dangerous_id();
+
+        if !is_interactive {
+            // Nothing can be confirmed, so dangerous commands can't be run
+            if let Some(dangerous_id) = dangerous_id {
+                return Err(Error::DangerousNonInteractive(dangerous_id.to_string()));
+            }
+            break;
+        }
+
+        if dangerous_id.is_none()
+            && get_should_skip_confirmation(
+                execution_context.confirm.unwrap_or_default(),
+                args.force,
+            )
+        {
+            // Confirmation not needed - break loop
             break;
         }
 
-        match command_selection::confirm_command_should_run(!tokens.is_empty())? {
+        match command_selection::confirm_command_should_run(!tokens.is_empty(), dangerous_id)? {
             RunChoice::Yes => {
                 // Break loop, do run
-                execution_context
-                    .template_context
-                    .clone_from(&template_context);
                 break;
             }
             RunChoice::No => {
                 // Exit if command was not confirmed and was not forced
-                return Ok(());
+                return Err(Error::Cancelled);
             }
             RunChoice::ChangeParams => {
                 // Continue the loop, params are re-requested if missing_defaults becomes true
                 should_prompt_for_parameters = true;
+                previous_runs = Some(runs.clone());
             }
         }
     }
 
-    let mut command = Command::new(shell);
-    if let Some(working_directory) = &execution_context.working_directory {
-        let expanded_working_dir = shellexpand::tilde(working_directory.as_str());
-        command.current_dir(expanded_working_dir.as_ref());
+    if let (Some(values_path), Some(template_context)) =
+        (&remembered_values_path, &template_context)
+    {
+        // Only the values entered, not those given with `--param` or `--each`
+        remembered_values::remember_values(
+            values_path,
+            &execution_context.history_key(),
+            template_context
+                .iter()
+                .filter(|(name, _)| tokens.contains(*name))
+                .map(|(name, value)| (name.clone(), value.clone())),
+        )?;
+    }
+
+    // Ad-hoc commands can be saved once they've been run, unless they were interrupted
+    let offer_to_save = || match ad_hoc_index {
+        Some(ad_hoc_index) if is_interactive && !interrupt::was_interrupted() => {
+            new_command::save_command(&config_path, parsed_command_defs[ad_hoc_index].clone())
+                .map(|_| ())
+        }
+        _ => Ok(()),
+    };
+
+    let tmux_target = args.tmux.or(execution_context.tmux);
+
+    if chain.has_next()
+        && (!each_contexts.is_empty()
+            || new_terminal
+            || tmux_target.is_some()
+            || args.background
+            || !args.watch.is_empty())
+    {
+        warn!("Chained commands are only run when the command is run once in this terminal");
+    }
+
+    if !each_contexts.is_empty() {
+        if new_terminal || args.tmux.is_some() {
+            warn!("Commands run with --each are run here, not in a new terminal or tmux");
+        }
+
+        if !args.skip_command_save {
+            file_handling::write_last_command(&last_command_path, &execution_context)?;
+        }
+
+        let exit_code = run_each(&args, &shell, &execution_context, runs, &history_path)?;
+        offer_to_save()?;
+        return Ok(exit_code);
+    }
+
+    if args.background {
+        if !args.skip_command_save {
+            file_handling::write_last_command(&last_command_path, &execution_context)?;
+        }
+
+        let log_path = execution_context
+            .log_output
+            .as_ref()
+            .map(|log_output| logs::get_log_path(log_output, &execution_context))
+            .transpose()?;
+        let (number, job) = jobs::start_job(
+            &jobs_path,
+            &shellexpand::tilde(DEFAULT_JOB_LOGS_PATH),
+            &shell,
+            &runs[0].prepared,
+            &execution_context.history_key(),
+            log_path,
+        )?;
+        println!(
+            "Started job {number} (pid {}), writing its output to `{}`. Follow it with `rc attach {number}`.",
+            job.pid, job.log_path
+        );
+
+        // The command's exit code isn't known, as it's still running
+        let mut history_entry =
+            HistoryEntry::new(&execution_context, &runs[0].prepared.command, None);
+        history_entry.log_path = Some(job.log_path);
+        if let Err(e) = history::append_entry(&history_path, &history_entry) {
+            warn!("Could not record command in history: {e}");
+        }
+
+        offer_to_save()?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if new_terminal || tmux_target.is_some() {
+        if !args.skip_command_save {
+            file_handling::write_last_command(&last_command_path, &execution_context)?;
+        }
+
+        match tmux_target {
+            Some(tmux_target) => tmux::run_in_tmux(
+                tmux_target,
+                execution_context.id.as_deref(),
+                &shell,
+                &runs[0].prepared,
+            )?,
+            None => {
+                terminal::run_in_terminal(&shell, &runs[0].prepared, settings.terminal.as_ref())?
+            }
+        }
+
+        // The command's exit code isn't known, as it runs elsewhere
+        let history_entry = HistoryEntry::new(&execution_context, &runs[0].prepared.command, None);
+        if let Err(e) = history::append_entry(&history_path, &history_entry) {
+            warn!("Could not record command in history: {e}");
+        }
+
+        offer_to_save()?;
+        return Ok(ExitCode::SUCCESS);
     }
 
     if args.skip_command_save {
@@ -220,37 +1279,368 @@ fn execute() -> Result<()> {
         file_handling::write_last_command(&last_command_path, &execution_context)?;
     }
 
-    // Give `-i` argument to start an interactive shell,
-    // which will make it read ~/.rc or ~/.profile or whatever file
-    command.args(vec!["-i", "-c", args_as_string.as_str()]);
+    if !args.watch.is_empty() {
+        watch::watch_and_run(&args.watch, args.clear, || {
+            let exit_code = run_command(
+                &args,
+                &shell,
+                &execution_context,
+                &runs[0].prepared,
+                &history_path,
+            )?;
+
+            if exit_code != 0 && args.output == OutputFormat::Human {
+                println!("Command exited with code {exit_code}");
+            }
+
+            Ok(())
+        })?;
+
+        return Ok(if interrupt::was_interrupted() {
+            ExitCode::from(interrupt::INTERRUPTED_EXIT_CODE)
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    let exit_code = run_chain(
+        &args,
+        &shell,
+        &execution_context,
+        &runs[0].prepared,
+        &chain,
+        &history_path,
+    )?;
+    offer_to_save()?;
+
+    Ok(ExitCode::from(exit_code))
+}
+
+/// The command's stdin: the file given with `--stdin-from`, otherwise `rc`'s own stdin.
+fn get_command_stdin(stdin_from: Option<&str>) -> Result<Stdio> {
+    let Some(stdin_from) = stdin_from else {
+        return Ok(Stdio::inherit());
+    };
+
+    let path = shellexpand::tilde(stdin_from);
+    let file = fs::File::open(path.as_ref())
+        .map_err(|e| Error::io_error("stdin".to_string(), stdin_from.to_string(), e))?;
+
+    Ok(Stdio::from(file))
+}
+
+/// Execute the command in this terminal and record it in the history, returning the code `rc`
+/// should exit with.
+fn run_command(
+    args: &Args,
+    shell: &str,
+    execution_context: &CommandExecutionTemplate,
+    prepared: &PreparedCommand,
+    history_path: &str,
+) -> Result<u8> {
+    // Start an interactive shell, which will make it read ~/.rc or ~/.profile or whatever file
+    let command = prepared.shell_command(shell, true)?;
+
+    let log_path = execution_context
+        .log_output
+        .as_deref()
+        .map(|log_output| logs::get_log_path(log_output, execution_context))
+        .transpose()?;
+
+    let stdin = get_command_stdin(args.stdin_from.as_deref())?;
+    let warn_after = execution_context
+        .warn_after
+        .as_deref()
+        .map(duration::parse_duration)
+        .transpose()?;
+
+    let start = Instant::now();
+    let exit_status = match args.output {
+        OutputFormat::Human => match &log_path {
+            Some(log_path) => {
+                execution::execute_command_logged(command, stdin, logs::create_log_file(log_path)?)?
+            }
+            None => execution::execute_command(command, stdin)?,
+        },
+        OutputFormat::Json => {
+            let output = execution::execute_command_captured(command, stdin)?;
+
+            if let Some(log_path) = &log_path {
+                let mut log_file = logs::create_log_file(log_path)?;
+                log_file
+                    .write_all(&output.stdout)
+                    .and_then(|()| log_file.write_all(&output.stderr))
+                    .map_err(|e| Error::io_error("log".to_string(), log_path.clone(), e))?;
+            }
+
+            let execution_result =
+                execution::ExecutionResult::new(prepared, &output, start.elapsed());
+            println!("{}", serde_json::to_string_pretty(&execution_result)?);
+
+            output.status
+        }
+    };
+    let duration = start.elapsed();
+
+    // Even if the command handled ctrl-c itself, it didn't finish
+    let is_interrupted = interrupt::was_interrupted();
+    let exit_code = if is_interrupted {
+        i32::from(interrupt::INTERRUPTED_EXIT_CODE)
+    } else {
+        execution::get_exit_code(&exit_status)
+    };
+
+    let mut history_entry =
+        HistoryEntry::new(execution_context, &prepared.command, Some(exit_code));
+    history_entry.log_path = log_path;
+    history_entry.duration_ms = Some(duration::as_millis(duration));
+    if let Err(e) = history::append_entry(history_path, &history_entry) {
+        warn!("Could not record command in history: {e}");
+    }
+
+    if args.output == OutputFormat::Human {
+        println!("\nFinished in {}", duration::format_duration(duration));
 
-    execution::execute_command(command, execution_context.environment)
+        if warn_after.is_some_and(|warn_after| duration > warn_after) {
+            println!(
+                "This took longer than expected (`warn_after: {}`)",
+                execution_context.warn_after.as_deref().unwrap_or_default()
+            );
+        }
+    }
+
+    if !exit_status.success() {
+        info!("Command exited with code {exit_code}");
+    }
+
+    if is_interrupted {
+        return Ok(interrupt::INTERRUPTED_EXIT_CODE);
+    }
+
+    Ok(execution::map_exit_code(
+        exit_code,
+        execution_context.success_codes.as_ref(),
+    ))
+}
+
+/// Run the command, then the command chained after it for whether it succeeded, if there is one,
+/// with the same parameter values. A failing command's exit code is kept even if a command
+/// chained with `or_else` succeeds, so a rolled back chain still fails.
+fn run_chain(
+    args: &Args,
+    shell: &str,
+    execution_context: &CommandExecutionTemplate,
+    prepared: &PreparedCommand,
+    chain: &Chain,
+    history_path: &str,
+) -> Result<u8> {
+    let exit_code = run_command(args, shell, execution_context, prepared, history_path)?;
+
+    let next = if exit_code == 0 {
+        &chain.and_then
+    } else {
+        &chain.or_else
+    };
+    let Some(next) = next.as_ref().filter(|_| !interrupt::was_interrupted()) else {
+        return Ok(exit_code);
+    };
+
+    let mut next_context = next.execution_context.clone();
+    next_context
+        .template_context
+        .clone_from(&execution_context.template_context);
+    let next_prepared = next.prepare(&next_context.template_context)?;
+
+    if args.output == OutputFormat::Human {
+        let when = if exit_code == 0 {
+            "succeeded"
+        } else {
+            "failed"
+        };
+        println!(
+            "\nThe command {when}, executing command:\n{}",
+            next_prepared.command
+        );
+    }
+
+    let next_exit_code = run_chain(
+        args,
+        shell,
+        &next_context,
+        &next_prepared,
+        next,
+        history_path,
+    )?;
+
+    Ok(if exit_code == 0 {
+        next_exit_code
+    } else {
+        exit_code
+    })
+}
+
+/// Execute each run of the command, recording them all in the history. Exits with the first
+/// failing run's exit code.
+fn run_each(
+    args: &Args,
+    shell: &str,
+    execution_context: &CommandExecutionTemplate,
+    runs: Vec<EachRun>,
+    history_path: &str,
+) -> Result<ExitCode> {
+    let results = parallel::execute_runs(
+        shell,
+        runs,
+        args.parallel,
+        args.output == OutputFormat::Json,
+    )?;
+
+    match args.output {
+        OutputFormat::Human => {
+            parallel::print_summary(&results, execution_context.success_codes.as_ref());
+        }
+        OutputFormat::Json => {
+            let execution_results: Vec<execution::ExecutionResult> = results
+                .iter()
+                .filter_map(|result| {
+                    result.output.as_ref().map(|output| {
+                        execution::ExecutionResult::new(
+                            &result.run.prepared,
+                            output,
+                            result.duration,
+                        )
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&execution_results)?);
+        }
+    }
+
+    for result in &results {
+        let mut run_context = execution_context.clone();
+        run_context
+            .template_context
+            .clone_from(&result.run.template_context);
+
+        let mut history_entry = HistoryEntry::new(
+            &run_context,
+            &result.run.prepared.command,
+            Some(result.exit_code),
+        );
+        history_entry.duration_ms = Some(duration::as_millis(result.duration));
+        if let Err(e) = history::append_entry(history_path, &history_entry) {
+            warn!("Could not record command in history: {e}");
+        }
+    }
+
+    let exit_code = results
+        .iter()
+        .map(|result| {
+            execution::map_exit_code(result.exit_code, execution_context.success_codes.as_ref())
+        })
+        .find(|exit_code| *exit_code != 0)
+        .unwrap_or(0);
+
+    if interrupt::was_interrupted() {
+        return Ok(ExitCode::from(interrupt::INTERRUPTED_EXIT_CODE));
+    }
+
+    Ok(ExitCode::from(exit_code))
+}
+
+fn run_validate(config_path: &str, strict: bool) -> Result<ExitCode> {
+    let issues = validation::validate_config(config_path, strict)?;
+    validation::print_validation_issues(config_path, &issues);
+
+    if issues
+        .iter()
+        .any(|issue| issue.severity == validation::Severity::Error)
+    {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Reloads the commands from the config and synced sources, so the command list updates while
+/// they are being edited. The list still works without this, e.g. if the system can't watch more
+/// files.
+fn get_command_reloader<'a>(
+    config_path: &'a str,
+    sources: &'a [SourceDefinition],
+    sources_path: &'a str,
+) -> Option<CommandReloader<'a>> {
+    let paths = std::iter::once(PathBuf::from(config_path))
+        .chain(
+            sources
+                .iter()
+                .map(|source| source.commands_path(sources_path))
+                .filter(|commands_path| commands_path.exists()),
+        )
+        .collect_vec();
+
+    CommandReloader::new(&paths, move || {
+        sources::add_source_commands(
+            file_handling::get_command_definitions(config_path)?,
+            sources,
+            sources_path,
+        )
+    })
+    .inspect_err(|e| warn!("Commands won't be reloaded when they change: {e}"))
+    .ok()
 }
 
 fn get_selected_option(
     args: &Args,
-    parsed_command_defs: &[CommandDefinition],
+    parsed_command_defs: &mut Vec<CommandDefinition>,
+    command_reloader: Option<CommandReloader>,
     last_command: Option<&CommandExecutionTemplate>,
-) -> Result<CommandChoice> {
-    if let Some(index) = args.command_index {
-        if index >= parsed_command_defs.len() {
-            return Err(Error::Misc(format!("Command index out of range: {index}!")));
-        }
+    settings: &Settings,
+    theme: Theme,
+    history_path: &str,
+) -> Result<(CommandChoice, CommandAction)> {
+    if let Some(command) = &args.command {
+        let command_index = match get_command_index(parsed_command_defs, command) {
+            Err(Error::CommandNotFound { id, suggestions }) if is_interactive(args) => {
+                // Only offered if it's the only command the ID looks like a typo of
+                let Some(close_id) = find_close_command_id(parsed_command_defs, &id) else {
+                    return Err(Error::CommandNotFound { id, suggestions });
+                };
 
-        Ok(Index(index))
+                if !command_selection::confirm_suggested_command(&id, &close_id)? {
+                    return Err(Error::Cancelled);
+                }
+
+                get_command_index(parsed_command_defs, &close_id)?
+            }
+            result => result?,
+        };
+        parsed_command_defs[command_index].check_available()?;
+
+        Ok((Index(command_index), CommandAction::Run))
+    } else if command_selection::is_using_plain_prompts() {
+        command_selection::prompt_for_command_choice_plain(parsed_command_defs, last_command)
     } else {
-        let selected_option =
-            command_selection::prompt_for_command_choice(parsed_command_defs, last_command)?;
+        let frecency_scores = history::get_frecency_scores(&history::read_entries(history_path)?);
+
+        let selected_option = command_selection::prompt_for_command_choice(
+            parsed_command_defs,
+            command_reloader.as_ref(),
+            last_command,
+            &frecency_scores,
+            settings.sort.unwrap_or_default(),
+            theme,
+        )?;
 
         let mut stdout = stdout();
 
-        let (_, height) = terminal::size()?; // todo: Do this before so we only scroll to end of commands not to bottom of terminal
+        let (_, height) = crossterm::terminal::size()?; // todo: Do this before so we only scroll to end of commands not to bottom of terminal
 
         queue!(
             stdout,
             cursor::MoveToColumn(0),
             cursor::MoveToRow(height),
-            terminal::Clear(ClearType::CurrentLine)
+            Clear(ClearType::CurrentLine)
         )?;
         disable_raw_mode()?;
         stdout.flush()?;
@@ -258,11 +1648,54 @@ fn get_selected_option(
     }
 }
 
+/// Print the commands that will be run, and where and how. If the parameters were changed, what
+/// changed in each command is highlighted.
 fn print_command_and_environment(
     execution_context: &CommandExecutionTemplate,
-    args_as_string: &String,
+    runs: &[EachRun],
+    previous_runs: Option<&[EachRun]>,
+    shell: &str,
 ) {
-    println!("Executing command:\n{args_as_string}");
+    // Runs are in the same order each time, as the `--each` values don't change
+    let get_previous_run = |i: usize| previous_runs.and_then(|previous_runs| previous_runs.get(i));
+    let display_command = |i: usize, run: &EachRun| match get_previous_run(i) {
+        Some(previous_run) if previous_run.prepared.command != run.prepared.command => {
+            diff::highlight_command_changes(&previous_run.prepared.command, &run.prepared.command)
+        }
+        _ => run.prepared.command.clone(),
+    };
+
+    if let [run] = runs {
+        println!("Executing command:\n{}", display_command(0, run));
+    } else {
+        println!("Executing commands:");
+        for (i, run) in runs.iter().enumerate() {
+            println!(
+                "[{}] {}",
+                run.label.as_deref().unwrap_or_default(),
+                display_command(i, run)
+            );
+        }
+    }
+
+    if let (Some(previous_run), Some(run)) = (get_previous_run(0), runs.first()) {
+        let changes =
+            diff::describe_parameter_changes(&previous_run.template_context, &run.template_context);
+        if changes.is_empty() {
+            println!("No parameters were changed.");
+        } else {
+            println!("Changed: {}", changes.join(", "));
+        }
+    }
+
+    // Each run can be in a different directory if the working directory uses an `--each` parameter
+    let working_directories = runs
+        .iter()
+        .map(|run| run.prepared.resolved_working_directory())
+        .unique()
+        .join(", ");
+    println!("In directory: {working_directories}");
+    println!("With shell: {shell}");
 
     if let Some(environment) = execution_context.environment.as_ref() {
         println!("With environment:");
@@ -270,16 +1703,41 @@ fn print_command_and_environment(
             println!("\t\"{key}\": \"{value}\"");
         }
     }
+
+    if let Some(container) = &execution_context.container {
+        println!("In {container}");
+    }
+
+    // The pod can use parameters, so is shown as it is for the first run
+    if let Some(kubectl) = runs.first().and_then(|run| run.prepared.kubectl.as_ref()) {
+        println!("In {kubectl}");
+    }
+}
+
+/// Print what a dry run would have run as JSON, a list if it's run for several `--each` values.
+fn print_dry_run(runs: &[EachRun], shell: &str) -> Result<()> {
+    let dry_run_results: Vec<execution::DryRunResult> = runs
+        .iter()
+        .map(|run| execution::DryRunResult::new(&run.prepared, &run.template_context, shell))
+        .collect();
+
+    let json = match dry_run_results.as_slice() {
+        [dry_run_result] => serde_json::to_string_pretty(dry_run_result)?,
+        _ => serde_json::to_string_pretty(&dry_run_results)?,
+    };
+    println!("{json}");
+
+    Ok(())
 }
 
 fn main() -> ExitCode {
     env_logger::init();
 
     match execute() {
-        Ok(()) => ExitCode::SUCCESS,
+        Ok(exit_code) => exit_code,
         Err(e) => {
             eprintln!("{e}");
-            ExitCode::FAILURE
+            ExitCode::from(e.exit_code())
         }
     }
 }
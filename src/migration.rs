@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::command_definitions::{merge_parameters, CommandDefinition, ParameterDefinition};
+use crate::config_editing;
+use crate::error::{Error, Result};
+use crate::file_handling::yaml_error_in_source;
+use crate::version;
+
+/// The latest layout of the command definitions. Version 1 is a list of commands, later versions
+/// are a mapping with the `version` and the list of `commands`.
+pub const CONFIG_VERSION: u64 = 2;
+
+/// The first lines of a new config, or one migrated from version 1.
+pub fn config_header() -> String {
+    format!("version: {CONFIG_VERSION}\ncommands:\n")
+}
+
+/// Parameters shared by commands, by the name of the set.
+pub type ParameterSets = HashMap<String, Vec<ParameterDefinition>>;
+
+#[derive(Deserialize)]
+struct VersionedConfig {
+    /// Parameters shared by commands, which list the names of the sets they use in their
+    /// `parameter_sets`.
+    #[serde(default)]
+    parameter_sets: ParameterSets,
+    commands: Vec<CommandDefinition>,
+}
+
+/// Add the parameters of the sets each command uses to its parameters. The command's own
+/// parameters replace the sets' ones with the same name, as do later sets the earlier ones'.
+pub fn add_parameter_sets(
+    mut commands: Vec<CommandDefinition>,
+    parameter_sets: &ParameterSets,
+) -> Result<Vec<CommandDefinition>> {
+    for command in &mut commands {
+        let Some(set_names) = &command.parameter_sets else {
+            continue;
+        };
+
+        let mut parameters = Vec::new();
+        for set_name in set_names {
+            let parameter_set =
+                parameter_sets
+                    .get(set_name)
+                    .ok_or_else(|| Error::UnknownParameterSet {
+                        command: command.history_key(),
+                        parameter_set: set_name.clone(),
+                    })?;
+            parameters = merge_parameters(&parameters, parameter_set);
+        }
+
+        command.parameters = Some(merge_parameters(
+            &parameters,
+            command.parameters.as_deref().unwrap_or_default(),
+        ));
+    }
+
+    Ok(commands)
+}
+
+/// Fields kept for commands that need a newer version of `rc`, to list them as needing an
+/// upgrade. The rest of the command may use fields and values that this version doesn't know.
+const FIELDS_KEPT_FOR_UPGRADE: [&str; 6] =
+    ["id", "aliases", "name", "group", "tags", "requires_version"];
+
+/// The command's `requires_version`, if this version of `rc` doesn't meet it.
+pub fn get_unsupported_requirement(command: &Value) -> Result<Option<String>> {
+    let Some(requirement) = command.get("requires_version").and_then(Value::as_str) else {
+        return Ok(None);
+    };
+
+    Ok((!version::is_supported(requirement)?).then(|| requirement.to_string()))
+}
+
+/// Reduce the commands that need a newer version of `rc` to the fields it can read, so the rest
+/// of them aren't errors. Returns whether there were any.
+fn reduce_commands_needing_upgrade(commands: &mut Value) -> Result<bool> {
+    let mut any_reduced = false;
+
+    for command in commands.as_sequence_mut().into_iter().flatten() {
+        if get_unsupported_requirement(command)?.is_none() {
+            continue;
+        }
+        let Value::Mapping(mapping) = command else {
+            continue;
+        };
+
+        let has_command_list = mapping
+            .get("command")
+            .and_then(Value::as_sequence)
+            .is_some_and(|parts| parts.iter().all(Value::is_string));
+        mapping.retain(|key, _| {
+            key.as_str().is_some_and(|key| {
+                FIELDS_KEPT_FOR_UPGRADE.contains(&key) || (key == "command" && has_command_list)
+            })
+        });
+        if !has_command_list {
+            mapping.insert("command".into(), Value::Sequence(Vec::new()));
+        }
+
+        any_reduced = true;
+    }
+
+    Ok(any_reduced)
+}
+
+/// The layout version of the config, and its list of commands.
+fn get_version_and_commands(config_path: &str, config: &Value) -> Result<(u64, Option<Value>)> {
+    match config {
+        Value::Mapping(mapping) => {
+            let version = mapping
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| Error::InvalidConfigLayout(config_path.to_string()))?;
+
+            Ok((version, mapping.get("commands").cloned()))
+        }
+        _ => Ok((1, Some(config.clone()))),
+    }
+}
+
+/// Commands from before `command` was a list, which had it as a single string.
+fn get_string_commands(commands: &Value) -> Vec<String> {
+    commands
+        .as_sequence()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter(|(_, command)| command.get("command").is_some_and(Value::is_string))
+        .map(|(i, command)| {
+            ["name", "id"]
+                .iter()
+                .find_map(|key| command.get(key).and_then(Value::as_str))
+                .map_or_else(|| i.to_string(), str::to_string)
+        })
+        .collect()
+}
+
+/// Parse the command definitions as they are written, and the parameter sets they use, in any of
+/// the layouts that can be read without migrating. Older layouts give an error explaining how to
+/// update them, rather than a parsing error.
+pub fn parse_config(
+    config_path: &str,
+    config: &str,
+) -> Result<(Vec<CommandDefinition>, ParameterSets)> {
+    let yaml_error = |e| yaml_error_in_source("reading", "config", config_path, config, e);
+
+    let mut value: Value = serde_yaml::from_str(config).map_err(yaml_error)?;
+    let (version, _) = get_version_and_commands(config_path, &value)?;
+
+    if version > CONFIG_VERSION {
+        return Err(Error::UnsupportedConfigVersion {
+            path: config_path.to_string(),
+            version,
+        });
+    }
+
+    let commands_value = if version == 1 {
+        Some(&mut value)
+    } else {
+        value.get_mut("commands")
+    };
+    let any_need_upgrade = match commands_value {
+        Some(commands_value) => reduce_commands_needing_upgrade(commands_value)?,
+        None => false,
+    };
+    let commands = get_version_and_commands(config_path, &value)?.1;
+
+    let string_commands = commands
+        .as_ref()
+        .map(get_string_commands)
+        .unwrap_or_default();
+    if !string_commands.is_empty() {
+        return Err(Error::OutdatedConfig {
+            path: config_path.to_string(),
+            message: format!(
+                "`command` must be a list, e.g. `command: [\"echo hello\"]`, but is a single string for `{}`",
+                string_commands.join("`, `")
+            ),
+        });
+    }
+
+    // The reduced commands are parsed from the YAML that was changed, which means errors don't
+    // have their location in the source
+    let (commands, parameter_sets) = match (version, any_need_upgrade) {
+        (1, false) => (
+            serde_yaml::from_str(config).map_err(yaml_error)?,
+            HashMap::new(),
+        ),
+        (1, true) => (
+            serde_yaml::from_value(value).map_err(yaml_error)?,
+            HashMap::new(),
+        ),
+        (_, false) => {
+            let versioned_config: VersionedConfig =
+                serde_yaml::from_str(config).map_err(yaml_error)?;
+            (versioned_config.commands, versioned_config.parameter_sets)
+        }
+        (_, true) => {
+            let versioned_config: VersionedConfig =
+                serde_yaml::from_value(value).map_err(yaml_error)?;
+            (versioned_config.commands, versioned_config.parameter_sets)
+        }
+    };
+
+    Ok((commands, parameter_sets))
+}
+
+/// Parse the command definitions, with the parameters of the sets they use added.
+pub fn parse_command_definitions(
+    config_path: &str,
+    config: &str,
+) -> Result<Vec<CommandDefinition>> {
+    let (commands, parameter_sets) = parse_config(config_path, config)?;
+
+    add_parameter_sets(commands, &parameter_sets)
+}
+
+/// Change `command: echo hello` to a list with the single item. Lines are changed rather than
+/// writing the parsed YAML, so comments are kept.
+fn convert_string_commands(config: &str) -> String {
+    config
+        .lines()
+        .map(|line| {
+            let key_start = line.len() - line.trim_start_matches([' ', '-']).len();
+            let Some(value) = line[key_start..].strip_prefix("command:") else {
+                return format!("{line}\n");
+            };
+
+            let value = value.trim();
+            // Lists, block scalars and anchors are left for the user to change
+            if value.is_empty() || value.starts_with(['[', '|', '>', '&', '*', '#']) {
+                return format!("{line}\n");
+            }
+
+            format!(
+                "{}command:\n{:indent$}  - {value}\n",
+                &line[..key_start],
+                "",
+                indent = key_start
+            )
+        })
+        .collect()
+}
+
+/// Put the list of commands under `commands`, after any comments at the start of the file.
+fn add_header(config: &str) -> String {
+    let lines: Vec<&str> = config.lines().collect();
+    let header_line = lines
+        .iter()
+        .position(|line| {
+            let trimmed = line.trim();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        })
+        .unwrap_or(lines.len());
+
+    let mut migrated_config: String = lines[..header_line]
+        .iter()
+        .map(|line| format!("{line}\n"))
+        .collect();
+    migrated_config.push_str(&config_header());
+    for line in &lines[header_line..] {
+        migrated_config.push_str(line);
+        migrated_config.push('\n');
+    }
+
+    migrated_config
+}
+
+/// Update the config to the latest layout, keeping a copy of the original next to it. Returns the
+/// version it was updated from and where the original was copied to, or `None` if it was already
+/// up to date.
+pub fn migrate_config(config_path: &str) -> Result<Option<(u64, String)>> {
+    let config = fs::read_to_string(config_path)
+        .map_err(|e| Error::io_error("config".to_string(), config_path.to_string(), e))?;
+    let value: Value = serde_yaml::from_str(&config)
+        .map_err(|e| yaml_error_in_source("reading", "config", config_path, &config, e))?;
+    let (version, commands) = get_version_and_commands(config_path, &value)?;
+
+    if version > CONFIG_VERSION {
+        return Err(Error::UnsupportedConfigVersion {
+            path: config_path.to_string(),
+            version,
+        });
+    }
+
+    let has_string_commands = commands
+        .as_ref()
+        .is_some_and(|commands| !get_string_commands(commands).is_empty());
+    if version == CONFIG_VERSION && !has_string_commands {
+        return Ok(None);
+    }
+
+    let mut migrated_config = convert_string_commands(&config);
+    if version == 1 {
+        migrated_config = add_header(&migrated_config);
+    }
+
+    // Nothing is written unless the migrated config can be read
+    parse_command_definitions(config_path, &migrated_config).map_err(|e| {
+        Error::ConfigEdit(format!(
+            "it could not be migrated automatically, so must be updated by hand ({e})"
+        ))
+    })?;
+
+    config_editing::write_config(config_path, &migrated_config)?;
+
+    Ok(Some((
+        version,
+        config_editing::get_backup_path(config_path),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::Path;
+
+    use super::*;
+
+    const VERSION_1_CONFIG: &str = r#"# My commands
+- id: hello
+  command: echo hello
+- id: list
+  command: ["ls", "-la"]
+"#;
+
+    fn parse(config: &str) -> Result<Vec<CommandDefinition>> {
+        parse_command_definitions("commands.yml", config)
+    }
+
+    fn temp_config_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("rc-test-{name}-{}.yml", std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn version_1_is_a_list_of_commands() {
+        let commands = parse("- id: list\n  command: [ls]\n").unwrap();
+
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].id.as_deref(), Some("list"));
+    }
+
+    #[test]
+    fn version_2_has_commands_and_parameter_sets() {
+        let commands = parse(
+            r#"version: 2
+parameter_sets:
+  cluster:
+    - name: env
+      default: dev
+    - name: region
+      default: eu
+commands:
+  - id: deploy
+    command: ["deploy", "{env}", "{region}"]
+    parameter_sets: [cluster]
+    parameters:
+      - name: region
+        default: us
+"#,
+        )
+        .unwrap();
+
+        let parameters = commands[0].parameters.as_ref().unwrap();
+        let defaults: Vec<(&str, Option<&str>)> = parameters
+            .iter()
+            .map(|parameter| (parameter.name.as_str(), parameter.default.as_deref()))
+            .collect();
+        assert_eq!(defaults, vec![("env", Some("dev")), ("region", Some("us"))]);
+    }
+
+    #[test]
+    fn unknown_parameter_set_is_an_error() {
+        let result =
+            parse("version: 2\ncommands:\n  - command: [ls]\n    parameter_sets: [nope]\n");
+
+        assert!(matches!(
+            result,
+            Err(Error::UnknownParameterSet { parameter_set, .. }) if parameter_set == "nope"
+        ));
+    }
+
+    #[test]
+    fn future_version_is_an_error() {
+        let result = parse("version: 3\ncommands:\n  - command: [ls]\n");
+
+        assert!(matches!(
+            result,
+            Err(Error::UnsupportedConfigVersion { version: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn mapping_without_a_version_is_an_error() {
+        assert!(matches!(
+            parse("commands:\n  - command: [ls]\n"),
+            Err(Error::InvalidConfigLayout(_))
+        ));
+    }
+
+    #[test]
+    fn string_command_needs_migrating() {
+        assert!(matches!(
+            parse(VERSION_1_CONFIG),
+            Err(Error::OutdatedConfig { message, .. }) if message.contains("`hello`")
+        ));
+    }
+
+    #[test]
+    fn commands_needing_a_newer_version_are_reduced() {
+        let commands = parse(
+            r#"version: 2
+commands:
+  - id: new
+    name: New
+    requires_version: ">=99.0"
+    command: ["new-tool"]
+    not_a_field_yet: true
+    parameters: "a future layout"
+  - id: old
+    requires_version: ">=0.1"
+    command: ["ls"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].id.as_deref(), Some("new"));
+        assert_eq!(commands[0].name.as_deref(), Some("New"));
+        assert_eq!(commands[0].command, vec!["new-tool"]);
+        assert!(commands[0].parameters.is_none());
+        assert!(commands[0].needs_upgrade());
+        assert!(!commands[1].needs_upgrade());
+    }
+
+    #[test]
+    fn command_needing_a_newer_version_without_a_command_list_has_an_empty_one() {
+        let commands =
+            parse("- id: new\n  requires_version: \"99\"\n  command: {run: new-tool}\n").unwrap();
+
+        assert!(commands[0].command.is_empty());
+        assert!(commands[0].needs_upgrade());
+    }
+
+    #[test]
+    fn string_commands_are_converted_to_lists() {
+        assert_eq!(
+            convert_string_commands("- id: a\n  command: echo hi # greet\n  - command: [ls]\n"),
+            "- id: a\n  command:\n    - echo hi # greet\n  - command: [ls]\n"
+        );
+    }
+
+    #[test]
+    fn header_goes_after_leading_comments() {
+        assert_eq!(
+            add_header("# My commands\n\n- command: [ls]\n"),
+            "# My commands\n\nversion: 2\ncommands:\n- command: [ls]\n"
+        );
+    }
+
+    #[test]
+    fn version_1_is_migrated_to_version_2() {
+        let config_path = temp_config_path("migrate");
+        fs::write(&config_path, VERSION_1_CONFIG).unwrap();
+
+        let result = migrate_config(&config_path);
+        let migrated_config = fs::read_to_string(&config_path).unwrap();
+        let backup = fs::read_to_string(config_editing::get_backup_path(&config_path)).unwrap();
+        let _ = fs::remove_file(&config_path);
+        let _ = fs::remove_file(config_editing::get_backup_path(&config_path));
+
+        assert_eq!(
+            result.unwrap(),
+            Some((1, config_editing::get_backup_path(&config_path)))
+        );
+        assert_eq!(
+            migrated_config,
+            "# My commands\nversion: 2\ncommands:\n- id: hello\n  command:\n    - echo hello\n- id: list\n  command: [\"ls\", \"-la\"]\n"
+        );
+        assert_eq!(backup, VERSION_1_CONFIG);
+
+        let commands = parse(&migrated_config).unwrap();
+        assert_eq!(commands[0].command, vec!["echo hello"]);
+        assert_eq!(commands[1].command, vec!["ls", "-la"]);
+    }
+
+    #[test]
+    fn latest_version_is_not_migrated() {
+        let config_path = temp_config_path("migrated");
+        fs::write(&config_path, "version: 2\ncommands:\n  - command: [ls]\n").unwrap();
+
+        let result = migrate_config(&config_path);
+        let _ = fs::remove_file(&config_path);
+
+        assert_eq!(result.unwrap(), None);
+        assert!(!Path::new(&config_editing::get_backup_path(&config_path)).exists());
+    }
+}
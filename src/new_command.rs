@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use crate::command_definitions::{self, CommandDefinition, ParameterDefinition};
+use crate::command_selection;
+use crate::config_editing;
+use crate::error::{Error, Result};
+use crate::file_handling;
+use crate::secrets::ConfigValue;
+
+/// Get the name of a placeholder like `{name}` or `{{ name }}` at the start of the text, and how
+/// long the placeholder is.
+fn parse_placeholder(text: &str) -> Option<(&str, usize)> {
+    let is_double = text.starts_with("{{");
+    let (open, close) = if is_double { ("{{", "}}") } else { ("{", "}") };
+
+    let inner = text.strip_prefix(open)?;
+    let end = inner.find(close)?;
+    let name = inner[..end].trim();
+
+    let is_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    is_name.then_some((name, open.len() + end + close.len()))
+}
+
+/// Convert text copied from elsewhere to a template. Placeholders that look like `{name}` or
+/// `{{ name }}` become parameters, and any other braces (e.g. `${HOME}` or in an `awk` program)
+/// are escaped. Returns the template, and the names of the parameters in the order they're used.
+fn template_from_text(text: &str) -> (String, Vec<String>) {
+    let mut template = String::new();
+    let mut parameter_names: Vec<String> = Vec::new();
+    let mut position = 0;
+
+    while let Some(c) = text[position..].chars().next() {
+        let placeholder = match c {
+            // `${name}` is a shell variable, not a placeholder
+            '{' if !template.ends_with('$') => parse_placeholder(&text[position..]),
+            _ => None,
+        };
+
+        match (c, placeholder) {
+            (_, Some((name, length))) => {
+                template.push_str(&format!("{{{name}}}"));
+                if !parameter_names.iter().any(|existing| existing == name) {
+                    parameter_names.push(name.to_string());
+                }
+                position += length;
+            }
+            ('{' | '}', None) => {
+                template.push('\\');
+                template.push(c);
+                position += 1;
+            }
+            _ => {
+                template.push(c);
+                position += c.len_utf8();
+            }
+        }
+    }
+
+    (template, parameter_names)
+}
+
+/// Ask for an ID for the command, then add it to the command definitions. The ID is asked for
+/// again if it can't be used. Returns `false` if no ID was given, so it wasn't saved.
+pub fn save_command(config_path: &str, command_definition: CommandDefinition) -> Result<bool> {
+    // A new config is started if there isn't one yet
+    let command_definitions = if Path::new(config_path).exists() {
+        file_handling::get_command_definitions(config_path)?
+    } else {
+        Vec::new()
+    };
+
+    while let Some(id) = command_selection::prompt_for_command_id(config_path)? {
+        let command_definition = CommandDefinition {
+            id: Some(id.clone()),
+            ..command_definition.clone()
+        };
+
+        let mut updated_definitions = command_definitions.clone();
+        updated_definitions.push(command_definition.clone());
+        if let Err(e) = command_definitions::validate_command_ids(&updated_definitions) {
+            println!("{e}");
+            continue;
+        }
+
+        config_editing::append_commands(config_path, &[command_definition])?;
+        println!("Saved, run it with `rc {id}`.");
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// Create a new command, starting from `text` (e.g. from the clipboard), which can be edited
+/// first. Placeholders in it are found and offered as parameters, with a default for each.
+pub fn create_command(config_path: &str, text: Option<&str>) -> Result<()> {
+    let text = command_selection::prompt_text("Command: ", text.unwrap_or_default().trim())?;
+    if text.is_empty() {
+        return Err(Error::Cancelled);
+    }
+
+    let (template, parameter_names) = template_from_text(&text);
+    if template != text {
+        println!("As a template: {template}");
+    }
+
+    let mut parameters = Vec::new();
+    for name in parameter_names {
+        let default = command_selection::prompt_text(
+            &format!("Default for parameter `{name}` (or leave empty for none): "),
+            "",
+        )?;
+
+        parameters.push(ParameterDefinition::new(
+            name,
+            (!default.is_empty()).then_some(ConfigValue::Plain(default)),
+        ));
+    }
+
+    let name = command_selection::prompt_text("Name (or leave empty for none): ", "")?;
+
+    let command_definition = CommandDefinition {
+        name: (!name.is_empty()).then_some(name),
+        command: vec![template],
+        parameters: (!parameters.is_empty()).then_some(parameters),
+        ..CommandDefinition::default()
+    };
+
+    if !save_command(config_path, command_definition)? {
+        return Err(Error::Cancelled);
+    }
+
+    Ok(())
+}
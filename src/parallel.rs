@@ -0,0 +1,225 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Output, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use itertools::Itertools;
+
+use crate::command_definitions::CommandExecutionTemplate;
+use crate::duration::format_duration;
+use crate::error::{Error, Result};
+use crate::execution::{self, PreparedCommand};
+use crate::interpolation::{self, PreparedTemplate};
+use crate::interrupt::{self, RunningChild};
+
+/// One execution of a command, with its parameters filled in.
+#[derive(Debug, Clone)]
+pub struct EachRun {
+    /// The `--each` values for this run, used to prefix its output. `None` when not using
+    /// `--each`.
+    pub label: Option<String>,
+    pub template_context: Option<HashMap<String, String>>,
+    pub prepared: PreparedCommand,
+}
+
+/// The result of one run of the command.
+pub struct EachResult {
+    pub run: EachRun,
+    pub exit_code: i32,
+    pub duration: Duration,
+    /// The captured output, if the output wasn't streamed.
+    pub output: Option<Output>,
+}
+
+/// Parse `--each` arguments like `host=web-01,web-02` into the `(name, value)`s for each run. If
+/// more than one parameter is given, every combination of their values is run.
+pub fn parse_each(each_arguments: &[String]) -> Result<Vec<Vec<(String, String)>>> {
+    if each_arguments.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let parameters = each_arguments
+        .iter()
+        .map(|each_argument| {
+            let (name, values) = each_argument
+                .split_once('=')
+                .filter(|(name, values)| !name.trim().is_empty() && !values.is_empty())
+                .ok_or_else(|| Error::InvalidEach(each_argument.clone()))?;
+
+            Ok(values
+                .split(',')
+                .map(|value| (name.trim().to_string(), value.to_string()))
+                .collect::<Vec<(String, String)>>())
+        })
+        .collect::<Result<Vec<Vec<(String, String)>>>>()?;
+
+    Ok(parameters.into_iter().multi_cartesian_product().collect())
+}
+
+/// Prepare the command for each set of `--each` values, or just once with the template context
+/// if there are none. Defaults computed from other parameters are filled in for each run, as they
+/// can use the `--each` values.
+pub fn resolve_runs(
+    execution_context: &CommandExecutionTemplate,
+    template: &PreparedTemplate,
+    template_context: &Option<HashMap<String, String>>,
+    defaults: &Option<HashMap<String, String>>,
+    each_contexts: &[Vec<(String, String)>],
+) -> Result<Vec<EachRun>> {
+    if each_contexts.is_empty() {
+        let mut run_context = template_context.clone();
+        interpolation::resolve_computed_defaults(&mut run_context, defaults);
+
+        return Ok(vec![EachRun {
+            label: None,
+            prepared: execution::prepare_execution(execution_context, template, &run_context)?,
+            template_context: run_context,
+        }]);
+    }
+
+    each_contexts
+        .iter()
+        .map(|each_context| {
+            let mut run_context = template_context.clone().unwrap_or_default();
+            run_context.extend(each_context.clone());
+            let mut run_context = Some(run_context);
+            interpolation::resolve_computed_defaults(&mut run_context, defaults);
+
+            Ok(EachRun {
+                label: Some(each_context.iter().map(|(_, value)| value).join(",")),
+                prepared: execution::prepare_execution(execution_context, template, &run_context)?,
+                template_context: run_context,
+            })
+        })
+        .collect()
+}
+
+/// Print each line read from `reader`, prefixed with the label.
+fn print_prefixed_lines(reader: impl Read, label: &str, to_stderr: bool) {
+    for line in BufReader::new(reader)
+        .lines()
+        .map_while(std::result::Result::ok)
+    {
+        // Lock while printing, so lines from different runs don't get mixed up
+        if to_stderr {
+            let _ = writeln!(std::io::stderr().lock(), "[{label}] {line}");
+        } else {
+            let _ = writeln!(std::io::stdout().lock(), "[{label}] {line}");
+        }
+    }
+}
+
+fn execute_run(shell: &str, run: &EachRun, capture_output: bool) -> Result<(i32, Option<Output>)> {
+    // Not an interactive shell, as the runs would all compete for the terminal
+    let mut command = run.prepared.shell_command(shell, false)?;
+    command.stdin(Stdio::null());
+
+    interrupt::set_process_group(&mut command);
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let _running_child = RunningChild::new(&child);
+
+    if capture_output {
+        let output = child.wait_with_output()?;
+        return Ok((execution::get_exit_code(&output.status), Some(output)));
+    }
+
+    let label = run.label.clone().unwrap_or_default();
+    let stdout_reader = child.stdout.take().map(|stdout| {
+        let label = label.clone();
+        thread::spawn(move || print_prefixed_lines(stdout, &label, false))
+    });
+    let stderr_reader = child
+        .stderr
+        .take()
+        .map(|stderr| thread::spawn(move || print_prefixed_lines(stderr, &label, true)));
+
+    let exit_status = child.wait()?;
+
+    for reader in stdout_reader.into_iter().chain(stderr_reader) {
+        let _ = reader.join();
+    }
+
+    Ok((execution::get_exit_code(&exit_status), None))
+}
+
+/// Execute the runs, with at most `parallel` at once. Output is prefixed with each run's label,
+/// or captured if `capture_output` is set. Results are returned in the same order as the runs.
+pub fn execute_runs(
+    shell: &str,
+    runs: Vec<EachRun>,
+    parallel: usize,
+    capture_output: bool,
+) -> Result<Vec<EachResult>> {
+    let run_count = runs.len();
+    let queue = Mutex::new(runs.into_iter().enumerate().collect::<VecDeque<_>>());
+    let results = Mutex::new(Vec::with_capacity(run_count));
+
+    thread::scope(|scope| {
+        for _ in 0..parallel.clamp(1, run_count.max(1)) {
+            scope.spawn(|| {
+                while let Some((i, run)) = queue.lock().ok().and_then(|mut queue| queue.pop_front())
+                {
+                    // Runs that haven't started yet are skipped after ctrl-c
+                    if interrupt::was_interrupted() {
+                        break;
+                    }
+
+                    let start = Instant::now();
+                    let result = execute_run(shell, &run, capture_output)
+                        .map(|(exit_code, output)| (exit_code, output, start.elapsed()));
+
+                    if let Ok(mut results) = results.lock() {
+                        results.push((i, run, result));
+                    }
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .sorted_by_key(|(i, _, _)| *i)
+        .map(|(_, run, result)| {
+            let (exit_code, output, duration) = result?;
+            Ok(EachResult {
+                run,
+                exit_code,
+                duration,
+                output,
+            })
+        })
+        .collect()
+}
+
+/// Print the exit code of each run.
+pub fn print_summary(results: &[EachResult], success_codes: Option<&Vec<i32>>) {
+    println!("\nSummary:");
+
+    let label_width = results
+        .iter()
+        .map(|result| result.run.label.as_deref().unwrap_or_default().len())
+        .max()
+        .unwrap_or(0);
+
+    for result in results {
+        let status = if execution::map_exit_code(result.exit_code, success_codes) == 0 {
+            "ok"
+        } else {
+            "failed"
+        };
+
+        println!(
+            "  {:<label_width$}   exit code {:>3}   {:>8}   {status}",
+            result.run.label.as_deref().unwrap_or_default(),
+            result.exit_code,
+            format_duration(result.duration)
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+
+use crossterm::cursor::MoveTo;
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use crossterm::style::Color::{Red, Reset};
+use crossterm::style::{Attribute, Print, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{enable_raw_mode, Clear, ClearType};
+use crossterm::{event, queue, terminal};
+use itertools::Itertools;
+use unicode_width::UnicodeWidthStr;
+
+use crate::command_selection::{truncate_to_width, RawModeGuard};
+use crate::error::Result;
+use crate::interpolation::render_computed_default;
+use crate::theme::{self, Theme};
+
+/// Row of the first parameter field, below the header and a blank line.
+const FIRST_FIELD_ROW: u16 = 2;
+
+struct FormState<'a> {
+    names: Vec<&'a String>,
+    values: Vec<String>,
+    /// Each field's default, which may be computed from other fields, e.g. `{env}-cluster`.
+    defaults: Vec<Option<&'a String>>,
+    /// What each computed default was last filled in as, so it's only filled in again if the
+    /// field hasn't been changed since.
+    computed_values: Vec<String>,
+    /// Values of parameters that aren't in the form, which defaults can also use.
+    known_values: &'a HashMap<String, String>,
+    /// Values each field was given recently, most recent first.
+    recent_values: Vec<&'a [String]>,
+    /// Which recent value the selected field was last changed to, and what it was before that.
+    recalled: Option<(usize, String)>,
+    selected_index: usize,
+    message: Option<String>,
+}
+
+impl FormState<'_> {
+    /// Fill in defaults computed from other fields with their current values.
+    fn update_computed_values(&mut self) {
+        let mut values = self.known_values.clone();
+        values.extend(
+            self.names
+                .iter()
+                .map(|name| (*name).clone())
+                .zip(self.values.iter().cloned()),
+        );
+
+        for (i, name) in self.names.iter().enumerate() {
+            let Some(default) = self.defaults[i] else {
+                continue;
+            };
+
+            if self.values[i] != self.computed_values[i] {
+                continue;
+            }
+
+            if let Some(value) = render_computed_default(default, &values) {
+                values.insert((*name).clone(), value.clone());
+                self.values[i].clone_from(&value);
+                self.computed_values[i] = value;
+            }
+        }
+    }
+
+    /// Change the selected field to the next older (`older` is true) or newer recent value. Going
+    /// newer than the most recent value puts back what was there before.
+    fn recall_value(&mut self, older: bool) {
+        let recent_values = self.recent_values[self.selected_index];
+        let value = &mut self.values[self.selected_index];
+
+        self.recalled = match (self.recalled.take(), older) {
+            (None, true) if !recent_values.is_empty() => Some((0, value.clone())),
+            (None, _) => None,
+            (Some((position, original)), true) => {
+                Some(((position + 1).min(recent_values.len() - 1), original))
+            }
+            (Some((0, original)), false) => {
+                *value = original;
+                None
+            }
+            (Some((position, original)), false) => Some((position - 1, original)),
+        };
+
+        if let Some((position, _)) = &self.recalled {
+            value.clone_from(&recent_values[*position]);
+        }
+    }
+}
+
+/// Show a form to fill in all the parameter values at once, pre-filled with the defaults. Values
+/// used recently for each parameter can be recalled with `<ctrl-p>` and `<ctrl-n>`. Defaults
+/// computed from other parameters are kept up to date as those are changed, until they're
+/// changed themselves.
+///
+/// Returns `None` if the form was cancelled.
+pub fn prompt_for_parameter_values(
+    command: &str,
+    tokens: &[String],
+    defaults: &Option<HashMap<String, String>>,
+    known_values: &HashMap<String, String>,
+    recent_values: &HashMap<String, Vec<String>>,
+    theme: &Theme,
+) -> Result<Option<HashMap<String, String>>> {
+    let names: Vec<&String> = tokens.iter().collect();
+
+    if names.is_empty() {
+        return Ok(Some(HashMap::new()));
+    }
+
+    let defaults: Vec<Option<&String>> = names
+        .iter()
+        .map(|name| defaults.as_ref().and_then(|defaults| defaults.get(*name)))
+        .collect();
+    let values: Vec<String> = defaults
+        .iter()
+        .map(|default| default.cloned().unwrap_or_default())
+        .collect();
+
+    let recent_values = names
+        .iter()
+        .map(|name| recent_values.get(*name).map_or(&[][..], Vec::as_slice))
+        .collect();
+
+    let mut form_state = FormState {
+        names,
+        computed_values: values.clone(),
+        values,
+        defaults,
+        known_values,
+        recent_values,
+        recalled: None,
+        selected_index: 0,
+        message: None,
+    };
+
+    enable_raw_mode()?;
+    let _raw_mode_guard = RawModeGuard;
+
+    let result = loop {
+        form_state.update_computed_values();
+        print_form(command, &form_state, theme)?;
+
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+
+        let field_count = form_state.names.len();
+        form_state.message = None;
+
+        let is_control = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        if !(is_control && matches!(key_event.code, KeyCode::Char('p' | 'n'))) {
+            form_state.recalled = None;
+        }
+
+        match key_event.code {
+            KeyCode::Char('c') if is_control => {
+                break None;
+            }
+            KeyCode::Char('p') if is_control => form_state.recall_value(true),
+            KeyCode::Char('n') if is_control => form_state.recall_value(false),
+            KeyCode::Esc => break None,
+            KeyCode::Up | KeyCode::BackTab => {
+                form_state.selected_index =
+                    (form_state.selected_index + field_count - 1) % field_count;
+            }
+            KeyCode::Down | KeyCode::Tab => {
+                form_state.selected_index = (form_state.selected_index + 1) % field_count;
+            }
+            KeyCode::Enter if form_state.selected_index + 1 < field_count => {
+                form_state.selected_index += 1;
+            }
+            KeyCode::Enter => {
+                if let Some(empty_index) = form_state
+                    .values
+                    .iter()
+                    .position(|value| value.trim().is_empty())
+                {
+                    form_state.message = Some(format!(
+                        "Please give value for `{}`",
+                        form_state.names[empty_index]
+                    ));
+                    form_state.selected_index = empty_index;
+                } else {
+                    break Some(
+                        form_state
+                            .names
+                            .iter()
+                            .zip(form_state.values.iter())
+                            .map(|(name, value)| ((*name).clone(), value.trim().to_string()))
+                            .collect(),
+                    );
+                }
+            }
+            KeyCode::Backspace => {
+                form_state.values[form_state.selected_index].pop();
+            }
+            KeyCode::Char(c) => {
+                form_state.values[form_state.selected_index].push(c);
+            }
+            _ => {}
+        }
+    };
+
+    let mut stdout = stdout();
+    queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+    stdout.flush()?;
+
+    Ok(result)
+}
+
+fn print_form(command: &str, form_state: &FormState, theme: &Theme) -> Result<()> {
+    let mut stdout = stdout();
+    let (width, _) = terminal::size()?;
+
+    let header = format!("  {command}   |   <enter>: Next/Run   |   <esc>: Cancel");
+    let header = truncate_to_width(&header, width as usize);
+    let header_padding = " ".repeat((width as usize).saturating_sub(header.width()));
+
+    queue!(stdout, Clear(ClearType::All), MoveTo(0, 0),)?;
+    theme::queue_highlight(
+        &mut stdout,
+        theme.header_background,
+        theme.header_foreground,
+    )?;
+    queue!(stdout, Print(header), Print(header_padding))?;
+    theme::queue_end_highlight(&mut stdout)?;
+
+    let label_width = form_state
+        .names
+        .iter()
+        .map(|name| name.width())
+        .max()
+        .unwrap_or(0);
+
+    for (i, (name, value)) in form_state
+        .names
+        .iter()
+        .zip(form_state.values.iter())
+        .enumerate()
+    {
+        let is_selected = i == form_state.selected_index;
+        let marker = if is_selected { ">" } else { " " };
+
+        queue!(stdout, MoveTo(0, FIRST_FIELD_ROW + i as u16))?;
+
+        if is_selected {
+            queue!(stdout, SetAttribute(Attribute::Bold))?;
+        }
+
+        queue!(
+            stdout,
+            Print(format!(
+                "{marker} {}{name}: {value}",
+                " ".repeat(label_width - name.width())
+            )),
+            SetAttribute(Attribute::Reset),
+        )?;
+    }
+
+    let message_row = FIRST_FIELD_ROW + form_state.names.len() as u16 + 1;
+
+    let recent_values = form_state.recent_values[form_state.selected_index];
+    if !recent_values.is_empty() {
+        let recent_line = format!(
+            "Recent (<ctrl-p>/<ctrl-n>): {}",
+            recent_values.iter().join(", ")
+        );
+
+        queue!(
+            stdout,
+            MoveTo(0, message_row + 1),
+            SetAttribute(Attribute::Dim),
+            Print(truncate_to_width(&recent_line, width as usize)),
+            SetAttribute(Attribute::Reset),
+        )?;
+    }
+
+    if let Some(message) = &form_state.message {
+        queue!(
+            stdout,
+            MoveTo(0, message_row),
+            SetForegroundColor(Red),
+            Print(message),
+            SetForegroundColor(Reset),
+        )?;
+    }
+
+    // Put the cursor at the end of the value being edited
+    let selected_value = &form_state.values[form_state.selected_index];
+    let cursor_column = 2 + label_width + 2 + selected_value.width();
+
+    queue!(
+        stdout,
+        MoveTo(
+            cursor_column as u16,
+            FIRST_FIELD_ROW + form_state.selected_index as u16
+        )
+    )?;
+
+    stdout.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::file_handling;
+
+/// The values last entered for each command's parameters, by the command's history key (see
+/// `CommandExecutionTemplate::history_key`).
+pub type RememberedValues = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Read the remembered values file, there are none if it does not exist.
+pub fn read_remembered_values(values_path: &str) -> Result<RememberedValues> {
+    if !Path::new(values_path).exists() {
+        return Ok(RememberedValues::new());
+    }
+
+    let remembered_values = fs::read_to_string(values_path).map_err(|e| {
+        Error::io_error("remembered values".to_string(), values_path.to_string(), e)
+    })?;
+
+    serde_yaml::from_str(&remembered_values).map_err(|e| {
+        Error::yaml_error(
+            "reading".to_string(),
+            "remembered values".to_string(),
+            values_path.to_string(),
+            e,
+        )
+    })
+}
+
+fn write_remembered_values(values_path: &str, remembered_values: &RememberedValues) -> Result<()> {
+    let remembered_values = serde_yaml::to_string(remembered_values).map_err(|e| {
+        Error::yaml_error(
+            "writing".to_string(),
+            "remembered values".to_string(),
+            values_path.to_string(),
+            e,
+        )
+    })?;
+
+    file_handling::write_atomically("remembered values", values_path, &remembered_values)
+}
+
+/// The values last entered for the command's parameters, if any were remembered.
+pub fn get_remembered_values(
+    values_path: &str,
+    command_key: &str,
+) -> Result<Option<HashMap<String, String>>> {
+    Ok(read_remembered_values(values_path)?
+        .remove(command_key)
+        .map(|values| values.into_iter().collect()))
+}
+
+/// Remember the values entered for the command's parameters, adding to those remembered before
+/// so parameters that weren't prompted for this time keep their values.
+pub fn remember_values(
+    values_path: &str,
+    command_key: &str,
+    values: impl IntoIterator<Item = (String, String)>,
+) -> Result<()> {
+    let _lock = file_handling::lock_file("remembered values", values_path)?;
+    let mut remembered_values = read_remembered_values(values_path)?;
+    remembered_values
+        .entry(command_key.to_string())
+        .or_default()
+        .extend(values);
+
+    write_remembered_values(values_path, &remembered_values)
+}
@@ -0,0 +1,101 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::command_definitions::CommandExecutionTemplate;
+use crate::error::{Error, Result};
+
+/// Extensions an executable can have on Windows, if `PATHEXT` isn't set.
+#[cfg(windows)]
+const DEFAULT_PATH_EXTENSIONS: &str = ".COM;.EXE;.BAT;.CMD";
+
+/// Names the binary could have: on Windows it can be run without its extension.
+fn get_candidate_names(binary: &str) -> Vec<String> {
+    #[cfg(windows)]
+    {
+        let extensions =
+            env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATH_EXTENSIONS.to_string());
+
+        std::iter::once(binary.to_string())
+            .chain(
+                extensions
+                    .split(';')
+                    .filter(|extension| !extension.is_empty())
+                    .map(|extension| format!("{binary}{extension}")),
+            )
+            .collect()
+    }
+
+    #[cfg(not(windows))]
+    vec![binary.to_string()]
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(not(unix))]
+    path.is_file()
+}
+
+/// Find the binary on the `PATH`, or at its path if it is given as one (e.g. `./bin/tool`).
+pub fn find_binary(binary: &str) -> Option<PathBuf> {
+    let expanded_binary = shellexpand::tilde(binary);
+    let binary_path = Path::new(expanded_binary.as_ref());
+
+    if binary_path.components().count() > 1 {
+        return get_candidate_names(&expanded_binary)
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|path| is_executable(path));
+    }
+
+    let paths = env::var_os("PATH")?;
+
+    env::split_paths(&paths)
+        .flat_map(|directory| {
+            get_candidate_names(binary)
+                .into_iter()
+                .map(move |name| directory.join(name))
+        })
+        .find(|path| is_executable(path))
+}
+
+/// Check that the binaries the command `requires` can be found, so it doesn't fail part way
+/// through with a less helpful error from the shell.
+pub fn check_requirements(execution_context: &CommandExecutionTemplate) -> Result<()> {
+    // The container engine or `kubectl` is needed to run the command in its container or pod
+    let container_engine = execution_context
+        .container
+        .as_ref()
+        .map(|container| container.engine().binary().to_string())
+        .or_else(|| {
+            execution_context
+                .kubectl
+                .as_ref()
+                .map(|_| "kubectl".to_string())
+        });
+
+    let missing_binaries = execution_context
+        .requires
+        .iter()
+        .flatten()
+        .chain(&container_engine)
+        .filter(|binary| find_binary(binary).is_none())
+        .collect_vec();
+
+    if missing_binaries.is_empty() {
+        return Ok(());
+    }
+
+    Err(Error::MissingRequirements {
+        missing: missing_binaries.iter().join("`, `"),
+        install_hint: execution_context.install_hint.clone(),
+    })
+}
@@ -0,0 +1,769 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::file_handling;
+use crate::interpolation::shell_quote;
+
+/// Ends the crontab line of a schedule, followed by its name, so it can be found to remove it.
+const CRON_MARKER: &str = "# rc schedule: ";
+
+const LAUNCHD_LABEL_PREFIX: &str = "com.rust-cuts.";
+const LAUNCH_AGENTS_PATH: &str = "~/Library/LaunchAgents";
+
+const SYSTEMD_UNIT_PREFIX: &str = "rc-";
+const SYSTEMD_USER_UNITS_PATH: &str = "~/.config/systemd/user";
+
+/// The fields of a cron expression, with the values they can have.
+const CRON_FIELDS: [(&str, u32, u32); 5] = [
+    ("minute", 0, 59),
+    ("hour", 0, 23),
+    ("day of the month", 1, 31),
+    ("month", 1, 12),
+    ("day of the week", 0, 7),
+];
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Days of the week as cron numbers them, from Sunday.
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How a schedule is installed.
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleBackend {
+    /// An entry in the user's crontab.
+    Cron,
+    /// A launchd agent, on macOS.
+    Launchd,
+    /// A systemd user timer, on Linux.
+    Systemd,
+}
+
+impl ScheduleBackend {
+    /// launchd on macOS, otherwise cron, which is available almost everywhere else.
+    pub fn for_platform() -> Self {
+        if cfg!(target_os = "macos") {
+            ScheduleBackend::Launchd
+        } else {
+            ScheduleBackend::Cron
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ScheduleBackend::Cron => "cron",
+            ScheduleBackend::Launchd => "launchd",
+            ScheduleBackend::Systemd => "systemd",
+        }
+    }
+}
+
+/// A command run on a schedule. These are saved so they can be listed and removed.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Schedule {
+    pub cron: String,
+    pub backend: ScheduleBackend,
+    /// The program (`rc`) and its arguments, which run the command.
+    pub arguments: Vec<String>,
+}
+
+/// Schedules by name.
+pub type Schedules = BTreeMap<String, Schedule>;
+
+/// When a cron expression runs. Each field has the values it matches, or `None` for any value.
+struct CronSchedule {
+    minutes: Option<Vec<u32>>,
+    hours: Option<Vec<u32>>,
+    days: Option<Vec<u32>>,
+    months: Option<Vec<u32>>,
+    weekdays: Option<Vec<u32>>,
+}
+
+fn parse_cron_value(value: &str, field_index: usize) -> Option<u32> {
+    let names: &[&str] = match field_index {
+        3 => &MONTH_NAMES,
+        4 => &DAY_NAMES,
+        _ => &[],
+    };
+    // Months are numbered from 1, days of the week from 0
+    let first_number = if field_index == 3 { 1 } else { 0 };
+
+    value.parse().ok().or_else(|| {
+        names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(value))
+            .map(|position| position as u32 + first_number)
+    })
+}
+
+/// Parse a cron field: `*`, values, ranges (`1-5`) and steps (`*/15`), separated by commas.
+fn parse_cron_field(field: &str, field_index: usize) -> Result<Option<Vec<u32>>> {
+    let (field_name, min, max) = CRON_FIELDS[field_index];
+    let invalid_field = || Error::InvalidSchedule(format!("`{field}` isn't a valid {field_name}"));
+
+    if field == "*" {
+        return Ok(None);
+    }
+
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().map_err(|_| invalid_field())?),
+            None => (part, 1),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (
+                parse_cron_value(start, field_index).ok_or_else(invalid_field)?,
+                parse_cron_value(end, field_index).ok_or_else(invalid_field)?,
+            ),
+            None => {
+                let start = parse_cron_value(range, field_index).ok_or_else(invalid_field)?;
+                // `5/10` is every 10 from 5
+                (start, if part.contains('/') { max } else { start })
+            }
+        };
+
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid_field());
+        }
+
+        values.extend((start..=end).step_by(step));
+    }
+
+    // Sunday is both 0 and 7
+    if field_index == 4 {
+        values = values.into_iter().map(|day| day % 7).collect();
+    }
+
+    Ok(Some(values.into_iter().sorted().dedup().collect()))
+}
+
+fn parse_cron(expression: &str) -> Result<CronSchedule> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    if fields.len() != CRON_FIELDS.len() {
+        return Err(Error::InvalidSchedule(format!(
+            "`{expression}` must have 5 fields: minute, hour, day of the month, month and day of the week"
+        )));
+    }
+
+    Ok(CronSchedule {
+        minutes: parse_cron_field(fields[0], 0)?,
+        hours: parse_cron_field(fields[1], 1)?,
+        days: parse_cron_field(fields[2], 2)?,
+        months: parse_cron_field(fields[3], 3)?,
+        weekdays: parse_cron_field(fields[4], 4)?,
+    })
+}
+
+impl CronSchedule {
+    /// The schedule as a systemd `OnCalendar` value, e.g. `Mon *-*-* 09:00:00`.
+    fn on_calendar(&self) -> String {
+        let list = |values: &Option<Vec<u32>>| match values {
+            Some(values) => values.iter().map(|value| format!("{value:02}")).join(","),
+            None => "*".to_string(),
+        };
+
+        let weekdays = match &self.weekdays {
+            Some(weekdays) => format!(
+                "{} ",
+                weekdays
+                    .iter()
+                    .map(|day| {
+                        let name = DAY_NAMES[*day as usize];
+                        format!("{}{}", name[..1].to_uppercase(), &name[1..])
+                    })
+                    .join(",")
+            ),
+            None => String::new(),
+        };
+
+        format!(
+            "{weekdays}*-{}-{} {}:{}:00",
+            list(&self.months),
+            list(&self.days),
+            list(&self.hours),
+            list(&self.minutes)
+        )
+    }
+
+    /// The schedule as launchd `StartCalendarInterval` entries, one for each combination of the
+    /// values, as launchd doesn't have lists.
+    fn calendar_intervals(&self) -> Vec<Vec<(&'static str, u32)>> {
+        [
+            ("Minute", &self.minutes),
+            ("Hour", &self.hours),
+            ("Day", &self.days),
+            ("Month", &self.months),
+            ("Weekday", &self.weekdays),
+        ]
+        .into_iter()
+        .filter_map(|(key, values)| {
+            values
+                .as_ref()
+                .map(|values| values.iter().map(|value| (key, *value)).collect_vec())
+        })
+        .multi_cartesian_product()
+        .collect()
+    }
+}
+
+/// Schedules are named after their command by default, which must be usable in file and unit
+/// names.
+pub fn get_default_name(command_id: &str) -> String {
+    command_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || get_default_name(name) != name {
+        return Err(Error::InvalidSchedule(format!(
+            "the name `{name}` can only have letters, numbers, `-` and `_`"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Run a program the schedule is installed with, returning its stdout.
+fn run_program(program: &str, arguments: &[&str], stdin: Option<&str>) -> Result<String> {
+    debug!("Running {program} {arguments:?}");
+
+    let mut child = match Command::new(program)
+        .args(arguments)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(Error::Schedule(format!("`{program}` is not installed.")))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if let (Some(input), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        child_stdin.write_all(input.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::Schedule(format!(
+            "`{program} {}` failed: {}",
+            arguments.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// The user's crontab, without the line for the schedule with the name.
+fn get_crontab_without(name: &str) -> Result<Vec<String>> {
+    // `crontab -l` fails if there is no crontab yet
+    let crontab = match run_program("crontab", &["-l"], None) {
+        Ok(crontab) => crontab,
+        Err(Error::Schedule(message)) if !message.ends_with("is not installed.") => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let marker = format!("{CRON_MARKER}{name}");
+    Ok(crontab
+        .lines()
+        .filter(|line| !line.ends_with(&marker))
+        .map(str::to_string)
+        .collect())
+}
+
+/// The schedule's line in the crontab, ending with its marker so it can be found to remove it.
+fn build_crontab_line(name: &str, schedule: &Schedule) -> String {
+    format!(
+        "{} {} {CRON_MARKER}{name}",
+        schedule.cron,
+        schedule.arguments.iter().map(|a| shell_quote(a)).join(" ")
+    )
+}
+
+fn write_crontab(lines: &[String]) -> Result<()> {
+    let crontab: String = lines.iter().map(|line| format!("{line}\n")).collect();
+    run_program("crontab", &["-"], Some(&crontab)).map(|_| ())
+}
+
+fn get_launchd_plist_path(name: &str) -> PathBuf {
+    Path::new(shellexpand::tilde(LAUNCH_AGENTS_PATH).as_ref())
+        .join(format!("{LAUNCHD_LABEL_PREFIX}{name}.plist"))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn build_launchd_plist(name: &str, schedule: &Schedule, cron: &CronSchedule) -> String {
+    let arguments: String = schedule
+        .arguments
+        .iter()
+        .map(|argument| format!("        <string>{}</string>\n", escape_xml(argument)))
+        .collect();
+
+    let intervals: String = cron
+        .calendar_intervals()
+        .iter()
+        .map(|interval| {
+            let entries: String = interval
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "            <key>{key}</key>\n            <integer>{value}</integer>\n"
+                    )
+                })
+                .collect();
+            format!("        <dict>\n{entries}        </dict>\n")
+        })
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL_PREFIX}{name}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>StartCalendarInterval</key>
+    <array>
+{intervals}    </array>
+</dict>
+</plist>
+"#
+    )
+}
+
+fn get_systemd_unit_path(name: &str, extension: &str) -> PathBuf {
+    Path::new(shellexpand::tilde(SYSTEMD_USER_UNITS_PATH).as_ref())
+        .join(format!("{SYSTEMD_UNIT_PREFIX}{name}.{extension}"))
+}
+
+fn build_systemd_units(name: &str, schedule: &Schedule, cron: &CronSchedule) -> (String, String) {
+    // systemd expands `$` in `ExecStart`, so they are escaped
+    let exec_start = schedule
+        .arguments
+        .iter()
+        .map(|argument| shell_quote(argument).replace('$', "$$"))
+        .join(" ");
+
+    let service = format!(
+        "[Unit]\nDescription=rc schedule {name}\n\n[Service]\nType=oneshot\nExecStart={exec_start}\n"
+    );
+    let timer = format!(
+        "[Unit]\nDescription=rc schedule {name}\n\n[Timer]\nOnCalendar={}\nPersistent=true\n\n[Install]\nWantedBy=timers.target\n",
+        cron.on_calendar()
+    );
+
+    (service, timer)
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    let io_error = |e| {
+        Error::io_error(
+            "schedule".to_string(),
+            path.to_string_lossy().to_string(),
+            e,
+        )
+    };
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(io_error)?;
+    }
+    fs::write(path, contents).map_err(io_error)
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(Error::io_error(
+            "schedule".to_string(),
+            path.to_string_lossy().to_string(),
+            e,
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Install the schedule with its backend, replacing any schedule with the same name.
+fn install(name: &str, schedule: &Schedule) -> Result<()> {
+    let cron = parse_cron(&schedule.cron)?;
+
+    match schedule.backend {
+        ScheduleBackend::Cron => {
+            let mut lines = get_crontab_without(name)?;
+            lines.push(build_crontab_line(name, schedule));
+            write_crontab(&lines)
+        }
+        ScheduleBackend::Launchd => {
+            let plist_path = get_launchd_plist_path(name);
+            let plist_path_string = plist_path.to_string_lossy().to_string();
+            if plist_path.exists() {
+                let _ = run_program("launchctl", &["unload", &plist_path_string], None);
+            }
+            write_file(&plist_path, &build_launchd_plist(name, schedule, &cron))?;
+            run_program("launchctl", &["load", "-w", &plist_path_string], None).map(|_| ())
+        }
+        ScheduleBackend::Systemd => {
+            let (service, timer) = build_systemd_units(name, schedule, &cron);
+            write_file(&get_systemd_unit_path(name, "service"), &service)?;
+            write_file(&get_systemd_unit_path(name, "timer"), &timer)?;
+            run_program("systemctl", &["--user", "daemon-reload"], None)?;
+            let timer_name = format!("{SYSTEMD_UNIT_PREFIX}{name}.timer");
+            run_program(
+                "systemctl",
+                &["--user", "enable", "--now", &timer_name],
+                None,
+            )
+            .map(|_| ())
+        }
+    }
+}
+
+fn uninstall(name: &str, schedule: &Schedule) -> Result<()> {
+    match schedule.backend {
+        ScheduleBackend::Cron => write_crontab(&get_crontab_without(name)?),
+        ScheduleBackend::Launchd => {
+            let plist_path = get_launchd_plist_path(name);
+            if plist_path.exists() {
+                run_program(
+                    "launchctl",
+                    &["unload", "-w", &plist_path.to_string_lossy()],
+                    None,
+                )?;
+            }
+            remove_file(&plist_path)
+        }
+        ScheduleBackend::Systemd => {
+            let timer_name = format!("{SYSTEMD_UNIT_PREFIX}{name}.timer");
+            // It may have already been disabled by hand
+            let _ = run_program(
+                "systemctl",
+                &["--user", "disable", "--now", &timer_name],
+                None,
+            );
+            remove_file(&get_systemd_unit_path(name, "timer"))?;
+            remove_file(&get_systemd_unit_path(name, "service"))?;
+            run_program("systemctl", &["--user", "daemon-reload"], None).map(|_| ())
+        }
+    }
+}
+
+/// Read the schedules file, there are none if it does not exist.
+pub fn read_schedules(schedules_path: &str) -> Result<Schedules> {
+    if !Path::new(schedules_path).exists() {
+        return Ok(Schedules::new());
+    }
+
+    let schedules = fs::read_to_string(schedules_path)
+        .map_err(|e| Error::io_error("schedules".to_string(), schedules_path.to_string(), e))?;
+
+    serde_yaml::from_str(&schedules).map_err(|e| {
+        Error::yaml_error(
+            "reading".to_string(),
+            "schedules".to_string(),
+            schedules_path.to_string(),
+            e,
+        )
+    })
+}
+
+fn write_schedules(schedules_path: &str, schedules: &Schedules) -> Result<()> {
+    let schedules = serde_yaml::to_string(schedules).map_err(|e| {
+        Error::yaml_error(
+            "writing".to_string(),
+            "schedules".to_string(),
+            schedules_path.to_string(),
+            e,
+        )
+    })?;
+
+    file_handling::write_atomically("schedules", schedules_path, &schedules)
+}
+
+/// Install the schedule and save it, so it can be listed and removed. An existing schedule with
+/// the same name is only replaced if `force` is given.
+pub fn add_schedule(
+    schedules_path: &str,
+    name: &str,
+    schedule: Schedule,
+    force: bool,
+) -> Result<()> {
+    check_name(name)?;
+    let _lock = file_handling::lock_file("schedules", schedules_path)?;
+    let mut schedules = read_schedules(schedules_path)?;
+
+    match schedules.get(name) {
+        Some(_) if !force => return Err(Error::ScheduleExists(name.to_string())),
+        // A different backend won't replace it, so it's removed first
+        Some(existing) if existing.backend != schedule.backend => uninstall(name, existing)?,
+        _ => {}
+    }
+
+    install(name, &schedule)?;
+    schedules.insert(name.to_string(), schedule);
+    write_schedules(schedules_path, &schedules)
+}
+
+pub fn remove_schedule(schedules_path: &str, name: &str) -> Result<()> {
+    let _lock = file_handling::lock_file("schedules", schedules_path)?;
+    let mut schedules = read_schedules(schedules_path)?;
+    let schedule = schedules
+        .remove(name)
+        .ok_or_else(|| Error::UnknownSchedule(name.to_string()))?;
+
+    uninstall(name, &schedule)?;
+    write_schedules(schedules_path, &schedules)
+}
+
+/// Print each schedule's name, when it runs and the `rc` arguments it runs with.
+pub fn print_schedules(schedules: &Schedules) {
+    if schedules.is_empty() {
+        println!(
+            "There are no schedules, add one with `rc schedule add <command> --cron <expression>`."
+        );
+        return;
+    }
+
+    for (name, schedule) in schedules {
+        println!(
+            "{name}: `{}` with {}, runs: rc {}",
+            schedule.cron,
+            schedule.backend.name(),
+            schedule
+                .arguments
+                .iter()
+                .skip(1)
+                .map(|argument| shell_quote(argument))
+                .join(" ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: usize = 0;
+    const HOUR: usize = 1;
+    const DAY: usize = 2;
+    const MONTH: usize = 3;
+    const WEEKDAY: usize = 4;
+
+    fn schedule(cron: &str, arguments: &[&str]) -> Schedule {
+        Schedule {
+            cron: cron.to_string(),
+            backend: ScheduleBackend::Cron,
+            arguments: arguments
+                .iter()
+                .map(|argument| argument.to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn values_can_be_numbers_or_names() {
+        assert_eq!(parse_cron_value("5", MINUTE), Some(5));
+        assert_eq!(parse_cron_value("MON", WEEKDAY), Some(1));
+        assert_eq!(parse_cron_value("sun", WEEKDAY), Some(0));
+        assert_eq!(parse_cron_value("jan", MONTH), Some(1));
+        assert_eq!(parse_cron_value("Dec", MONTH), Some(12));
+        assert_eq!(parse_cron_value("mon", MONTH), None);
+        assert_eq!(parse_cron_value("jan", HOUR), None);
+    }
+
+    #[test]
+    fn star_is_any_value() {
+        assert_eq!(parse_cron_field("*", MINUTE).unwrap(), None);
+    }
+
+    #[test]
+    fn lists_and_ranges() {
+        assert_eq!(
+            parse_cron_field("1-5", HOUR).unwrap(),
+            Some(vec![1, 2, 3, 4, 5])
+        );
+        assert_eq!(parse_cron_field("3,1,3", DAY).unwrap(), Some(vec![1, 3]));
+        assert_eq!(
+            parse_cron_field("1-2,10", MONTH).unwrap(),
+            Some(vec![1, 2, 10])
+        );
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(
+            parse_cron_field("*/15", MINUTE).unwrap(),
+            Some(vec![0, 15, 30, 45])
+        );
+        assert_eq!(
+            parse_cron_field("10-20/5", MINUTE).unwrap(),
+            Some(vec![10, 15, 20])
+        );
+        // From the value to the end of the field
+        assert_eq!(
+            parse_cron_field("5/20", MINUTE).unwrap(),
+            Some(vec![5, 25, 45])
+        );
+        assert_eq!(
+            parse_cron_field("*/6", HOUR).unwrap(),
+            Some(vec![0, 6, 12, 18])
+        );
+    }
+
+    #[test]
+    fn day_names() {
+        assert_eq!(
+            parse_cron_field("MON-FRI", WEEKDAY).unwrap(),
+            Some(vec![1, 2, 3, 4, 5])
+        );
+        assert_eq!(
+            parse_cron_field("sat,SUN", WEEKDAY).unwrap(),
+            Some(vec![0, 6])
+        );
+        assert_eq!(
+            parse_cron_field("jun-aug", MONTH).unwrap(),
+            Some(vec![6, 7, 8])
+        );
+    }
+
+    #[test]
+    fn sunday_is_zero_or_seven() {
+        assert_eq!(parse_cron_field("7", WEEKDAY).unwrap(), Some(vec![0]));
+        assert_eq!(parse_cron_field("0,7", WEEKDAY).unwrap(), Some(vec![0]));
+        assert_eq!(
+            parse_cron_field("5-7", WEEKDAY).unwrap(),
+            Some(vec![0, 5, 6])
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_errors() {
+        for (field, field_index) in [
+            ("60", MINUTE),
+            ("24", HOUR),
+            ("0", DAY),
+            ("32", DAY),
+            ("13", MONTH),
+            ("8", WEEKDAY),
+            ("50-70", MINUTE),
+        ] {
+            assert!(
+                matches!(
+                    parse_cron_field(field, field_index),
+                    Err(Error::InvalidSchedule(_))
+                ),
+                "`{field}` should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_fields_are_errors() {
+        for field in ["5-1", "*/0", "*/x", "x", "1-", "", "1,,2"] {
+            assert!(
+                matches!(
+                    parse_cron_field(field, MINUTE),
+                    Err(Error::InvalidSchedule(_))
+                ),
+                "`{field}` should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn expression_must_have_five_fields() {
+        assert!(parse_cron("* * * *").is_err());
+        assert!(parse_cron("0 * * * * *").is_err());
+        assert!(parse_cron("").is_err());
+    }
+
+    #[test]
+    fn expression_is_parsed_by_field() {
+        let cron = parse_cron("30 9 1,15 * MON-FRI").unwrap();
+
+        assert_eq!(cron.minutes, Some(vec![30]));
+        assert_eq!(cron.hours, Some(vec![9]));
+        assert_eq!(cron.days, Some(vec![1, 15]));
+        assert_eq!(cron.months, None);
+        assert_eq!(cron.weekdays, Some(vec![1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn crontab_line_quotes_arguments_and_ends_with_marker() {
+        let schedule = schedule(
+            "0 9 * * 1",
+            &["/usr/local/bin/rc", "deploy", "--param", "env=my prod"],
+        );
+
+        assert_eq!(
+            build_crontab_line("deploy", &schedule),
+            "0 9 * * 1 /usr/local/bin/rc deploy --param 'env=my prod' # rc schedule: deploy"
+        );
+    }
+
+    #[test]
+    fn on_calendar_for_weekdays() {
+        assert_eq!(
+            parse_cron("0 9 * * MON-FRI").unwrap().on_calendar(),
+            "Mon,Tue,Wed,Thu,Fri *-*-* 09:00:00"
+        );
+    }
+
+    #[test]
+    fn on_calendar_for_lists_and_steps() {
+        assert_eq!(
+            parse_cron("*/30 * 1 1,6 *").unwrap().on_calendar(),
+            "*-01,06-01 *:00,30:00"
+        );
+        assert_eq!(
+            parse_cron("* * * * *").unwrap().on_calendar(),
+            "*-*-* *:*:00"
+        );
+    }
+
+    #[test]
+    fn systemd_units_run_the_arguments_on_calendar() {
+        let schedule = schedule("15 2 * * SUN", &["rc", "backup", "--param", "dir=$HOME"]);
+        let (service, timer) =
+            build_systemd_units("backup", &schedule, &parse_cron(&schedule.cron).unwrap());
+
+        assert!(
+            service.contains("ExecStart=rc backup --param 'dir=$$HOME'\n"),
+            "{service}"
+        );
+        assert!(timer.contains("OnCalendar=Sun *-*-* 02:15:00\n"), "{timer}");
+    }
+}
@@ -0,0 +1,99 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::command_definitions::CommandDefinition;
+
+/// A command that matched a search, for `rc search`.
+#[derive(Serialize, Debug)]
+pub struct SearchResult {
+    /// The index to run the command with, if it has no ID.
+    pub index: usize,
+    pub id: Option<String>,
+    pub aliases: Vec<String>,
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    pub command: String,
+    /// How well the command matched, higher is better.
+    pub score: i64,
+    /// Which field matched best: `id`, `name`, `group`, `tags` or `command`.
+    pub matched: &'static str,
+}
+
+/// Fuzzy match the query against each command's IDs, name, group, tags and command, scoring each
+/// command by its best matching field. The best matches are first. Commands that can't be run
+/// here aren't included.
+pub fn search_commands(
+    command_definitions: &[CommandDefinition],
+    query: &str,
+) -> Vec<SearchResult> {
+    let matcher = SkimMatcherV2::default();
+
+    command_definitions
+        .iter()
+        .enumerate()
+        .filter(|(_, command_definition)| command_definition.is_available())
+        .filter_map(|(index, command_definition)| {
+            let command = command_definition.command.join(" ");
+            let fields = [
+                ("id", command_definition.ids().join(" ")),
+                ("name", command_definition.name.clone().unwrap_or_default()),
+                (
+                    "group",
+                    command_definition.group.clone().unwrap_or_default(),
+                ),
+                ("tags", command_definition.tags.iter().flatten().join(" ")),
+                ("command", command.clone()),
+            ];
+
+            // The first field with the best score, so an ID match wins over the same match in the
+            // command
+            let (score, matched) = fields
+                .iter()
+                .filter_map(|(field, text)| Some((matcher.fuzzy_match(text, query)?, *field)))
+                .rev()
+                .max_by_key(|(score, _)| *score)?;
+
+            Some(SearchResult {
+                index,
+                id: command_definition.id.clone(),
+                aliases: command_definition.aliases.clone().unwrap_or_default(),
+                name: command_definition.name.clone(),
+                tags: command_definition.tags.clone().unwrap_or_default(),
+                command,
+                score,
+                matched,
+            })
+        })
+        .sorted_by_key(|result| (-result.score, result.index))
+        .collect()
+}
+
+/// Print each result's ID (or index, if it has no ID) and name, and its command below.
+pub fn print_search_results(results: &[SearchResult], query: &str) {
+    if results.is_empty() {
+        println!("No commands match `{query}`.");
+        return;
+    }
+
+    for result in results {
+        let key = result
+            .id
+            .clone()
+            .unwrap_or_else(|| result.index.to_string());
+
+        match &result.name {
+            Some(name) => print!("{key}: {name}"),
+            None => print!("{key}"),
+        }
+        if result.tags.is_empty() {
+            println!();
+        } else {
+            println!(" [{}]", result.tags.join(", "));
+        }
+
+        // Multi-line function bodies are shown on one line
+        println!("    {}", result.command.replace('\n', "; "));
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::ErrorKind;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::Value;
+
+use crate::error::{Error, Result};
+
+/// The YAML tag for a secret, e.g. `!secret prod_db_password`.
+const SECRET_TAG: &str = "secret";
+/// The service secrets are stored under in the keychain.
+pub const KEYCHAIN_SERVICE: &str = "rust-cuts";
+const DEFAULT_SECRETS_FILE: &str = "~/.rust-cuts/secrets.age";
+
+/// Where secrets are read from.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    /// The OS keychain: the login keychain on macOS, or the Secret Service (e.g. GNOME Keyring)
+    /// on Linux.
+    #[default]
+    Keychain,
+    /// A YAML file of secrets by name, encrypted with `age`.
+    Age,
+}
+
+/// The `secrets` setting.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SecretsDefinition {
+    pub backend: Option<SecretBackend>,
+    /// The `age` encrypted file, `~/.rust-cuts/secrets.age` if not given.
+    pub file: Option<String>,
+    /// The `age` identity to decrypt the file with. Without one, `age` asks for the passphrase.
+    pub identity: Option<String>,
+}
+
+static SECRETS_DEFINITION: OnceLock<SecretsDefinition> = OnceLock::new();
+/// Secrets already read, so each is only read once.
+static SECRETS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+/// The secrets in the `age` file, once it has been decrypted.
+static AGE_SECRETS: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Read secrets as the settings say from now on, rather than from the keychain.
+pub fn use_secrets_definition(secrets_definition: SecretsDefinition) {
+    let _ = SECRETS_DEFINITION.set(secrets_definition);
+}
+
+/// A value from the config, which can be a secret, e.g. `!secret prod_db_password`. Secrets are
+/// only read when the command runs, so they're never shown, logged or saved.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigValue {
+    Plain(String),
+    /// The name of the secret.
+    Secret(String),
+}
+
+impl ConfigValue {
+    /// The secret's name, if the value is a secret.
+    pub fn secret_name(&self) -> Option<&str> {
+        match self {
+            ConfigValue::Plain(_) => None,
+            ConfigValue::Secret(name) => Some(name),
+        }
+    }
+
+    pub fn is_secret(&self) -> bool {
+        self.secret_name().is_some()
+    }
+
+    /// The value, reading it from the backend if it's a secret.
+    pub fn reveal(&self) -> Result<String> {
+        match self {
+            ConfigValue::Plain(value) => Ok(value.clone()),
+            ConfigValue::Secret(name) => get_secret(name),
+        }
+    }
+}
+
+/// Secrets are shown as `!secret name`, never their value.
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigValue::Plain(value) => write!(f, "{value}"),
+            ConfigValue::Secret(name) => write!(f, "!{SECRET_TAG} {name}"),
+        }
+    }
+}
+
+impl From<String> for ConfigValue {
+    fn from(value: String) -> Self {
+        ConfigValue::Plain(value)
+    }
+}
+
+impl From<&str> for ConfigValue {
+    fn from(value: &str) -> Self {
+        ConfigValue::Plain(value.to_string())
+    }
+}
+
+/// A value from the config. Numbers and booleans are taken as they're written, e.g. `8080` or
+/// `true`. A secret saved as JSON (e.g. in the history) is `{"secret": name}`, as JSON has no
+/// tags.
+fn from_value<E: de::Error>(value: Value) -> std::result::Result<ConfigValue, E> {
+    match value {
+        Value::String(value) => Ok(ConfigValue::Plain(value)),
+        Value::Number(value) => Ok(ConfigValue::Plain(value.to_string())),
+        Value::Bool(value) => Ok(ConfigValue::Plain(value.to_string())),
+        Value::Tagged(tagged) if tagged.tag == SECRET_TAG => match tagged.value {
+            Value::String(name) if !name.is_empty() => Ok(ConfigValue::Secret(name)),
+            _ => Err(E::custom("`!secret` must be followed by the secret's name")),
+        },
+        Value::Mapping(mapping) if mapping.len() == 1 => match mapping.into_iter().next() {
+            Some((Value::String(key), Value::String(name)))
+                if key == SECRET_TAG && !name.is_empty() =>
+            {
+                Ok(ConfigValue::Secret(name))
+            }
+            _ => Err(E::custom("expected a string, number or boolean")),
+        },
+        Value::Tagged(tagged) => Err(E::custom(format!(
+            "unknown tag `{}`, the only tag is `!secret`",
+            tagged.tag
+        ))),
+        _ => Err(E::custom("expected a string, number or boolean")),
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        from_value(Value::deserialize(deserializer)?)
+    }
+}
+
+/// A secret is saved as a `!secret` tag in YAML, and `{"secret": name}` in JSON.
+impl Serialize for ConfigValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            ConfigValue::Plain(value) => serializer.serialize_str(value),
+            ConfigValue::Secret(name) => {
+                serializer.serialize_newtype_variant("ConfigValue", 1, SECRET_TAG, name)
+            }
+        }
+    }
+}
+
+/// Run the command, returning its output without the trailing newline. `description` says what
+/// it's reading, for errors.
+fn read_output(mut command: Command, description: &str) -> Result<String> {
+    let binary = command.get_program().to_string_lossy().to_string();
+
+    let output = command
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => {
+                Error::Secret(format!("{description}, as `{binary}` isn't installed"))
+            }
+            _ => Error::Secret(format!(
+                "{description}, as `{binary}` could not be run: {e}"
+            )),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Secret(match stderr.trim() {
+            "" => format!("{description}, as it wasn't found"),
+            stderr => format!("{description}: {stderr}"),
+        }));
+    }
+
+    let output = String::from_utf8_lossy(&output.stdout);
+    Ok(output.strip_suffix('\n').unwrap_or(&output).to_string())
+}
+
+fn read_keychain(name: &str) -> Result<String> {
+    let description = format!("`{name}` from the keychain (service `{KEYCHAIN_SERVICE}`)");
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("security");
+        command.args([
+            "find-generic-password",
+            "-s",
+            KEYCHAIN_SERVICE,
+            "-a",
+            name,
+            "-w",
+        ]);
+        read_output(command, &description)
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let mut command = Command::new("secret-tool");
+        command.args(["lookup", "service", KEYCHAIN_SERVICE, "account", name]);
+        read_output(command, &description)
+    }
+
+    #[cfg(not(unix))]
+    {
+        Err(Error::Secret(format!(
+            "{description}, as the keychain isn't supported on this platform (the `age` backend \
+             can be used instead)"
+        )))
+    }
+}
+
+fn read_age_file(secrets_definition: &SecretsDefinition) -> Result<BTreeMap<String, String>> {
+    let file = shellexpand::tilde(
+        secrets_definition
+            .file
+            .as_deref()
+            .unwrap_or(DEFAULT_SECRETS_FILE),
+    )
+    .to_string();
+
+    let mut command = Command::new("age");
+    command.arg("--decrypt");
+    if let Some(identity) = &secrets_definition.identity {
+        command.args(["--identity", &shellexpand::tilde(identity)]);
+    }
+    command.arg(&file);
+
+    let secrets = read_output(command, &format!("the secrets in `{file}`"))?;
+
+    serde_yaml::from_str(&secrets).map_err(|e| {
+        Error::Secret(format!(
+            "the secrets in `{file}`, as they should be YAML of each secret by name ({e})"
+        ))
+    })
+}
+
+/// Read the secret from the backend in the settings, or the keychain if there isn't one.
+fn get_secret(name: &str) -> Result<String> {
+    let mut secrets = SECRETS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(secret) = secrets.get(name) {
+        return Ok(secret.clone());
+    }
+
+    let secrets_definition = SECRETS_DEFINITION.get().cloned().unwrap_or_default();
+    let secret = match secrets_definition.backend.unwrap_or_default() {
+        SecretBackend::Keychain => read_keychain(name)?,
+        SecretBackend::Age => {
+            if AGE_SECRETS.get().is_none() {
+                let _ = AGE_SECRETS.set(read_age_file(&secrets_definition)?);
+            }
+
+            AGE_SECRETS
+                .get()
+                .and_then(|age_secrets| age_secrets.get(name))
+                .cloned()
+                .ok_or_else(|| {
+                    Error::Secret(format!(
+                        "`{name}`, as it isn't in the `age` encrypted secrets"
+                    ))
+                })?
+        }
+    };
+
+    secrets.insert(name.to_string(), secret.clone());
+
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_definitions::CommandDefinition;
+
+    fn parse(yaml: &str) -> std::result::Result<ConfigValue, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    #[test]
+    fn secret_tag_is_a_secret() {
+        assert_eq!(
+            parse("!secret prod_db_password").unwrap(),
+            ConfigValue::Secret("prod_db_password".to_string())
+        );
+    }
+
+    #[test]
+    fn string_is_plain() {
+        assert_eq!(
+            parse("\"!secret x\"").unwrap(),
+            ConfigValue::Plain("!secret x".to_string())
+        );
+        assert_eq!(parse("hello").unwrap(), ConfigValue::from("hello"));
+    }
+
+    #[test]
+    fn numbers_and_booleans_are_plain() {
+        assert_eq!(parse("8080").unwrap(), ConfigValue::from("8080"));
+        assert_eq!(parse("1.5").unwrap(), ConfigValue::from("1.5"));
+        assert_eq!(parse("true").unwrap(), ConfigValue::from("true"));
+    }
+
+    #[test]
+    fn other_values_are_errors() {
+        assert!(parse("!password x").is_err());
+        assert!(parse("!secret").is_err());
+        assert!(parse("!secret [x]").is_err());
+        assert!(parse("[x]").is_err());
+        assert!(parse("{x: y}").is_err());
+        assert!(parse("~").is_err());
+    }
+
+    #[test]
+    fn secret_is_saved_as_a_tag_in_yaml() {
+        let secret = ConfigValue::Secret("api_token".to_string());
+        let yaml = serde_yaml::to_string(&secret).unwrap();
+
+        assert_eq!(yaml.trim(), "!secret api_token");
+        assert_eq!(parse(&yaml).unwrap(), secret);
+    }
+
+    #[test]
+    fn secret_is_saved_as_a_mapping_in_json() {
+        let secret = ConfigValue::Secret("api_token".to_string());
+        let json = serde_json::to_string(&secret).unwrap();
+
+        assert_eq!(json, r#"{"secret":"api_token"}"#);
+        assert_eq!(serde_json::from_str::<ConfigValue>(&json).unwrap(), secret);
+    }
+
+    #[test]
+    fn secret_is_shown_by_name() {
+        assert_eq!(
+            ConfigValue::Secret("api_token".to_string()).to_string(),
+            "!secret api_token"
+        );
+    }
+
+    #[test]
+    fn command_with_numeric_default_and_environment() {
+        let command_definitions: Vec<CommandDefinition> = serde_yaml::from_str(
+            r#"
+- command: ["serve", "--port", "{port}"]
+  parameters:
+    - name: port
+      default: 8080
+  environment:
+    PORT: 8080
+    DEBUG: true
+    TOKEN: !secret api_token
+"#,
+        )
+        .unwrap();
+
+        let parameter = &command_definitions[0].parameters.as_ref().unwrap()[0];
+        assert_eq!(parameter.default.as_deref(), Some("8080"));
+        assert_eq!(parameter.secret, None);
+
+        let environment = command_definitions[0].environment.as_ref().unwrap();
+        assert_eq!(environment["PORT"], ConfigValue::from("8080"));
+        assert_eq!(environment["DEBUG"], ConfigValue::from("true"));
+        assert_eq!(
+            environment["TOKEN"],
+            ConfigValue::Secret("api_token".to_string())
+        );
+    }
+
+    #[test]
+    fn secret_default_is_shown_by_name() {
+        let command_definitions: Vec<CommandDefinition> = serde_yaml::from_str(
+            r#"
+- command: ["curl", "-H", "Authorization: Bearer {token}"]
+  parameters:
+    - name: token
+      default: !secret api_token
+"#,
+        )
+        .unwrap();
+
+        let parameter = &command_definitions[0].parameters.as_ref().unwrap()[0];
+        assert_eq!(parameter.default.as_deref(), Some("!secret api_token"));
+        assert_eq!(parameter.secret.as_deref(), Some("api_token"));
+
+        let yaml = serde_yaml::to_string(parameter).unwrap();
+        assert!(yaml.contains("default: !secret api_token"), "{yaml}");
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::secrets::{ConfigValue, SecretsDefinition};
+use crate::sources::SourceDefinition;
+use crate::terminal::TerminalDefinition;
+use crate::theme::ThemeDefinition;
+
+/// How commands are ordered in the command list.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    /// The order they are defined in the config.
+    #[default]
+    Definition,
+    /// Most frequently and recently used first.
+    Frecency,
+}
+
+impl SortMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            SortMode::Definition => SortMode::Frecency,
+            SortMode::Frecency => SortMode::Definition,
+        }
+    }
+}
+
+/// A named set of paths and environment variables, chosen with `--profile` or `RC_PROFILE`.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ProfileDefinition {
+    /// Directory containing the profile's commands, history and last command files. Any of
+    /// these can also be given individually.
+    pub root: Option<String>,
+    pub config_path: Option<String>,
+    pub history_path: Option<String>,
+    pub last_command_path: Option<String>,
+    pub bookmarks_path: Option<String>,
+    /// Added to the environment of every command, unless the command sets the same variable.
+    pub environment: Option<HashMap<String, ConfigValue>>,
+}
+
+impl ProfileDefinition {
+    fn path_in_root(&self, path: &Option<String>, file_name: &str) -> Option<String> {
+        path.clone().or_else(|| {
+            self.root
+                .as_ref()
+                .map(|root| format!("{}/{file_name}", root.trim_end_matches('/')))
+        })
+    }
+
+    pub fn config_path(&self) -> Option<String> {
+        self.path_in_root(&self.config_path, "commands.yml")
+    }
+
+    pub fn history_path(&self) -> Option<String> {
+        self.path_in_root(&self.history_path, "history.jsonl")
+    }
+
+    pub fn last_command_path(&self) -> Option<String> {
+        self.path_in_root(&self.last_command_path, "last_command.yml")
+    }
+
+    pub fn bookmarks_path(&self) -> Option<String> {
+        self.path_in_root(&self.bookmarks_path, "bookmarks.yml")
+    }
+
+    /// Add the profile's environment variables to the command's environment.
+    pub fn apply_environment(&self, environment: &mut Option<HashMap<String, ConfigValue>>) {
+        let Some(profile_environment) = &self.environment else {
+            return;
+        };
+
+        let environment = environment.get_or_insert_with(HashMap::new);
+
+        for (key, value) in profile_environment {
+            environment
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// The `history` section of the settings: how much of the history is kept. Entries outside these
+/// limits are removed whenever `rc` starts.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct HistorySettings {
+    /// Remove entries older than this, e.g. `90d`.
+    pub max_age: Option<String>,
+    /// Keep at most this many of the most recent entries.
+    pub max_entries: Option<usize>,
+}
+
+/// User settings, read from the settings YAML file. All settings are optional.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Settings {
+    pub sort: Option<SortMode>,
+    pub theme: Option<ThemeDefinition>,
+    /// Always choose commands from a numbered list, as with `--plain-select`.
+    pub plain_select: Option<bool>,
+    /// Shared commands to add to the local ones, see `rc sync`.
+    pub sources: Option<Vec<SourceDefinition>>,
+    pub profiles: Option<HashMap<String, ProfileDefinition>>,
+    /// Terminal emulator to use with `--new-terminal`, instead of the platform's usual one.
+    pub terminal: Option<TerminalDefinition>,
+    pub history: Option<HistorySettings>,
+    /// Make keys in the command definitions that aren't fields (e.g. `parameterss`) an error,
+    /// rather than a warning. `rc validate` does this unless it's turned off.
+    pub strict: Option<bool>,
+    /// Start the parameters of every command from the values last entered for them, unless the
+    /// command sets `remember_values` itself.
+    pub remember_values: Option<bool>,
+    /// Where secrets (`!secret name`) are read from, the keychain if not given.
+    pub secrets: Option<SecretsDefinition>,
+}
+
+impl Settings {
+    /// Get the profile with the given name, which must be defined.
+    pub fn get_profile(&self, profile_name: Option<&str>) -> Result<Option<&ProfileDefinition>> {
+        let Some(profile_name) = profile_name else {
+            return Ok(None);
+        };
+
+        self.profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(profile_name))
+            .map(Some)
+            .ok_or_else(|| Error::UnknownProfile(profile_name.to_string()))
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io::{stdout, IsTerminal, Write};
+
+use clap::ValueEnum;
+
+use crate::error::{Error, Result};
+use crate::execution::PreparedCommand;
+use crate::terminal::build_command_line;
+
+/// Shells that `rc shell-init` can bind a key in.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Shell {
+    Zsh,
+    Bash,
+    Fish,
+}
+
+/// Insert the command at the cursor, redrawing the prompt whether or not one was chosen. `{key}`
+/// is replaced with the key to bind.
+const ZSH_INIT: &str = r#"rc-widget() {
+  local command
+  command="$(rc --print-only < /dev/tty)"
+  if [[ -n "$command" ]]; then
+    LBUFFER+="$command"
+  fi
+  zle reset-prompt
+}
+zle -N rc-widget
+bindkey '{key}' rc-widget
+"#;
+
+const BASH_INIT: &str = r#"__rc_widget() {
+  local command
+  command="$(rc --print-only < /dev/tty)"
+  if [[ -n "$command" ]]; then
+    READLINE_LINE="${READLINE_LINE:0:$READLINE_POINT}$command${READLINE_LINE:$READLINE_POINT}"
+    READLINE_POINT=$((READLINE_POINT + ${#command}))
+  fi
+}
+bind -x '"{key}": __rc_widget'
+"#;
+
+const FISH_INIT: &str = r#"function __rc_widget
+    set -l command (rc --print-only < /dev/tty | string collect)
+    if test -n "$command"
+        commandline --insert -- $command
+    end
+    commandline --function repaint
+end
+bind {key} __rc_widget
+"#;
+
+/// Convert a key like `ctrl-g` or `alt-g` to how the shell writes it in a key binding.
+fn get_key_binding(shell: Shell, key: &str) -> Result<String> {
+    let invalid_key = || Error::InvalidKeyBinding(key.to_string());
+
+    let key = key.to_lowercase();
+    let (modifier, letter) = key.split_once('-').ok_or_else(invalid_key)?;
+    let letter = match letter.chars().collect::<Vec<_>>()[..] {
+        [letter] if letter.is_ascii_lowercase() => letter,
+        _ => return Err(invalid_key()),
+    };
+
+    let binding = match (modifier, shell) {
+        ("ctrl", Shell::Zsh) => format!("^{}", letter.to_ascii_uppercase()),
+        ("ctrl", Shell::Bash) => format!("\\C-{letter}"),
+        ("ctrl", Shell::Fish) => format!("\\c{letter}"),
+        ("alt", _) => format!("\\e{letter}"),
+        _ => return Err(invalid_key()),
+    };
+
+    Ok(binding)
+}
+
+/// The script to add to the shell's config (e.g. with `eval "$(rc shell-init zsh)"`), which binds
+/// the key to choose a command and insert it at the cursor, instead of running it.
+pub fn get_init_script(shell: Shell, key: &str) -> Result<String> {
+    let script = match shell {
+        Shell::Zsh => ZSH_INIT,
+        Shell::Bash => BASH_INIT,
+        Shell::Fish => FISH_INIT,
+    };
+
+    Ok(script.replace("{key}", &get_key_binding(shell, key)?))
+}
+
+/// The command as one line that can be run on its own (e.g. with `eval`). If it has a working
+/// directory or environment, these are set in a subshell first, so they don't change the shell
+/// that runs it.
+pub fn get_standalone_command(prepared: &PreparedCommand) -> Result<String> {
+    let has_environment = prepared
+        .environment
+        .as_ref()
+        .is_some_and(|environment| !environment.is_empty());
+
+    if prepared.expanded_working_directory().is_none() && !has_environment {
+        return build_command_line(prepared);
+    }
+
+    Ok(format!("({})", build_command_line(prepared)?))
+}
+
+/// Where stdout was going before it was moved to the terminal, so the command can be printed to
+/// it.
+pub struct CapturedStdout(File);
+
+/// Move stdout to the terminal if it's being captured (e.g. with `$(rc --print-only)`), so the
+/// command list and prompts can be shown. Returns the captured stdout, or `None` if stdout wasn't
+/// moved, as it's already the terminal or there isn't one.
+pub fn redirect_stdout_to_terminal() -> Result<Option<CapturedStdout>> {
+    if stdout().is_terminal() {
+        return Ok(None);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::fs::OpenOptions;
+        use std::os::fd::{AsRawFd, FromRawFd};
+
+        let Ok(terminal) = OpenOptions::new()
+            .write(true)
+            .open(crate::command_selection::TTY_PATH)
+        else {
+            return Ok(None);
+        };
+
+        stdout().flush()?;
+
+        // SAFETY: `dup` and `dup2` only copy file descriptors, and the one returned by `dup` is
+        // owned by the `File` it's given to, so it's closed exactly once.
+        unsafe {
+            let captured = libc::dup(libc::STDOUT_FILENO);
+            if captured < 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+
+            if libc::dup2(terminal.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+                let error = std::io::Error::last_os_error();
+                libc::close(captured);
+                return Err(error.into());
+            }
+
+            Ok(Some(CapturedStdout(File::from_raw_fd(captured))))
+        }
+    }
+
+    #[cfg(not(unix))]
+    Ok(None)
+}
+
+/// Print the text to stdout, or where it was going before it was moved to the terminal.
+pub fn print_output(captured_stdout: Option<CapturedStdout>, text: &str) -> Result<()> {
+    match captured_stdout {
+        Some(CapturedStdout(mut file)) => writeln!(file, "{text}")?,
+        None => println!("{text}"),
+    }
+
+    Ok(())
+}
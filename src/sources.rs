@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::command_definitions::CommandDefinition;
+use crate::error::{Error, Result};
+use crate::file_handling;
+
+/// File in a git source that contains the commands, if not given.
+const DEFAULT_SOURCE_PATH: &str = "commands.yml";
+
+/// A shared set of commands, from a git repository or a YAML file at a URL.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SourceDefinition {
+    /// A git repository URL (ending in `.git`), or the URL of a YAML file to download.
+    pub url: String,
+    /// Shown next to the source's commands. Defaults to the last part of the URL.
+    pub name: Option<String>,
+    /// Path of the commands YAML file within a git repository.
+    pub path: Option<String>,
+}
+
+impl SourceDefinition {
+    fn is_git(&self) -> bool {
+        self.url.ends_with(".git") || self.url.starts_with("git@")
+    }
+
+    pub fn name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+
+        let last_part = self
+            .url
+            .trim_end_matches('/')
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or(&self.url);
+
+        last_part
+            .trim_end_matches(".git")
+            .trim_end_matches(".yml")
+            .trim_end_matches(".yaml")
+            .to_string()
+    }
+
+    /// Where the source is downloaded to: a directory for git, or a file otherwise.
+    fn cache_path(&self, sources_path: &str) -> PathBuf {
+        let path = Path::new(sources_path);
+
+        if self.is_git() {
+            path.join(self.name())
+        } else {
+            path.join(format!("{}.yml", self.name()))
+        }
+    }
+
+    /// The downloaded commands YAML file.
+    pub fn commands_path(&self, sources_path: &str) -> PathBuf {
+        let cache_path = self.cache_path(sources_path);
+
+        if self.is_git() {
+            cache_path.join(self.path.as_deref().unwrap_or(DEFAULT_SOURCE_PATH))
+        } else {
+            cache_path
+        }
+    }
+}
+
+fn run_sync_command(mut command: Command, source: &SourceDefinition) -> Result<()> {
+    debug!("Running {command:?}");
+
+    let output = command.output()?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::SourceSync {
+            source_name: source.name(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+fn sync_source(source: &SourceDefinition, sources_path: &str) -> Result<()> {
+    let cache_path = source.cache_path(sources_path);
+
+    let command = if source.is_git() {
+        if cache_path.exists() {
+            let mut command = Command::new("git");
+            command
+                .arg("-C")
+                .arg(&cache_path)
+                .args(["pull", "--ff-only"]);
+            command
+        } else {
+            let mut command = Command::new("git");
+            command
+                .args(["clone", "--depth", "1", &source.url])
+                .arg(&cache_path);
+            command
+        }
+    } else {
+        let mut command = Command::new("curl");
+        command
+            .args([
+                "--fail",
+                "--silent",
+                "--show-error",
+                "--location",
+                &source.url,
+            ])
+            .arg("--output")
+            .arg(&cache_path);
+        command
+    };
+
+    run_sync_command(command, source)
+}
+
+/// Download or update all the sources, returning whether they were all synced.
+pub fn sync_sources(sources: &[SourceDefinition], sources_path: &str) -> Result<bool> {
+    if sources.is_empty() {
+        println!("No sources are defined in the settings.");
+        return Ok(true);
+    }
+
+    fs::create_dir_all(sources_path)
+        .map_err(|e| Error::io_error("sources".to_string(), sources_path.to_string(), e))?;
+
+    let mut all_synced = true;
+
+    for source in sources {
+        match sync_source(source, sources_path) {
+            Ok(()) => println!("Synced `{}` from {}", source.name(), source.url),
+            Err(e) => {
+                eprintln!("{e}");
+                all_synced = false;
+            }
+        }
+    }
+
+    Ok(all_synced)
+}
+
+/// Add the commands from the synced sources after the local commands, marked with their source.
+///
+/// Source commands with the same ID as a local command are left out, so local commands can
+/// override shared ones.
+pub fn add_source_commands(
+    mut command_definitions: Vec<CommandDefinition>,
+    sources: &[SourceDefinition],
+    sources_path: &str,
+) -> Result<Vec<CommandDefinition>> {
+    let mut ids: HashSet<String> = command_definitions
+        .iter()
+        .flat_map(CommandDefinition::ids)
+        .cloned()
+        .collect();
+
+    for source in sources {
+        let commands_path = source.commands_path(sources_path);
+
+        if !commands_path.exists() {
+            warn!(
+                "Source `{}` has not been synced, run `rc sync` to get its commands",
+                source.name()
+            );
+            continue;
+        }
+
+        let source_command_definitions =
+            file_handling::get_command_definitions(&commands_path.to_string_lossy())?;
+
+        for mut command_definition in source_command_definitions {
+            if let Some(id) = &command_definition.id {
+                if !ids.insert(id.clone()) {
+                    debug!(
+                        "Skipping command `{id}` from source `{}`, it is already defined",
+                        source.name()
+                    );
+                    continue;
+                }
+            }
+
+            if let Some(aliases) = &mut command_definition.aliases {
+                aliases.retain(|alias| {
+                    let is_new = ids.insert(alias.clone());
+                    if !is_new {
+                        debug!(
+                            "Skipping alias `{alias}` from source `{}`, it is already used",
+                            source.name()
+                        );
+                    }
+                    is_new
+                });
+            }
+
+            command_definition.source = Some(source.name());
+            command_definitions.push(command_definition);
+        }
+    }
+
+    Ok(command_definitions)
+}
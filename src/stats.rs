@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::duration::format_duration;
+use crate::history::{format_age, HistoryEntry};
+
+/// How often a command has been run, how often it failed, and how long it usually takes.
+#[derive(Serialize, Debug)]
+pub struct CommandStats {
+    pub command_key: String,
+    pub runs: usize,
+    /// Runs that exited with a non-zero code.
+    pub failures: usize,
+    /// Failures out of the runs that recorded an exit code, from 0 to 1. Runs in a new terminal
+    /// don't record one.
+    pub failure_rate: Option<f64>,
+    /// Seconds since the Unix epoch that the command was last run.
+    pub last_run: u64,
+    /// Average of the runs that recorded how long they took.
+    pub average_duration_ms: Option<u64>,
+}
+
+/// Get the stats of each command in the history, most run first.
+pub fn get_command_stats(entries: &[HistoryEntry]) -> Vec<CommandStats> {
+    let mut entries_by_command: HashMap<&str, Vec<&HistoryEntry>> = HashMap::new();
+
+    for entry in entries {
+        entries_by_command
+            .entry(entry.command_key.as_str())
+            .or_default()
+            .push(entry);
+    }
+
+    entries_by_command
+        .into_iter()
+        .map(|(command_key, entries)| {
+            let durations: Vec<u64> = entries
+                .iter()
+                .filter_map(|entry| entry.duration_ms)
+                .collect();
+
+            let average_duration_ms = u64::try_from(durations.len())
+                .ok()
+                .filter(|count| *count > 0)
+                .map(|count| durations.iter().sum::<u64>() / count);
+
+            let exit_codes: Vec<i32> = entries.iter().filter_map(|entry| entry.exit_code).collect();
+            let failures = exit_codes
+                .iter()
+                .filter(|exit_code| **exit_code != 0)
+                .count();
+            let failure_rate =
+                (!exit_codes.is_empty()).then(|| failures as f64 / exit_codes.len() as f64);
+
+            CommandStats {
+                command_key: command_key.to_string(),
+                runs: entries.len(),
+                failures,
+                failure_rate,
+                last_run: entries
+                    .iter()
+                    .map(|entry| entry.timestamp)
+                    .max()
+                    .unwrap_or(0),
+                average_duration_ms,
+            }
+        })
+        .sorted_by(|a, b| {
+            b.runs
+                .cmp(&a.runs)
+                .then_with(|| a.command_key.cmp(&b.command_key))
+        })
+        .collect()
+}
+
+pub fn print_command_stats(stats: &[CommandStats]) {
+    if stats.is_empty() {
+        println!("No commands have been run yet.");
+        return;
+    }
+
+    let key_width = stats
+        .iter()
+        .map(|command_stats| command_stats.command_key.len())
+        .max()
+        .unwrap_or(0)
+        .max("Command".len());
+
+    println!(
+        "{:<key_width$}   {:>6}   {:>6}   {:>16}   {:>16}",
+        "Command", "Runs", "Failed", "Last run", "Average duration"
+    );
+
+    for command_stats in stats {
+        println!(
+            "{:<key_width$}   {:>6}   {:>6}   {:>16}   {:>16}",
+            command_stats.command_key,
+            command_stats.runs,
+            command_stats
+                .failure_rate
+                .map_or("-".to_string(), |failure_rate| format!(
+                    "{:.0}%",
+                    failure_rate * 100.0
+                )),
+            format_age(command_stats.last_run),
+            command_stats
+                .average_duration_ms
+                .map_or("-".to_string(), |average_duration_ms| format_duration(
+                    Duration::from_millis(average_duration_ms)
+                ))
+        );
+    }
+}
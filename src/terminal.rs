@@ -0,0 +1,436 @@
+use std::process::{Child, Command, Stdio};
+
+use itertools::Itertools;
+use log::debug;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::execution::PreparedCommand;
+use crate::interpolation::shell_quote;
+
+/// Placeholder in a custom terminal's arguments for the shell running the command.
+const COMMAND_PLACEHOLDER: &str = "{command}";
+
+/// Terminal emulators to try on Linux, in order, with the arguments that come before the command
+/// to run. These can also be chosen by name with the `terminal` setting on any platform.
+const LINUX_TERMINALS: [(&str, &[&str]); 9] = [
+    ("x-terminal-emulator", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("xfce4-terminal", &["-x"]),
+    ("kitty", &[]),
+    ("alacritty", &["-e"]),
+    ("wezterm", &["start", "--"]),
+    ("foot", &[]),
+    ("xterm", &["-e"]),
+];
+
+/// The terminal emulator to use with `--new-terminal`, from the `terminal` setting.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TerminalDefinition {
+    /// One of the supported terminals, e.g. `kitty`.
+    Name(String),
+    /// Any other terminal, with the arguments to run a command in it. The argument
+    /// `{command}` is replaced with the shell running the command, otherwise this is added to
+    /// the end.
+    Custom {
+        command: String,
+        arguments: Option<Vec<String>>,
+    },
+}
+
+impl TerminalDefinition {
+    /// The terminal's binary, and the arguments to run the shell in it.
+    fn get_command_and_arguments(
+        &self,
+        shell: &str,
+        script: &str,
+    ) -> Result<(String, Vec<String>)> {
+        let shell_arguments = [shell, "-i", "-c", script].map(str::to_string);
+
+        match self {
+            Self::Name(name) => {
+                let (_, arguments) = LINUX_TERMINALS
+                    .iter()
+                    .find(|(terminal, _)| terminal == name)
+                    .ok_or_else(|| Error::UnknownTerminal(name.clone()))?;
+
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .chain(shell_arguments)
+                    .collect();
+
+                Ok((name.clone(), arguments))
+            }
+            Self::Custom { command, arguments } => {
+                let arguments = arguments.clone().unwrap_or_default();
+
+                let arguments = if arguments
+                    .iter()
+                    .any(|argument| argument == COMMAND_PLACEHOLDER)
+                {
+                    arguments
+                        .into_iter()
+                        .flat_map(|argument| {
+                            if argument == COMMAND_PLACEHOLDER {
+                                shell_arguments.to_vec()
+                            } else {
+                                vec![argument]
+                            }
+                        })
+                        .collect()
+                } else {
+                    arguments.into_iter().chain(shell_arguments).collect()
+                };
+
+                Ok((command.clone(), arguments))
+            }
+        }
+    }
+}
+
+/// Build a line of shell that changes to the command's working directory and exports its
+/// environment, then runs it. Each step only runs if the one before succeeded.
+///
+/// Commands with secrets can't be, as the line is given to another program to run, where the
+/// secrets could be seen or saved.
+pub fn build_command_line(prepared: &PreparedCommand) -> Result<String> {
+    if prepared.has_secrets() {
+        return Err(Error::Secret(
+            "secrets for a command run outside of `rc` (in a new terminal, tmux or with \
+             `--print-only`), as they could be seen or saved there"
+                .to_string(),
+        ));
+    }
+
+    let mut setup = Vec::new();
+
+    if let Some(working_directory) = prepared.expanded_working_directory() {
+        setup.push(format!("cd {}", shell_quote(&working_directory)));
+    }
+
+    for (key, value) in prepared.environment.iter().flatten().sorted() {
+        setup.push(format!("export {key}={}", shell_quote(&value.to_string())));
+    }
+
+    setup.push(prepared.runnable_command());
+
+    Ok(setup.join(" && "))
+}
+
+/// Build a script that runs the command in the working directory with the environment, then
+/// waits for enter to be pressed so the output can be read before the window closes.
+pub fn build_script(prepared: &PreparedCommand) -> Result<String> {
+    Ok(format!(
+        "{}; printf '\\n[Exited with %s, press enter to close]' \"$?\"; read -r _",
+        build_command_line(prepared)?
+    ))
+}
+
+/// Opens a new terminal window running a shell script, without waiting for it to close.
+pub trait TerminalLauncher {
+    fn launch(&self, shell: &str, script: &str) -> Result<()>;
+}
+
+/// The platform's usual terminal: Terminal.app on macOS, the first installed of
+/// `LINUX_TERMINALS` on Linux, and a new console window on Windows.
+pub struct PlatformTerminal;
+
+/// The AppleScript that has Terminal.app run the script with the shell.
+#[cfg(target_os = "macos")]
+fn build_apple_script(shell: &str, script: &str) -> String {
+    let shell_command = format!("{} -i -c {}", shell_quote(shell), shell_quote(script));
+    // Escape for an AppleScript string, which only treats backslashes and double quotes
+    // specially. Single quotes are left for the shell, which `shell_quote` has escaped.
+    let shell_command = shell_command.replace('\\', "\\\\").replace('"', "\\\"");
+
+    format!("tell application \"Terminal\" to do script \"{shell_command}\"")
+}
+
+#[cfg(target_os = "macos")]
+impl TerminalLauncher for PlatformTerminal {
+    fn launch(&self, shell: &str, script: &str) -> Result<()> {
+        let status = Command::new("osascript")
+            .args([
+                "-e",
+                &build_apple_script(shell, script),
+                "-e",
+                "tell application \"Terminal\" to activate",
+            ])
+            .stdout(Stdio::null())
+            .status()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::NoTerminal)
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl TerminalLauncher for PlatformTerminal {
+    fn launch(&self, shell: &str, script: &str) -> Result<()> {
+        for (terminal, arguments) in LINUX_TERMINALS {
+            let result = spawn_terminal(
+                terminal,
+                &arguments
+                    .iter()
+                    .chain(&[shell, "-i", "-c", script])
+                    .collect_vec(),
+            );
+
+            match result {
+                Ok(_) => return Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    debug!("Terminal `{terminal}` is not installed");
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(Error::NoTerminal)
+    }
+}
+
+#[cfg(windows)]
+impl TerminalLauncher for PlatformTerminal {
+    fn launch(&self, shell: &str, script: &str) -> Result<()> {
+        use std::os::windows::process::CommandExt;
+
+        /// Start the process with its own console window, from `CreateProcess`.
+        const CREATE_NEW_CONSOLE: u32 = 0x0000_0010;
+
+        // The shell is started directly rather than through `cmd /C start`, as `cmd` would parse
+        // the script again, breaking on quotes and running anything after `&` itself
+        let result = Command::new(shell)
+            .args(["-i", "-c", script])
+            .creation_flags(CREATE_NEW_CONSOLE)
+            .stdin(Stdio::null())
+            .spawn();
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(Error::NoTerminal),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+impl TerminalLauncher for PlatformTerminal {
+    fn launch(&self, _shell: &str, _script: &str) -> Result<()> {
+        Err(Error::NoTerminal)
+    }
+}
+
+/// Start the terminal, without waiting for it to close.
+fn spawn_terminal<S: AsRef<std::ffi::OsStr>>(
+    terminal: &str,
+    arguments: &[S],
+) -> std::io::Result<Child> {
+    Command::new(terminal)
+        .args(arguments)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+/// The terminal from the `terminal` setting, rather than looking for one.
+impl TerminalLauncher for TerminalDefinition {
+    fn launch(&self, shell: &str, script: &str) -> Result<()> {
+        let (terminal, arguments) = self.get_command_and_arguments(shell, script)?;
+
+        match spawn_terminal(&terminal, &arguments) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::TerminalNotFound(terminal))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Run the command in a new terminal window, without waiting for it to finish. The terminal from
+/// the `terminal` setting is used if there is one, otherwise the platform's usual terminal.
+pub fn run_in_terminal(
+    shell: &str,
+    prepared: &PreparedCommand,
+    terminal_definition: Option<&TerminalDefinition>,
+) -> Result<()> {
+    let script = build_script(prepared)?;
+    debug!("Running in a new terminal: {script}");
+
+    let launcher: &dyn TerminalLauncher = match terminal_definition {
+        Some(terminal_definition) => terminal_definition,
+        None => &PlatformTerminal,
+    };
+
+    launcher.launch(shell, &script)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::command_definitions::{CommandDefinition, CommandExecutionTemplate};
+    use crate::execution;
+    use crate::interpolation::PreparedTemplate;
+    use crate::secrets::ConfigValue;
+
+    fn shell_arguments() -> Vec<String> {
+        ["bash", "-i", "-c", "echo hi"].map(str::to_string).to_vec()
+    }
+
+    fn custom(arguments: &[&str]) -> TerminalDefinition {
+        TerminalDefinition::Custom {
+            command: "my-terminal".to_string(),
+            arguments: Some(
+                arguments
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect(),
+            ),
+        }
+    }
+
+    fn prepare(
+        command_definition: CommandDefinition,
+        template_context: &[(&str, &str)],
+    ) -> PreparedCommand {
+        let execution_context =
+            CommandExecutionTemplate::from_command_definition(&command_definition);
+        let template = PreparedTemplate::parse(
+            &execution_context.command,
+            execution_context.working_directory.as_ref(),
+        )
+        .unwrap();
+        let template_context = template_context
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<HashMap<String, String>>();
+
+        execution::prepare_execution(&execution_context, &template, &Some(template_context))
+            .unwrap()
+    }
+
+    #[test]
+    fn named_terminal_has_its_arguments_before_the_shell() {
+        let (command, arguments) = TerminalDefinition::Name("gnome-terminal".to_string())
+            .get_command_and_arguments("bash", "echo hi")
+            .unwrap();
+
+        assert_eq!(command, "gnome-terminal");
+        assert_eq!(arguments[0], "--");
+        assert_eq!(arguments[1..], shell_arguments());
+    }
+
+    #[test]
+    fn unknown_terminal_name_is_an_error() {
+        let result = TerminalDefinition::Name("not-a-terminal".to_string())
+            .get_command_and_arguments("bash", "echo hi");
+
+        assert!(matches!(result, Err(Error::UnknownTerminal(name)) if name == "not-a-terminal"));
+    }
+
+    #[test]
+    fn custom_terminal_replaces_command_placeholder() {
+        let (command, arguments) = custom(&["--title", "rc", "{command}", "--hold"])
+            .get_command_and_arguments("bash", "echo hi")
+            .unwrap();
+
+        assert_eq!(command, "my-terminal");
+        assert_eq!(
+            arguments,
+            [
+                vec!["--title".to_string(), "rc".to_string()],
+                shell_arguments(),
+                vec!["--hold".to_string()],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn custom_terminal_without_placeholder_has_shell_added_to_the_end() {
+        let (_, arguments) = custom(&["-e"])
+            .get_command_and_arguments("bash", "echo hi")
+            .unwrap();
+
+        assert_eq!(
+            arguments,
+            [vec!["-e".to_string()], shell_arguments()].concat()
+        );
+    }
+
+    #[test]
+    fn command_line_quotes_directory_and_environment() {
+        let prepared = prepare(
+            CommandDefinition {
+                command: vec!["echo".to_string(), "{message|quote}".to_string()],
+                working_directory: Some("/tmp/it's here".to_string()),
+                environment: Some(HashMap::from([
+                    ("GREETING".to_string(), ConfigValue::from("say \"hi\"")),
+                    ("NAME".to_string(), ConfigValue::from("o'brien")),
+                ])),
+                ..CommandDefinition::default()
+            },
+            &[("message", "it's \"done\"")],
+        );
+
+        assert_eq!(
+            build_command_line(&prepared).unwrap(),
+            "cd '/tmp/it'\\''s here' && export GREETING='say \"hi\"' && \
+             export NAME='o'\\''brien' && echo 'it'\\''s \"done\"'"
+        );
+    }
+
+    #[test]
+    fn script_waits_after_the_command() {
+        let prepared = prepare(
+            CommandDefinition {
+                command: vec!["echo".to_string(), "hi".to_string()],
+                ..CommandDefinition::default()
+            },
+            &[],
+        );
+
+        assert_eq!(
+            build_script(&prepared).unwrap(),
+            "echo hi; printf '\\n[Exited with %s, press enter to close]' \"$?\"; read -r _"
+        );
+    }
+
+    #[test]
+    fn command_line_with_secrets_is_an_error() {
+        let prepared = prepare(
+            CommandDefinition {
+                command: vec!["psql".to_string()],
+                environment: Some(HashMap::from([(
+                    "PGPASSWORD".to_string(),
+                    ConfigValue::Secret("db_password".to_string()),
+                )])),
+                ..CommandDefinition::default()
+            },
+            &[],
+        );
+
+        assert!(matches!(
+            build_command_line(&prepared),
+            Err(Error::Secret(_))
+        ));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn apple_script_escapes_backslashes_and_double_quotes() {
+        assert_eq!(
+            build_apple_script("/bin/zsh", r#"echo "a\b" 'c'"#),
+            r#"tell application "Terminal" to do script "/bin/zsh -i -c 'echo \"a\\b\" '\\''c'\\'''""#
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::env;
+use std::io::{stdout, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossterm::queue;
+use crossterm::style::{
+    self, Attribute, Color, SetAttribute, SetBackgroundColor, SetForegroundColor,
+};
+use serde::Deserialize;
+
+use crate::cli_args::ColorPolicy;
+use crate::command_definitions::ColorDefinition;
+use crate::error::Result;
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Built in themes, which can be used as-is or as a base for custom colors.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    Solarized,
+}
+
+/// The `theme` section of the settings: a named theme, with any of its colors overridden.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ThemeDefinition {
+    pub name: Option<ThemeName>,
+    pub header_background: Option<ColorDefinition>,
+    pub header_foreground: Option<ColorDefinition>,
+    pub selected_background: Option<ColorDefinition>,
+    pub selected_foreground: Option<ColorDefinition>,
+    pub separator: Option<ColorDefinition>,
+}
+
+/// Colors used to draw the command list and parameter form.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub header_background: Color,
+    pub header_foreground: Color,
+    pub selected_background: Color,
+    pub selected_foreground: Color,
+    pub separator: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        ThemeName::default().theme()
+    }
+}
+
+impl ThemeName {
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemeName::Dark => Theme {
+                header_background: Color::DarkGreen,
+                header_foreground: Color::Reset,
+                selected_background: Color::DarkBlue,
+                selected_foreground: Color::Yellow,
+                separator: Color::DarkGrey,
+            },
+            ThemeName::Light => Theme {
+                header_background: Color::DarkCyan,
+                header_foreground: Color::White,
+                selected_background: Color::Blue,
+                selected_foreground: Color::White,
+                separator: Color::Grey,
+            },
+            ThemeName::Solarized => Theme {
+                header_background: Color::Rgb {
+                    r: 0x85,
+                    g: 0x99,
+                    b: 0x00,
+                },
+                header_foreground: Color::Rgb {
+                    r: 0x00,
+                    g: 0x2b,
+                    b: 0x36,
+                },
+                selected_background: Color::Rgb {
+                    r: 0x26,
+                    g: 0x8b,
+                    b: 0xd2,
+                },
+                selected_foreground: Color::Rgb {
+                    r: 0xfd,
+                    g: 0xf6,
+                    b: 0xe3,
+                },
+                separator: Color::Rgb {
+                    r: 0x58,
+                    g: 0x6e,
+                    b: 0x75,
+                },
+            },
+        }
+    }
+}
+
+fn color_or(color_definition: &Option<ColorDefinition>, default: Color) -> Result<Color> {
+    match color_definition {
+        Some(color_definition) => Ok(color_definition.as_crossterm_color()?.unwrap_or(default)),
+        None => Ok(default),
+    }
+}
+
+impl ThemeDefinition {
+    pub fn resolve(&self) -> Result<Theme> {
+        let base = self.name.unwrap_or_default().theme();
+
+        Ok(Theme {
+            header_background: color_or(&self.header_background, base.header_background)?,
+            header_foreground: color_or(&self.header_foreground, base.header_foreground)?,
+            selected_background: color_or(&self.selected_background, base.selected_background)?,
+            selected_foreground: color_or(&self.selected_foreground, base.selected_foreground)?,
+            separator: color_or(&self.separator, base.separator)?,
+        })
+    }
+}
+
+/// Turn colors on or off for everything that's printed. With `auto`, they're used if stdout is a
+/// terminal and the `NO_COLOR` environment variable isn't set (see <https://no-color.org>).
+pub fn apply_color_policy(color_policy: ColorPolicy) {
+    let enabled = match color_policy {
+        ColorPolicy::Auto => {
+            env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+                && stdout().is_terminal()
+        }
+        ColorPolicy::Always => true,
+        ColorPolicy::Never => false,
+    };
+
+    COLOR_ENABLED.store(enabled, Ordering::SeqCst);
+    style::force_color_output(enabled);
+}
+
+pub fn is_color_enabled() -> bool {
+    COLOR_ENABLED.load(Ordering::SeqCst)
+}
+
+/// Start drawing something that stands out, like the header or the selected command, in the
+/// colors. Without colors it's drawn reversed instead, so it still stands out.
+pub fn queue_highlight(
+    writer: &mut impl Write,
+    background: Color,
+    foreground: Color,
+) -> Result<()> {
+    if is_color_enabled() {
+        queue!(
+            writer,
+            SetBackgroundColor(background),
+            SetForegroundColor(foreground)
+        )?;
+    } else {
+        queue!(writer, SetAttribute(Attribute::Reverse))?;
+    }
+
+    Ok(())
+}
+
+/// Stop drawing what was started with `queue_highlight`.
+pub fn queue_end_highlight(writer: &mut impl Write) -> Result<()> {
+    queue!(
+        writer,
+        SetBackgroundColor(Color::Reset),
+        SetForegroundColor(Color::Reset),
+        SetAttribute(Attribute::NoReverse),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use std::env;
+use std::process::Command;
+
+use clap::ValueEnum;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::execution::PreparedCommand;
+use crate::terminal::build_script;
+
+/// Name used for the tmux window, pane or session if the command has no ID.
+const DEFAULT_TMUX_NAME: &str = "rc";
+
+/// Where in tmux to run a command.
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TmuxTarget {
+    /// A new window in the current session.
+    Window,
+    /// A new pane, split from the current one.
+    Pane,
+    /// A new detached session.
+    Session,
+}
+
+/// tmux names can't contain `.` or `:`, as they are used in target names.
+fn get_tmux_name(command_id: Option<&str>) -> String {
+    command_id
+        .unwrap_or(DEFAULT_TMUX_NAME)
+        .replace(['.', ':'], "-")
+}
+
+fn run_tmux(arguments: &[&str]) -> Result<String> {
+    debug!("Running tmux {arguments:?}");
+
+    let output = match Command::new("tmux").args(arguments).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::Tmux("tmux is not installed.".to_string()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(Error::Tmux(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Run the command in a new tmux window, pane or session, named after the command's ID.
+pub fn run_in_tmux(
+    target: TmuxTarget,
+    command_id: Option<&str>,
+    shell: &str,
+    prepared: &PreparedCommand,
+) -> Result<()> {
+    let name = get_tmux_name(command_id);
+    let script = build_script(prepared)?;
+    let shell_command = [shell, "-i", "-c", script.as_str()];
+
+    let is_inside_tmux = env::var_os("TMUX").is_some();
+    if target != TmuxTarget::Session && !is_inside_tmux {
+        return Err(Error::Tmux(
+            "A new window or pane can only be opened from inside tmux, use a session instead."
+                .to_string(),
+        ));
+    }
+
+    match target {
+        TmuxTarget::Window => {
+            run_tmux(&[&["new-window", "-n", &name][..], &shell_command].concat())?;
+        }
+        TmuxTarget::Pane => {
+            let pane_id = run_tmux(
+                &[
+                    &["split-window", "-P", "-F", "#{pane_id}"][..],
+                    &shell_command,
+                ]
+                .concat(),
+            )?;
+            run_tmux(&["select-pane", "-t", &pane_id, "-T", &name])?;
+        }
+        TmuxTarget::Session => {
+            run_tmux(&[&["new-session", "-d", "-s", &name][..], &shell_command].concat())?;
+            println!("Started tmux session `{name}`, attach to it with: tmux attach -t {name}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,498 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::command_definitions::{expand_variants, resolve_extends, CommandDefinition};
+use crate::duration::parse_duration;
+use crate::error::{Error, Result};
+use crate::file_handling;
+use crate::interpolation::{get_templates, get_tokens};
+use crate::migration;
+use crate::requirements::find_binary;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the command definitions, with the line of the command it applies to if
+/// this could be found.
+#[derive(Debug, Clone)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Severity {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => formatter.write_str("error"),
+            Severity::Warning => formatter.write_str("warning"),
+        }
+    }
+}
+
+/// Describe the command in messages by its name, falling back to its ID, the command itself, then
+/// its index.
+fn describe_command(command_definition: &CommandDefinition, index: usize) -> String {
+    command_definition
+        .name
+        .clone()
+        .or_else(|| command_definition.id.clone())
+        .filter(|description| !description.is_empty())
+        .unwrap_or_else(|| match command_definition.command.join(" ") {
+            command if command.is_empty() => format!("at index {index}"),
+            command => command,
+        })
+}
+
+/// Find the line number (starting at 1) of each top level list item in the YAML, which are the
+/// commands. Returns `None` if the commands can't be matched up to lines, e.g. if the YAML uses
+/// flow style.
+pub fn get_command_lines(config: &str, command_count: usize) -> Option<Vec<usize>> {
+    let list_items: Vec<(usize, usize)> = config
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let trimmed = line.trim_start();
+            if trimmed == "-" || trimmed.starts_with("- ") {
+                Some((i + 1, line.len() - trimmed.len()))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    // Commands are the list items with the least indentation
+    let command_indent = list_items.iter().map(|(_, indent)| *indent).min()?;
+
+    let command_lines: Vec<usize> = list_items
+        .into_iter()
+        .filter(|(_, indent)| *indent == command_indent)
+        .map(|(line, _)| line)
+        .collect();
+
+    (command_lines.len() == command_count).then_some(command_lines)
+}
+
+/// Check a single command, adding any issues found.
+/// The name closest to `name`, if it is close enough to likely be a typo of it.
+fn suggest_name<'a>(name: &str, names: &[&'a str]) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    names
+        .iter()
+        .map(|other| (strsim::damerau_levenshtein(name, other), *other))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, other)| other)
+}
+
+/// Warn about parameters that aren't used in the command and tokens without a parameter
+/// definition, suggesting which is meant when one looks like a typo of the other.
+fn get_parameter_warnings(
+    description: &str,
+    parameter_names: &HashSet<&str>,
+    tokens: &HashSet<String>,
+) -> Vec<String> {
+    let unused_parameters: Vec<&str> = parameter_names
+        .iter()
+        .filter(|parameter_name| !tokens.contains(**parameter_name))
+        .copied()
+        .sorted()
+        .collect();
+    let undefined_tokens: Vec<&str> = tokens
+        .iter()
+        .map(String::as_str)
+        .filter(|token| !parameter_names.contains(token))
+        .sorted()
+        .collect();
+
+    let mut warnings = Vec::new();
+
+    for parameter_name in &unused_parameters {
+        let mut warning = format!(
+            "Command `{description}` defines parameter `{parameter_name}`, \
+            which is not used in the command."
+        );
+        if let Some(token) = suggest_name(parameter_name, &undefined_tokens) {
+            warning.push_str(&format!(" Should `{{{token}}}` be `{{{parameter_name}}}`?"));
+        }
+        warnings.push(warning);
+    }
+
+    for token in &undefined_tokens {
+        let mut warning = format!(
+            "Command `{description}` uses `{{{token}}}`, \
+            which has no parameter definition (so no default)."
+        );
+        if let Some(parameter_name) = suggest_name(token, &unused_parameters) {
+            warning.push_str(&format!(" Did you mean `{{{parameter_name}}}`?"));
+        }
+        warnings.push(warning);
+    }
+
+    warnings
+}
+
+/// The parameter warnings of each command, e.g. for `--verbose` to show when the commands are
+/// loaded. Invalid templates are skipped, as they are reported when the command is run.
+pub fn find_parameter_warnings(command_definitions: &[CommandDefinition]) -> Vec<String> {
+    command_definitions
+        .iter()
+        .enumerate()
+        .flat_map(|(i, command_definition)| {
+            let mut tokens = get_templates(&command_definition.command)
+                .map(|templates| get_tokens(&templates))
+                .unwrap_or_default();
+            tokens.extend(
+                get_templates(command_definition.working_directory.as_slice())
+                    .map(|templates| get_tokens(&templates))
+                    .unwrap_or_default(),
+            );
+            if let Some(kubectl) = &command_definition.kubectl {
+                tokens.extend(kubectl.tokens().unwrap_or_default());
+            }
+
+            let parameter_names: HashSet<&str> = command_definition
+                .parameters
+                .iter()
+                .flatten()
+                .map(|parameter| parameter.name.as_str())
+                .collect();
+
+            get_parameter_warnings(
+                &describe_command(command_definition, i),
+                &parameter_names,
+                &tokens,
+            )
+        })
+        .collect()
+}
+
+fn validate_command(
+    command_definition: &CommandDefinition,
+    index: usize,
+    seen_ids: &mut HashSet<String>,
+    issues: &mut Vec<(Severity, String)>,
+) {
+    let description = describe_command(command_definition, index);
+
+    let mut error = |message: String| issues.push((Severity::Error, message));
+
+    if command_definition.command.is_empty() {
+        error(format!("Command `{description}` has an empty `command`."));
+    }
+
+    if command_definition.id.is_none() && command_definition.dangerous.unwrap_or(false) {
+        error(Error::DangerousCommandWithoutId(description.clone()).to_string());
+    }
+
+    for id in command_definition.ids() {
+        if id.parse::<usize>().is_ok() {
+            error(Error::NumericCommandId(id.clone()).to_string());
+        }
+
+        if !seen_ids.insert(id.clone()) {
+            error(Error::DuplicateCommandId(id.clone()).to_string());
+        }
+    }
+
+    if let Err(e) = command_definition.foreground_color() {
+        error(format!("Command `{description}` foreground color: {e}"));
+    }
+
+    if let Err(e) = command_definition.background_color() {
+        error(format!("Command `{description}` background color: {e}"));
+    }
+
+    if let Some(Err(e)) = command_definition
+        .container
+        .as_ref()
+        .map(|container| container.check())
+    {
+        error(format!("Command `{description}`: {e}"));
+    }
+
+    if let Some(kubectl) = &command_definition.kubectl {
+        if let Err(e) = kubectl.check() {
+            error(format!("Command `{description}`: {e}"));
+        }
+
+        if command_definition.container.is_some() {
+            error(format!(
+                "Command `{description}`: {}",
+                Error::InvalidKubectl("it can't be used with `container`".to_string())
+            ));
+        }
+    }
+
+    if let Some(warn_after) = &command_definition.warn_after {
+        if let Err(e) = parse_duration(warn_after) {
+            error(format!("Command `{description}` `warn_after`: {e}"));
+        }
+    }
+
+    let mut tokens = match get_templates(&command_definition.command) {
+        Ok(templates) => get_tokens(&templates),
+        Err(e) => {
+            error(format!(
+                "Command `{description}` has an invalid template: {e}"
+            ));
+            HashSet::new()
+        }
+    };
+
+    let working_directory_tokens =
+        match get_templates(command_definition.working_directory.as_slice()) {
+            Ok(templates) => get_tokens(&templates),
+            Err(e) => {
+                error(format!(
+                    "Command `{description}` has an invalid working directory template: {e}"
+                ));
+                HashSet::new()
+            }
+        };
+    let has_working_directory_tokens = !working_directory_tokens.is_empty();
+    tokens.extend(working_directory_tokens);
+
+    if let Some(kubectl) = &command_definition.kubectl {
+        match kubectl.tokens() {
+            Ok(kubectl_tokens) => tokens.extend(kubectl_tokens),
+            Err(e) => error(format!(
+                "Command `{description}` has an invalid `kubectl` template: {e}"
+            )),
+        }
+    }
+
+    for (variant_name, variant) in command_definition.variants.iter().flatten() {
+        for parameter_name in variant
+            .parameters
+            .iter()
+            .flatten()
+            .map(|(name, _)| name)
+            .sorted()
+        {
+            if !tokens.contains(parameter_name) {
+                issues.push((
+                    Severity::Warning,
+                    format!(
+                        "Command `{description}` variant `{variant_name}` sets `{parameter_name}`, \
+                        which is not used in the command."
+                    ),
+                ));
+            }
+        }
+    }
+
+    for binary in command_definition.requires.iter().flatten() {
+        if find_binary(binary).is_none() {
+            issues.push((
+                Severity::Warning,
+                format!("Command `{description}` requires `{binary}`, which is not on the PATH."),
+            ));
+        }
+    }
+
+    let parameter_names: HashSet<&str> = command_definition
+        .parameters
+        .iter()
+        .flatten()
+        .map(|parameter| parameter.name.as_str())
+        .collect();
+
+    issues.extend(
+        get_parameter_warnings(&description, &parameter_names, &tokens)
+            .into_iter()
+            .map(|warning| (Severity::Warning, warning)),
+    );
+
+    // Working directories with parameters can only be checked when the command is run
+    if let Some(working_directory) = command_definition
+        .working_directory
+        .as_ref()
+        .filter(|_| !has_working_directory_tokens)
+    {
+        let expanded_working_directory = shellexpand::tilde(working_directory);
+        let path = Path::new(expanded_working_directory.as_ref());
+
+        if !path.exists() {
+            issues.push((
+                Severity::Warning,
+                format!(
+                    "Command `{description}` working directory `{working_directory}` does not exist."
+                ),
+            ));
+        } else if !path.is_dir() {
+            issues.push((
+                Severity::Warning,
+                format!(
+                    "Command `{description}` working directory `{working_directory}` is not a directory."
+                ),
+            ));
+        }
+    }
+}
+
+/// Check all the command definitions in the config file, returning every issue found rather than
+/// stopping at the first. If `strict`, keys that aren't fields are errors rather than warnings.
+pub fn validate_config(config_path: &str, strict: bool) -> Result<Vec<ValidationIssue>> {
+    let config = fs::read_to_string(config_path)
+        .map_err(|e| Error::io_error("config".to_string(), config_path.to_string(), e))?;
+
+    let command_definitions = match migration::parse_command_definitions(config_path, &config) {
+        Ok(command_definitions) => command_definitions,
+        Err(Error::Yaml {
+            original, details, ..
+        }) => {
+            return Ok(vec![ValidationIssue {
+                severity: Severity::Error,
+                line: original.location().map(|location| location.line()),
+                message: format!("Invalid command definitions: {original}{details}"),
+            }]);
+        }
+        Err(e) => {
+            return Ok(vec![ValidationIssue {
+                severity: Severity::Error,
+                line: None,
+                message: e.to_string(),
+            }]);
+        }
+    };
+
+    if command_definitions.is_empty() {
+        return Ok(vec![ValidationIssue {
+            severity: Severity::Error,
+            line: None,
+            message: Error::empty_command_definition(config_path.to_string()).to_string(),
+        }]);
+    }
+
+    let command_lines = get_command_lines(&config, command_definitions.len());
+    let mut seen_ids = HashSet::new();
+    let mut validation_issues = Vec::new();
+
+    // The commands are still checked as they are if the ones they extend can't be found
+    let command_definitions = match resolve_extends(command_definitions.clone()) {
+        Ok(command_definitions) => command_definitions,
+        Err(e) => {
+            validation_issues.push(ValidationIssue {
+                severity: Severity::Error,
+                line: None,
+                message: e.to_string(),
+            });
+            command_definitions
+        }
+    };
+
+    let unknown_fields = file_handling::find_unknown_fields(&config);
+
+    // Commands can be chained to variants, so their IDs are included
+    let all_ids: HashSet<String> = expand_variants(command_definitions.clone())
+        .iter()
+        .flat_map(CommandDefinition::ids)
+        .cloned()
+        .collect();
+
+    for (i, command_definition) in command_definitions.iter().enumerate() {
+        let mut issues = Vec::new();
+        let description = describe_command(command_definition, i);
+
+        // Only the fields this version knows are read, so there's nothing else to check
+        if command_definition.needs_upgrade() {
+            issues.push((
+                Severity::Warning,
+                Error::UpgradeNeeded {
+                    command: description.clone(),
+                    requirement: command_definition
+                        .requires_version
+                        .clone()
+                        .unwrap_or_default(),
+                }
+                .to_string(),
+            ));
+        } else {
+            validate_command(command_definition, i, &mut seen_ids, &mut issues);
+        }
+
+        for chained_id in [&command_definition.and_then, &command_definition.or_else]
+            .into_iter()
+            .flatten()
+        {
+            if !all_ids.contains(chained_id) {
+                issues.push((
+                    Severity::Error,
+                    Error::UnknownChainedCommand {
+                        command: description.clone(),
+                        chained: chained_id.clone(),
+                    }
+                    .to_string(),
+                ));
+            }
+        }
+        let line = command_lines.as_ref().map(|command_lines| command_lines[i]);
+
+        validation_issues.extend(
+            issues
+                .into_iter()
+                .map(|(severity, message)| ValidationIssue {
+                    severity,
+                    line,
+                    message,
+                }),
+        );
+
+        // These are on the line of the key, rather than the command
+        validation_issues.extend(
+            unknown_fields
+                .iter()
+                .filter(|unknown_field| unknown_field.command_index == i)
+                .map(|unknown_field| ValidationIssue {
+                    severity: if strict {
+                        Severity::Error
+                    } else {
+                        Severity::Warning
+                    },
+                    line: file_handling::find_unknown_field_line(
+                        &config,
+                        command_lines.as_deref(),
+                        unknown_field,
+                    )
+                    .or(line),
+                    message: format!("Command `{description}`: {}", unknown_field.describe()),
+                }),
+        );
+    }
+
+    Ok(validation_issues)
+}
+
+/// Print the issues, prefixed with the file and line, followed by a summary.
+pub fn print_validation_issues(config_path: &str, issues: &[ValidationIssue]) {
+    for issue in issues {
+        match issue.line {
+            Some(line) => println!(
+                "{config_path}:{line}: {}: {}",
+                issue.severity, issue.message
+            ),
+            None => println!("{config_path}: {}: {}", issue.severity, issue.message),
+        }
+    }
+
+    let error_count = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    let warning_count = issues.len() - error_count;
+
+    if issues.is_empty() {
+        println!("{config_path}: No problems found.");
+    } else {
+        println!("{config_path}: {error_count} error(s), {warning_count} warning(s).");
+    }
+}
@@ -0,0 +1,64 @@
+use std::cmp::Ordering;
+
+use crate::error::{Error, Result};
+
+/// The version of `rc` that is running.
+pub const RC_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Parse a version like `0.6` or `1.2.3` into its numbers. Missing numbers are 0, so `0.6` is the
+/// same as `0.6.0`.
+fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let mut numbers = [0; 3];
+    let mut parts = version.trim().trim_start_matches('v').split('.');
+
+    for number in &mut numbers {
+        match parts.next() {
+            Some(part) => *number = part.parse().ok()?,
+            None => break,
+        }
+    }
+
+    // More than three numbers isn't a version
+    parts.next().is_none().then_some(numbers)
+}
+
+/// Whether `version` meets the requirement, e.g. `>=0.6`. A requirement is one or more
+/// comparisons (`>=`, `>`, `<=`, `<` or `=`) separated by commas, which must all be met. A
+/// version on its own is the same as `>=`.
+pub fn meets_requirement(requirement: &str, version: &str) -> Result<bool> {
+    let invalid_requirement = || Error::InvalidVersionRequirement(requirement.to_string());
+    let version = parse_version(version).ok_or_else(invalid_requirement)?;
+
+    for comparison in requirement.split(',') {
+        let comparison = comparison.trim();
+        let (operator, required_version) = [">=", "<=", ">", "<", "="]
+            .into_iter()
+            .find_map(|operator| {
+                comparison
+                    .strip_prefix(operator)
+                    .map(|required_version| (operator, required_version))
+            })
+            .unwrap_or((">=", comparison));
+        let required_version = parse_version(required_version).ok_or_else(invalid_requirement)?;
+
+        let ordering = version.cmp(&required_version);
+        let is_met = match operator {
+            ">=" => ordering != Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            ">" => ordering == Ordering::Greater,
+            "<" => ordering == Ordering::Less,
+            _ => ordering == Ordering::Equal,
+        };
+
+        if !is_met {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Whether this version of `rc` meets the requirement.
+pub fn is_supported(requirement: &str) -> Result<bool> {
+    meets_requirement(requirement, RC_VERSION)
+}
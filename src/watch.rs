@@ -0,0 +1,160 @@
+use std::env;
+use std::path::{self, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use crossterm::cursor::MoveTo;
+use crossterm::execute;
+use crossterm::terminal::{Clear, ClearType};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use itertools::Itertools;
+use log::debug;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::error::{Error, Result};
+use crate::interrupt;
+
+/// How long to wait for more changes after one is seen, so that saving several files (or an
+/// editor writing a file in several steps) only reruns the command once.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(200);
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for glob in globs {
+        builder
+            .add(Glob::new(glob).map_err(|e| Error::Watch(format!("Invalid glob `{glob}`: {e}")))?);
+    }
+
+    builder
+        .build()
+        .map_err(|e| Error::Watch(format!("Invalid globs: {e}")))
+}
+
+/// Get the first changed path that matches the globs, relative to the watched directory.
+fn get_matching_path(event: &Event, glob_set: &GlobSet, root: &Path) -> Option<String> {
+    // Files being read (e.g. by the command itself) are not changes
+    if matches!(event.kind, EventKind::Access(_)) {
+        return None;
+    }
+
+    event.paths.iter().find_map(|path| {
+        let relative_path = path.strip_prefix(root).unwrap_or(path);
+
+        glob_set
+            .is_match(relative_path)
+            .then(|| relative_path.to_string_lossy().to_string())
+    })
+}
+
+/// Wait for a change to a file matching the globs, then for changes to stop.
+fn wait_for_change(
+    receiver: &Receiver<notify::Result<Event>>,
+    glob_set: &GlobSet,
+    root: &Path,
+) -> Result<String> {
+    let changed_path = loop {
+        let event = receiver
+            .recv()
+            .map_err(|_| Error::Watch("Stopped receiving file changes.".to_string()))?
+            .map_err(|e| Error::Watch(e.to_string()))?;
+
+        if let Some(changed_path) = get_matching_path(&event, glob_set, root) {
+            break changed_path;
+        }
+    };
+
+    loop {
+        match receiver.recv_timeout(DEBOUNCE_DURATION) {
+            Ok(event) => debug!("Ignoring change during debounce: {event:?}"),
+            Err(RecvTimeoutError::Timeout) => return Ok(changed_path),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Error::Watch("Stopped receiving file changes.".to_string()))
+            }
+        }
+    }
+}
+
+/// Watches files for changes without blocking, e.g. to reload the commands while they are being
+/// chosen.
+pub struct FileWatcher {
+    // Changes stop being sent when the watcher is dropped
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    paths: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Watch the files at `paths`. Their directories are watched rather than the files, as editors
+    /// often save by replacing the file.
+    pub fn new(paths: &[PathBuf]) -> Result<Self> {
+        let paths = paths
+            .iter()
+            .map(path::absolute)
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).map_err(|e| Error::Watch(e.to_string()))?;
+
+        for directory in paths.iter().filter_map(|path| path.parent()).unique() {
+            watcher
+                .watch(directory, RecursiveMode::NonRecursive)
+                .map_err(|e| Error::Watch(e.to_string()))?;
+        }
+
+        Ok(FileWatcher {
+            _watcher: watcher,
+            receiver,
+            paths,
+        })
+    }
+
+    /// Whether any of the files have changed since this was last called.
+    pub fn has_changed(&self) -> bool {
+        // All the changes are taken, so one save isn't seen as several
+        let events: Vec<_> = self.receiver.try_iter().collect();
+
+        events.into_iter().flatten().any(|event| {
+            !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|path| self.paths.contains(path))
+        })
+    }
+}
+
+/// Call `run`, then call it again each time a file in the current directory that matches one of
+/// the globs changes, until interrupted.
+pub fn watch_and_run(
+    globs: &[String],
+    clear_screen: bool,
+    mut run: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let glob_set = build_glob_set(globs)?;
+    let root = env::current_dir()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|e| Error::Watch(e.to_string()))?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(|e| Error::Watch(e.to_string()))?;
+
+    loop {
+        run()?;
+
+        if interrupt::was_interrupted() {
+            return Ok(());
+        }
+
+        println!(
+            "\nWatching for changes to {} (ctrl-c to stop)",
+            globs.join(", ")
+        );
+        let changed_path = wait_for_change(&receiver, &glob_set, &root)?;
+
+        if clear_screen {
+            execute!(std::io::stdout(), Clear(ClearType::All), MoveTo(0, 0))?;
+        }
+        println!("`{changed_path}` changed, rerunning\n");
+    }
+}
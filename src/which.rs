@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::command_definitions::{CommandDefinition, CommandExecutionTemplate};
+use crate::error::Result;
+use crate::interpolation::{build_default_lookup, PreparedTemplate};
+use crate::migration;
+use crate::settings::ProfileDefinition;
+use crate::sources::SourceDefinition;
+use crate::validation::get_command_lines;
+
+/// Find the line the command at `index` in the file starts on, if it can be found.
+fn get_definition_line(path: &Path, index: usize) -> Option<usize> {
+    let config = fs::read_to_string(path).ok()?;
+    let command_count = migration::parse_command_definitions(&path.to_string_lossy(), &config)
+        .ok()?
+        .len();
+
+    get_command_lines(&config, command_count)?
+        .get(index)
+        .copied()
+}
+
+/// The file the command is defined in, with its line if it can be found.
+fn get_definition_location(
+    command_definitions: &[CommandDefinition],
+    index: usize,
+    config_path: &str,
+    sources: &[SourceDefinition],
+    sources_path: &str,
+) -> String {
+    let command_definition = &command_definitions[index];
+
+    let location = match &command_definition.source {
+        // Local commands are listed first, in the order they are defined
+        None => Some((Path::new(config_path).to_path_buf(), Some(index))),
+        Some(source_name) => sources
+            .iter()
+            .find(|source| source.name() == *source_name)
+            .map(|source| {
+                let commands_path = source.commands_path(sources_path);
+
+                // Some of the source's commands may have been left out, so find it again
+                let source_index = fs::read_to_string(&commands_path)
+                    .ok()
+                    .and_then(|config| {
+                        migration::parse_command_definitions(
+                            &commands_path.to_string_lossy(),
+                            &config,
+                        )
+                        .ok()
+                    })
+                    .and_then(|source_command_definitions| {
+                        source_command_definitions
+                            .iter()
+                            .position(|source_command| {
+                                source_command.id == command_definition.id
+                                    && source_command.command == command_definition.command
+                            })
+                    });
+
+                (commands_path, source_index)
+            }),
+    };
+
+    match location {
+        Some((path, index)) => match index.and_then(|index| get_definition_line(&path, index)) {
+            Some(line) => format!("{}:{line}", path.display()),
+            None => path.display().to_string(),
+        },
+        None => "unknown".to_string(),
+    }
+}
+
+/// Print where the command is defined, its parameters and what it would run with their defaults.
+pub fn print_command_info(
+    command_definitions: &[CommandDefinition],
+    index: usize,
+    config_path: &str,
+    profile: Option<(&str, &ProfileDefinition)>,
+    sources: &[SourceDefinition],
+    sources_path: &str,
+) -> Result<()> {
+    let command_definition = &command_definitions[index];
+
+    let mut execution_context =
+        CommandExecutionTemplate::from_command_definition(command_definition);
+    if let Some((_, profile)) = profile {
+        profile.apply_environment(&mut execution_context.environment);
+    }
+
+    if let Some(name) = &command_definition.name {
+        println!("Name: {name}");
+    }
+
+    if let Some(id) = &command_definition.id {
+        println!("ID: {id}");
+    }
+
+    if let Some(aliases) = &command_definition.aliases {
+        println!("Aliases: {}", aliases.join(", "));
+    }
+
+    if let Some(extends) = &command_definition.extends {
+        println!("Extends: {extends}");
+    }
+
+    println!("Index: {index}");
+    println!(
+        "Defined in: {}",
+        get_definition_location(
+            command_definitions,
+            index,
+            config_path,
+            sources,
+            sources_path
+        )
+    );
+
+    if let Some(source) = &command_definition.source {
+        println!("Source: {source}");
+    }
+
+    if let Some((profile_name, _)) = profile {
+        println!("Profile: {profile_name}");
+    }
+
+    let template = PreparedTemplate::parse(
+        &execution_context.command,
+        execution_context.working_directory.as_ref(),
+    )?;
+    let tokens = template.tokens();
+
+    // Parameters without a default are left as their template token
+    let defaults = build_default_lookup(&command_definition.parameters).unwrap_or_default();
+    let context: HashMap<String, String> = tokens
+        .iter()
+        .map(|token| {
+            let value = defaults
+                .get(token)
+                .cloned()
+                .unwrap_or_else(|| format!("{{{token}}}"));
+            (token.clone(), value)
+        })
+        .collect();
+
+    if !tokens.is_empty() {
+        let parameters = tokens
+            .iter()
+            .sorted()
+            .map(|token| match defaults.get(token) {
+                Some(default) => format!("{token} [{default}]"),
+                None => token.clone(),
+            })
+            .join(", ");
+        println!("Parameters: {parameters}");
+    }
+
+    let context = Some(context);
+
+    if let Some(working_directory) = template.render_working_directory(&context)? {
+        println!("Working directory: {working_directory}");
+    }
+
+    if let Some(environment) = &execution_context.environment {
+        let environment = environment
+            .iter()
+            .sorted()
+            .map(|(key, value)| format!("{key}={value}"))
+            .join(" ");
+        println!("Environment: {environment}");
+    }
+
+    println!("Command: {}", template.render_command(&context)?);
+
+    Ok(())
+}